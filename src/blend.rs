@@ -0,0 +1,292 @@
+use crate::{
+    node_graph_spatial::{NodeGraphSpatial, NodeSettings},
+    subgraph::passthrough,
+};
+use kanter_core::{
+    node::{MixType, NodeType},
+    node_graph::{Edge as CoreEdge, NodeId},
+};
+use serde::{Deserialize, Serialize};
+
+/// How far below the blended height the base can be and still show through in a height blend.
+const HEIGHT_BLEND_SOFTNESS: f32 = 0.1;
+
+/// How a Mix node combines its base input with the blended input.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BlendMode {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Overlay,
+    Screen,
+    /// Fades from the base to the blended input by a third mask input.
+    Lerp,
+    Min,
+    Max,
+    Difference,
+    SoftLight,
+    /// Treats both inputs as heights and keeps the higher one, with a soft transition where they
+    /// are close.
+    HeightBlend,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        Self::Add
+    }
+}
+
+impl BlendMode {
+    /// Every blend mode, in the order they are listed in the property menu.
+    pub const ALL: [BlendMode; 12] = [
+        BlendMode::Add,
+        BlendMode::Subtract,
+        BlendMode::Multiply,
+        BlendMode::Divide,
+        BlendMode::Overlay,
+        BlendMode::Screen,
+        BlendMode::Lerp,
+        BlendMode::Min,
+        BlendMode::Max,
+        BlendMode::Difference,
+        BlendMode::SoftLight,
+        BlendMode::HeightBlend,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Add => "Add",
+            Self::Subtract => "Subtract",
+            Self::Multiply => "Multiply",
+            Self::Divide => "Divide",
+            Self::Overlay => "Overlay",
+            Self::Screen => "Screen",
+            Self::Lerp => "Lerp",
+            Self::Min => "Min",
+            Self::Max => "Max",
+            Self::Difference => "Difference",
+            Self::SoftLight => "Soft light",
+            Self::HeightBlend => "Height blend",
+        }
+    }
+
+    /// Returns the `MixType` doing the same thing in `kanter_core`, if there is one.
+    fn mix_type(self) -> Option<MixType> {
+        match self {
+            Self::Add => Some(MixType::Add),
+            Self::Subtract => Some(MixType::Subtract),
+            Self::Multiply => Some(MixType::Multiply),
+            Self::Divide => Some(MixType::Divide),
+            _ => None,
+        }
+    }
+
+    fn from_mix_type(mix_type: MixType) -> Self {
+        match mix_type {
+            MixType::Add => Self::Add,
+            MixType::Subtract => Self::Subtract,
+            MixType::Multiply => Self::Multiply,
+            MixType::Divide => Self::Divide,
+        }
+    }
+
+    /// The number of inputs a Mix node with this blend mode has, the base and blended input
+    /// followed by the mask for lerps.
+    pub fn input_count(self) -> usize {
+        match self {
+            Self::Lerp => 3,
+            _ => 2,
+        }
+    }
+
+    /// Blends a single value of the blended input onto the base.
+    fn blend(self, base: f32, blend: f32, mask: f32) -> f32 {
+        match self {
+            Self::Add => base + blend,
+            Self::Subtract => base - blend,
+            Self::Multiply => base * blend,
+            Self::Divide => {
+                if blend == 0. {
+                    0.
+                } else {
+                    base / blend
+                }
+            }
+            Self::Overlay => {
+                if base < 0.5 {
+                    2. * base * blend
+                } else {
+                    1. - 2. * (1. - base) * (1. - blend)
+                }
+            }
+            Self::Screen => 1. - (1. - base) * (1. - blend),
+            Self::Lerp => base + (blend - base) * mask,
+            Self::Min => base.min(blend),
+            Self::Max => base.max(blend),
+            Self::Difference => (base - blend).abs(),
+            // The W3C formula, which unlike Photoshop's is continuous.
+            Self::SoftLight => {
+                if blend <= 0.5 {
+                    base - (1. - 2. * blend) * base * (1. - base)
+                } else {
+                    let darkened = if base <= 0.25 {
+                        ((16. * base - 12.) * base + 4.) * base
+                    } else {
+                        base.sqrt()
+                    };
+                    base + (2. * blend - 1.) * (darkened - base)
+                }
+            }
+            Self::HeightBlend => {
+                let amount = ((blend - base) / HEIGHT_BLEND_SOFTNESS * 0.5 + 0.5)
+                    .max(0.)
+                    .min(1.);
+                base + (blend - base) * amount
+            }
+        }
+    }
+}
+
+/// The settings of a Mix node. Only Mix nodes `kanter_core` can't process on its own, because
+/// of their blend mode or opacity, have their settings stored. In the `NodeGraph` they are Mix
+/// nodes when `kanter_core` has their blend mode, and Graph nodes otherwise, and processing
+/// replaces them with the blended result between passes like filter nodes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MixNode {
+    pub blend_mode: BlendMode,
+    /// How much of the blended result is laid over the base, from 0 to 1.
+    pub opacity: f32,
+}
+
+impl MixNode {
+    /// Whether `kanter_core` processes the node as a plain Mix node.
+    fn is_core(&self) -> bool {
+        self.blend_mode.mix_type().is_some() && (self.opacity - 1.).abs() < f32::EPSILON
+    }
+
+    /// Returns the `NodeType` the node has in the `NodeGraph`.
+    pub fn node_type(&self) -> NodeType {
+        match self.blend_mode.mix_type() {
+            Some(mix_type) => NodeType::Mix(mix_type),
+            None => passthrough(self.blend_mode.input_count(), 1),
+        }
+    }
+
+    /// Blends two channels of the same size. A missing blended input counts as black, a missing
+    /// mask as halfway.
+    pub fn apply(&self, base: &[f32], blend: Option<&[f32]>, mask: Option<&[f32]>) -> Vec<f32> {
+        base.iter()
+            .enumerate()
+            .map(|(i, base)| {
+                let blend = blend.map_or(0., |blend| blend[i]);
+                let mask = mask.map_or(0.5, |mask| mask[i]);
+                let blended = self.blend_mode.blend(*base, blend, mask);
+
+                base + (blended - base) * self.opacity
+            })
+            .collect()
+    }
+}
+
+/// Returns the settings of a node, if it is a Mix node.
+pub(crate) fn mix_node(node_graph_spatial: &NodeGraphSpatial, node_id: NodeId) -> Option<MixNode> {
+    if let Some(NodeSettings::Mix(mix_node)) = node_graph_spatial.node_settings.get(&node_id) {
+        return Some(mix_node.clone());
+    }
+
+    match node_graph_spatial
+        .node_graph
+        .node_with_id(node_id)?
+        .node_type
+    {
+        NodeType::Mix(mix_type) => Some(MixNode {
+            blend_mode: BlendMode::from_mix_type(mix_type),
+            opacity: 1.,
+        }),
+        _ => None,
+    }
+}
+
+/// Replaces the settings of a Mix node and changes its `NodeType` to match. Returns the edges into
+/// inputs the node no longer has, which are removed.
+pub(crate) fn set_mix_node(
+    node_graph_spatial: &mut NodeGraphSpatial,
+    node_id: NodeId,
+    mix_node: MixNode,
+) -> Vec<CoreEdge> {
+    let removed_edges = node_graph_spatial.set_node_type(node_id, mix_node.node_type());
+
+    if mix_node.is_core() {
+        node_graph_spatial.node_settings.remove(&node_id);
+    } else {
+        node_graph_spatial
+            .node_settings
+            .insert(node_id, NodeSettings::Mix(mix_node));
+    }
+
+    removed_edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} is not close to {}", a, b);
+    }
+
+    #[test]
+    fn blend_modes_match_their_formulas() {
+        let (base, blend) = (0.25, 0.5);
+
+        assert_close(BlendMode::Add.blend(base, blend, 0.), 0.75);
+        assert_close(BlendMode::Subtract.blend(base, blend, 0.), -0.25);
+        assert_close(BlendMode::Multiply.blend(base, blend, 0.), 0.125);
+        assert_close(BlendMode::Divide.blend(base, blend, 0.), 0.5);
+        assert_close(BlendMode::Screen.blend(base, blend, 0.), 0.625);
+        assert_close(BlendMode::Min.blend(base, blend, 0.), 0.25);
+        assert_close(BlendMode::Max.blend(base, blend, 0.), 0.5);
+        assert_close(BlendMode::Difference.blend(base, blend, 0.), 0.25);
+        assert_close(BlendMode::Lerp.blend(base, blend, 0.5), 0.375);
+    }
+
+    #[test]
+    fn dividing_by_zero_gives_black() {
+        assert_eq!(BlendMode::Divide.blend(0.5, 0., 0.), 0.);
+    }
+
+    #[test]
+    fn overlay_and_soft_light_are_continuous() {
+        let jump = |mode: BlendMode, base: f32, blend: f32, base_step: f32, blend_step: f32| {
+            let below = mode.blend(base - base_step, blend - blend_step, 0.);
+            let above = mode.blend(base + base_step, blend + blend_step, 0.);
+            (below - above).abs()
+        };
+
+        for value in &[0., 0.3, 0.7, 1.] {
+            // Where the formulas switch between their two halves.
+            assert!(jump(BlendMode::Overlay, 0.5, *value, 1e-4, 0.) < 1e-2);
+            assert!(jump(BlendMode::SoftLight, *value, 0.5, 0., 1e-4) < 1e-2);
+            assert!(jump(BlendMode::SoftLight, 0.25, *value, 1e-4, 0.) < 1e-2);
+        }
+    }
+
+    #[test]
+    fn height_blend_keeps_the_higher_input() {
+        assert_close(BlendMode::HeightBlend.blend(0.8, 0.2, 0.), 0.8);
+        assert_close(BlendMode::HeightBlend.blend(0.2, 0.8, 0.), 0.8);
+        assert_close(BlendMode::HeightBlend.blend(0.5, 0.5, 0.), 0.5);
+    }
+
+    #[test]
+    fn opacity_fades_towards_the_base() {
+        let mix_node = MixNode {
+            blend_mode: BlendMode::Add,
+            opacity: 0.5,
+        };
+
+        let blended = mix_node.apply(&[0.25], Some(&[0.5][..]), None);
+        assert_close(blended[0], 0.5);
+    }
+}
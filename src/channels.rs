@@ -0,0 +1,246 @@
+use crate::{
+    connection::SlotType,
+    node_graph_spatial::{NodeGraphSpatial, NodeSettings},
+    shared::WidgetSide,
+    subgraph::passthrough,
+};
+use kanter_core::{
+    node::{NodeType, Side},
+    node_graph::{NodeId, SlotId},
+};
+use serde::{Deserialize, Serialize};
+
+/// The number of channels in an RGBA texture, and of inputs and outputs on channel nodes.
+pub const CHANNEL_COUNT: usize = 4;
+const CHANNEL_LETTERS: [char; CHANNEL_COUNT] = ['R', 'G', 'B', 'A'];
+
+/// Whether a channel node pulls an RGBA texture apart into gray channels or packs gray channels
+/// into an RGBA texture. A Split node has one RGBA input and four gray outputs, a Merge node four
+/// gray inputs and one RGBA output. In the `NodeGraph` both have a slot per channel on each side,
+/// the RGBA slot is shown as one slot and connected as a unit.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ChannelNodeKind {
+    Split,
+    Merge,
+}
+
+pub type OptionChannelNodeKind = Option<ChannelNodeKind>;
+
+impl ChannelNodeKind {
+    pub const ALL: [ChannelNodeKind; 2] = [ChannelNodeKind::Split, ChannelNodeKind::Merge];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Split => "Split RGBA",
+            Self::Merge => "Merge RGBA",
+        }
+    }
+
+    /// Returns the type of data the slots on the given side take or give.
+    pub fn slot_type(self, side: WidgetSide) -> SlotType {
+        match (self, side) {
+            (Self::Split, WidgetSide::Input) | (Self::Merge, WidgetSide::Output) => SlotType::Rgba,
+            _ => SlotType::Gray,
+        }
+    }
+
+    /// Returns the number of slots shown on the given side.
+    pub fn slot_count(self, side: WidgetSide) -> usize {
+        match self.slot_type(side) {
+            SlotType::Rgba => 1,
+            _ => CHANNEL_COUNT,
+        }
+    }
+}
+
+/// Returns the `NodeType` channel nodes have in the `NodeGraph`: a graph passing each input
+/// straight through to an output.
+pub fn channel_node_type() -> NodeType {
+    passthrough(CHANNEL_COUNT, CHANNEL_COUNT)
+}
+
+/// The settings of a Split RGBA or Merge RGBA node. In the `NodeGraph` the node is a Graph node
+/// passing its inputs straight through, processing replaces it with an RGBA image of the routed
+/// channels between passes like filter nodes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelNode {
+    pub kind: ChannelNodeKind,
+    /// The input each output takes its channel from, `[0, 1, 2, 3]` leaves them in order.
+    pub swizzle: [usize; CHANNEL_COUNT],
+    /// The value of each input while nothing is connected to it. They belong to the inputs, so
+    /// an output gets the fallback of the input the swizzle takes its channel from.
+    pub fallbacks: [f32; CHANNEL_COUNT],
+}
+
+impl ChannelNode {
+    pub fn new(kind: ChannelNodeKind) -> Self {
+        Self {
+            kind,
+            swizzle: [0, 1, 2, 3],
+            fallbacks: [0., 0., 0., 1.],
+        }
+    }
+
+    /// Returns the swizzle written as letters, like "RGBA".
+    pub fn swizzle_text(&self) -> String {
+        self.swizzle
+            .iter()
+            .map(|input| CHANNEL_LETTERS[*input])
+            .collect()
+    }
+
+    /// Reads a swizzle written as four of the letters R, G, B and A, in any case.
+    pub fn parse_swizzle(text: &str) -> Option<[usize; CHANNEL_COUNT]> {
+        let inputs: Vec<usize> = text
+            .trim()
+            .chars()
+            .map(|letter| {
+                CHANNEL_LETTERS
+                    .iter()
+                    .position(|channel_letter| *channel_letter == letter.to_ascii_uppercase())
+            })
+            .collect::<Option<_>>()?;
+
+        match inputs[..] {
+            [red, green, blue, alpha] => Some([red, green, blue, alpha]),
+            _ => None,
+        }
+    }
+
+    /// Routes the input channels to the outputs. Inputs that are `None` are filled with their
+    /// fallback value, wherever the swizzle sends them.
+    pub fn apply(&self, pixel_count: usize, inputs: &[Option<Vec<f32>>]) -> Vec<Vec<f32>> {
+        self.swizzle
+            .iter()
+            .map(|input| match inputs.get(*input) {
+                Some(Some(values)) => values.clone(),
+                _ => vec![self.fallbacks[*input]; pixel_count],
+            })
+            .collect()
+    }
+}
+
+/// Returns the settings of a node, if it is a channel node.
+pub(crate) fn channel_node(
+    node_graph_spatial: &NodeGraphSpatial,
+    node_id: NodeId,
+) -> Option<&ChannelNode> {
+    match node_graph_spatial.node_settings.get(&node_id)? {
+        NodeSettings::Channels(channel_node) => Some(channel_node),
+        _ => None,
+    }
+}
+
+/// Replaces the settings of a channel node.
+pub(crate) fn set_channel_node(
+    node_graph_spatial: &mut NodeGraphSpatial,
+    node_id: NodeId,
+    channel_node: ChannelNode,
+) {
+    node_graph_spatial
+        .node_settings
+        .insert(node_id, NodeSettings::Channels(channel_node));
+}
+
+/// Whether the node shows the slots on the given side as one RGBA slot.
+fn is_bundled_side(
+    node_graph_spatial: &NodeGraphSpatial,
+    node_id: NodeId,
+    side: WidgetSide,
+) -> bool {
+    channel_node(node_graph_spatial, node_id).map_or(false, |channel_node| {
+        channel_node.kind.slot_type(side) == SlotType::Rgba
+    })
+}
+
+/// Whether an edge carries one of the channels after the first into the RGBA input of a Split
+/// node, or out of the RGBA output of a Merge node. Those edges follow the edge of the first
+/// channel and are not shown.
+pub(crate) fn is_bundled(
+    node_graph_spatial: &NodeGraphSpatial,
+    output: (NodeId, SlotId),
+    input: (NodeId, SlotId),
+) -> bool {
+    let ((output_id, output_slot), (input_id, input_slot)) = (output, input);
+
+    (output_slot.0 > 0 && is_bundled_side(node_graph_spatial, output_id, WidgetSide::Output))
+        || (input_slot.0 > 0 && is_bundled_side(node_graph_spatial, input_id, WidgetSide::Input))
+}
+
+/// Returns the slots of the channels that go along with an edge into the RGBA input of a Split
+/// node or out of the RGBA output of a Merge node, each connecting the same slot on both nodes.
+/// An RGBA output is connected as a unit from its first slot, to a node with a slot for every
+/// channel.
+pub(crate) fn bundled_slots(
+    node_graph_spatial: &NodeGraphSpatial,
+    output: (NodeId, SlotId),
+    input: (NodeId, SlotId),
+) -> Vec<SlotId> {
+    let ((output_id, output_slot), (input_id, input_slot)) = (output, input);
+
+    let bundled = is_bundled_side(node_graph_spatial, output_id, WidgetSide::Output)
+        || is_bundled_side(node_graph_spatial, input_id, WidgetSide::Input);
+    if !bundled || output_slot.0 != 0 || input_slot.0 != 0 {
+        return Vec::new();
+    }
+
+    let capacity = |node_id: NodeId, side: Side| {
+        node_graph_spatial
+            .node_graph
+            .node_with_id(node_id)
+            .map_or(0, |node| node.capacity(side))
+    };
+    if capacity(output_id, Side::Output) < CHANNEL_COUNT
+        || capacity(input_id, Side::Input) < CHANNEL_COUNT
+    {
+        return Vec::new();
+    }
+
+    (1..CHANNEL_COUNT as u32).map(SlotId).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_swizzle_reads_four_letters() {
+        assert_eq!(ChannelNode::parse_swizzle("RGBA"), Some([0, 1, 2, 3]));
+        assert_eq!(ChannelNode::parse_swizzle(" bgra "), Some([2, 1, 0, 3]));
+        assert_eq!(ChannelNode::parse_swizzle("aaaR"), Some([3, 3, 3, 0]));
+    }
+
+    #[test]
+    fn parse_swizzle_rejects_other_text() {
+        assert_eq!(ChannelNode::parse_swizzle("RGB"), None);
+        assert_eq!(ChannelNode::parse_swizzle("RGBAA"), None);
+        assert_eq!(ChannelNode::parse_swizzle("RGBX"), None);
+        assert_eq!(ChannelNode::parse_swizzle(""), None);
+    }
+
+    #[test]
+    fn swizzle_text_round_trips() {
+        let mut channel_node = ChannelNode::new(ChannelNodeKind::Merge);
+        channel_node.swizzle = [3, 0, 0, 2];
+
+        let text = channel_node.swizzle_text();
+        assert_eq!(text, "ARRB");
+        assert_eq!(
+            ChannelNode::parse_swizzle(&text),
+            Some(channel_node.swizzle)
+        );
+    }
+
+    #[test]
+    fn apply_routes_inputs_and_fills_in_fallbacks() {
+        let mut channel_node = ChannelNode::new(ChannelNodeKind::Split);
+        channel_node.swizzle = [2, 1, 0, 3];
+        channel_node.fallbacks = [0.1, 0.2, 0.3, 0.4];
+        let red = vec![1., 0.5];
+        let blue = vec![0., 0.25];
+
+        let outputs = channel_node.apply(2, &[Some(red.clone()), None, Some(blue.clone())]);
+
+        assert_eq!(outputs, vec![blue, vec![0.2; 2], red, vec![0.4; 2]]);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    generator::unbaked,
+    node_graph_spatial::{Location, NodeGraphSpatial},
+};
+use clipboard::{ClipboardContext, ClipboardProvider};
+use kanter_core::{
+    node::{Node as CoreNode, Side},
+    node_graph::NodeId,
+};
+
+/// Returns a `NodeGraphSpatial` containing copies of the given nodes, their locations, and the
+/// edges between them.
+pub(crate) fn copy_nodes(source: &NodeGraphSpatial, node_ids: &[NodeId]) -> NodeGraphSpatial {
+    let mut clip = NodeGraphSpatial::default();
+    append_nodes(&mut clip, source, node_ids, (0., 0.));
+    clip
+}
+
+/// Adds everything in `clip` to `target`, placing the top left node at `position`. Returns the
+/// ids the nodes got in `target`.
+pub(crate) fn paste_nodes(
+    target: &mut NodeGraphSpatial,
+    clip: &NodeGraphSpatial,
+    position: (f64, f64),
+) -> Vec<NodeId> {
+    let top_left = top_left(clip);
+    let offset = (position.0 - top_left.0, position.1 - top_left.1);
+
+    append_nodes(target, clip, &clip.node_graph.node_ids(), offset)
+        .into_iter()
+        .map(|(_, new_node_id)| new_node_id)
+        .collect()
+}
+
+/// Returns the location of the top left corner of all nodes in the clip.
+pub(crate) fn top_left(clip: &NodeGraphSpatial) -> (f64, f64) {
+    clip.locations
+        .iter()
+        .fold(None, |top_left: Option<(f64, f64)>, location| {
+            Some(match top_left {
+                Some((x, y)) => (x.min(location.point.0), y.min(location.point.1)),
+                None => location.point,
+            })
+        })
+        .unwrap_or((0., 0.))
+}
+
+/// Copies the given nodes and the edges between them from `source` into `target`, moving them
+/// by `offset`. Nodes get new ids in `target`, the returned pairs map old ids to new ones.
+pub(crate) fn append_nodes(
+    target: &mut NodeGraphSpatial,
+    source: &NodeGraphSpatial,
+    node_ids: &[NodeId],
+    offset: (f64, f64),
+) -> Vec<(NodeId, NodeId)> {
+    let mut id_map: Vec<(NodeId, NodeId)> = Vec::new();
+
+    for node_id in node_ids {
+        let node = match source.node_graph.node_with_id(*node_id) {
+            Some(node) => node,
+            None => continue,
+        };
+        let new_node_id = match target
+            .node_graph
+            .add_node(CoreNode::new(node.node_type.clone()))
+        {
+            Ok(new_node_id) => new_node_id,
+            Err(_) => continue,
+        };
+
+        let point = source
+            .locations
+            .iter()
+            .find(|location| location.node_id == *node_id)
+            .map_or((0., 0.), |location| location.point);
+        target.locations.push(Location {
+            node_id: new_node_id,
+            point: (point.0 + offset.0, point.1 + offset.1),
+        });
+
+        if let Some(node_settings) = source.node_settings.get(node_id) {
+            target
+                .node_settings
+                .insert(new_node_id, node_settings.clone());
+        }
+
+        id_map.push((*node_id, new_node_id));
+    }
+
+    let new_id = |node_id: NodeId| {
+        id_map
+            .iter()
+            .find(|(old_node_id, _)| *old_node_id == node_id)
+            .map(|(_, new_node_id)| *new_node_id)
+    };
+
+    for edge in &source.node_graph.edges {
+        if let (Some(output_id), Some(input_id)) = (new_id(edge.output_id), new_id(edge.input_id))
+        {
+            let _ = target.node_graph.connect_arbitrary(
+                output_id,
+                Side::Output,
+                edge.output_slot,
+                input_id,
+                Side::Input,
+                edge.input_slot,
+            );
+        }
+    }
+
+    id_map
+}
+
+/// Puts the clip on the system clipboard as JSON text, so it can be pasted in another instance.
+pub(crate) fn to_system_clipboard(clip: &NodeGraphSpatial) -> bool {
+    let json = match serde_json::to_string(&unbaked(clip)) {
+        Ok(json) => json,
+        Err(_) => return false,
+    };
+
+    let clipboard: Result<ClipboardContext, _> = ClipboardProvider::new();
+    match clipboard {
+        Ok(mut clipboard) => clipboard.set_contents(json).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Returns the clip on the system clipboard, if there is one.
+pub(crate) fn from_system_clipboard() -> Option<NodeGraphSpatial> {
+    let clipboard: Result<ClipboardContext, _> = ClipboardProvider::new();
+    let json = clipboard.ok()?.get_contents().ok()?;
+
+    serde_json::from_str(&json).ok()
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::PathBuf};
+
+const RECENT_FILES_LENGTH: usize = 10;
+
+/// Settings that are kept between sessions, stored as JSON in the user's config directory.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// Graph files that were recently opened or saved, the most recent first.
+    #[serde(default)]
+    pub recent_files: Vec<String>,
+}
+
+/// Returns the directory Kanter keeps its settings and recovery file in.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("kanter"))
+}
+
+impl Config {
+    fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("config.json"))
+    }
+
+    /// Loads the config, falling back to the defaults if there is none or it can't be read.
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Moves `path` to the top of the recent files, forgetting the oldest ones beyond the limit.
+    pub fn add_recent_file(&mut self, path: &str) {
+        self.recent_files.retain(|recent_file| recent_file != path);
+        self.recent_files.insert(0, path.to_string());
+        self.recent_files.truncate(RECENT_FILES_LENGTH);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{
+    channels::channel_node, generator::generator, node_graph_spatial::NodeGraphSpatial, shared::*,
+};
+use kanter_core::{
+    node::NodeType,
+    node_graph::{NodeGraph, NodeId, SlotId},
+};
+use orbtk::prelude::*;
+use std::fmt;
+
+/// The kind of data that flows through a slot.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SlotType {
+    Gray,
+    Rgba,
+    /// The node works on whatever it is given, like `Mix` and `Resize`.
+    Any,
+}
+into_property_source!(SlotType);
+
+impl Default for SlotType {
+    fn default() -> Self {
+        Self::Any
+    }
+}
+
+impl SlotType {
+    /// Whether an output slot of this type can feed an input slot of the `input` type. Each slot
+    /// of an RGBA output carries one of its channels, so it can feed a gray input.
+    pub fn is_compatible(self, input: SlotType) -> bool {
+        self == SlotType::Any
+            || input == SlotType::Any
+            || self == input
+            || (self == SlotType::Rgba && input == SlotType::Gray)
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Self::Gray => Color::rgb(150, 150, 150),
+            Self::Rgba => Color::rgb(230, 130, 60),
+            Self::Any => Color::rgb(220, 220, 220),
+        }
+    }
+}
+
+impl fmt::Display for SlotType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gray => write!(f, "gray"),
+            Self::Rgba => write!(f, "RGBA"),
+            Self::Any => write!(f, "any"),
+        }
+    }
+}
+
+/// Returns the type of data a slot on a node of the given type takes or gives.
+pub fn slot_type(node_type: &NodeType, side: WidgetSide, _slot_id: u32) -> SlotType {
+    match (node_type, side) {
+        (NodeType::InputGray, WidgetSide::Output)
+        | (NodeType::OutputGray, WidgetSide::Input)
+        | (NodeType::Value(_), WidgetSide::Output)
+        | (NodeType::HeightToNormal, WidgetSide::Input) => SlotType::Gray,
+        (NodeType::InputRgba, WidgetSide::Output)
+        | (NodeType::OutputRgba, WidgetSide::Input)
+        | (NodeType::Image(_), WidgetSide::Output)
+        | (NodeType::HeightToNormal, WidgetSide::Output) => SlotType::Rgba,
+        _ => SlotType::Any,
+    }
+}
+
+/// Returns the type of data a slot on a node in the graph takes or gives. Unlike `slot_type` this
+/// knows that generator nodes, which are image nodes in the `NodeGraph`, give gray data, and
+/// which side of a channel node takes RGBA data.
+pub(crate) fn node_slot_type(
+    node_graph_spatial: &NodeGraphSpatial,
+    node_id: NodeId,
+    side: WidgetSide,
+    slot_id: u32,
+) -> SlotType {
+    if side == WidgetSide::Output && generator(node_graph_spatial, node_id).is_some() {
+        return SlotType::Gray;
+    }
+    if let Some(channel_node) = channel_node(node_graph_spatial, node_id) {
+        return channel_node.kind.slot_type(side);
+    }
+
+    node_graph_spatial
+        .node_graph
+        .node_with_id(node_id)
+        .map_or(SlotType::default(), |node| {
+            slot_type(&node.node_type, side, slot_id)
+        })
+}
+
+/// Why an edge can not be created between two slots.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConnectionError {
+    SameSide,
+    SameNode,
+    Type(SlotType, SlotType),
+    Cycle,
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SameSide => write!(f, "an output has to be connected to an input"),
+            Self::SameNode => write!(f, "a node can not be connected to itself"),
+            Self::Type(output, input) => write!(
+                f,
+                "a {} output can not be connected to a {} input",
+                output, input
+            ),
+            Self::Cycle => write!(f, "the connection would create a cycle"),
+        }
+    }
+}
+
+/// Checks whether an edge from the given output slot to the given input slot can be added to the
+/// graph.
+pub(crate) fn check_connection(
+    node_graph_spatial: &NodeGraphSpatial,
+    output: (NodeId, SlotId),
+    input: (NodeId, SlotId),
+) -> Result<(), ConnectionError> {
+    let (output_id, output_slot) = output;
+    let (input_id, input_slot) = input;
+    let node_graph = &node_graph_spatial.node_graph;
+
+    if output_id == input_id {
+        return Err(ConnectionError::SameNode);
+    }
+
+    if node_graph.node_with_id(output_id).is_some() && node_graph.node_with_id(input_id).is_some()
+    {
+        let output_type =
+            node_slot_type(node_graph_spatial, output_id, WidgetSide::Output, output_slot.0);
+        let input_type =
+            node_slot_type(node_graph_spatial, input_id, WidgetSide::Input, input_slot.0);
+
+        if !output_type.is_compatible(input_type) {
+            return Err(ConnectionError::Type(output_type, input_type));
+        }
+    }
+
+    if is_downstream(node_graph, input_id, output_id) {
+        return Err(ConnectionError::Cycle);
+    }
+
+    Ok(())
+}
+
+/// Whether `target` can be reached by following edges from the outputs of `start`.
+pub fn is_downstream(node_graph: &NodeGraph, start: NodeId, target: NodeId) -> bool {
+    let mut visited: Vec<NodeId> = Vec::new();
+    let mut to_visit = vec![start];
+
+    while let Some(node_id) = to_visit.pop() {
+        if node_id == target {
+            return true;
+        }
+        if visited.contains(&node_id) {
+            continue;
+        }
+        visited.push(node_id);
+
+        to_visit.extend(
+            node_graph
+                .edges
+                .iter()
+                .filter(|edge| edge.output_id == node_id)
+                .map(|edge| edge.input_id),
+        );
+    }
+
+    false
+}
@@ -0,0 +1,202 @@
+use crate::filter::Curve;
+use orbtk::{prelude::*, shell::MouseButton};
+use std::cell::Cell;
+
+const EDITOR_WIDTH: f64 = 190.;
+const EDITOR_HEIGHT: f64 = 120.;
+/// How close to a point the mouse has to be to grab it, in pixels.
+const POINT_HIT_DISTANCE: f64 = 6.;
+const POINT_SIZE: f64 = 6.;
+/// How many straight lines the curve is drawn with.
+const DRAW_SEGMENTS: usize = 64;
+const BACKGROUND_COLOR: Color = Color::rgb(40, 40, 40);
+const GUIDE_COLOR: Color = Color::rgb(70, 70, 70);
+const CURVE_COLOR: Color = Color::rgb(220, 220, 220);
+
+into_property_source!(Curve);
+
+#[derive(Copy, Clone, Debug)]
+enum CurveMouse {
+    Press(Mouse),
+    Release,
+    Move(Point),
+}
+
+// Edits a curve by dragging its points. Clicking between points adds one, right clicking a point
+// removes it.
+widget!(
+    CurveEditor<CurveEditorState>: MouseHandler {
+        curve: Curve,
+        origin: Point
+    }
+);
+
+impl Template for CurveEditor {
+    fn template(self, id: Entity, _ctx: &mut BuildContext) -> Self {
+        self.name("CurveEditor")
+            .curve(Curve::default())
+            .origin(Point::default())
+            .width(EDITOR_WIDTH)
+            .height(EDITOR_HEIGHT)
+            .margin((5., 5., 5., 5.))
+            .on_mouse_down(move |states, m| {
+                states
+                    .get::<CurveEditorState>(id)
+                    .mouse(CurveMouse::Press(m));
+                true
+            })
+            .on_mouse_up(move |states, _| {
+                states
+                    .get::<CurveEditorState>(id)
+                    .mouse(CurveMouse::Release);
+                true
+            })
+            .on_mouse_move(move |states, p| {
+                states
+                    .get::<CurveEditorState>(id)
+                    .mouse(CurveMouse::Move(p));
+                true
+            })
+    }
+
+    fn render_object(&self) -> Box<dyn RenderObject> {
+        Box::new(CurveEditorRenderObject)
+    }
+}
+
+#[derive(Default, AsAny)]
+pub struct CurveEditorState {
+    mouse: Cell<Option<CurveMouse>>,
+    /// The index of the point being dragged.
+    dragged_point: Option<usize>,
+}
+
+impl State for CurveEditorState {
+    fn update(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
+        let mouse = match self.mouse.take() {
+            Some(mouse) => mouse,
+            None => return,
+        };
+
+        let mut curve = ctx.widget().clone::<Curve>("curve");
+        let origin = *ctx.widget().get::<Point>("origin");
+        let to_curve = |position: Point| {
+            (
+                ((position.x - origin.x) / EDITOR_WIDTH) as f32,
+                (1. - (position.y - origin.y) / EDITOR_HEIGHT) as f32,
+            )
+        };
+
+        match mouse {
+            CurveMouse::Press(mouse) => {
+                let position = mouse.position;
+                let hit_point = curve.points.iter().position(|point| {
+                    to_editor(*point, origin).distance(position) <= POINT_HIT_DISTANCE
+                });
+
+                match (mouse.button, hit_point) {
+                    (MouseButton::Left, Some(index)) => self.dragged_point = Some(index),
+                    (MouseButton::Left, None) => {
+                        self.dragged_point = curve.insert_point(to_curve(position));
+                    }
+                    (MouseButton::Right, Some(index)) => curve.remove_point(index),
+                    _ => (),
+                }
+            }
+            CurveMouse::Release => self.dragged_point = None,
+            CurveMouse::Move(position) => {
+                if let Some(index) = self.dragged_point {
+                    curve.move_point(index, to_curve(position));
+                }
+            }
+        }
+
+        if curve != *ctx.widget().get::<Curve>("curve") {
+            ctx.widget().set::<Curve>("curve", curve);
+        }
+    }
+}
+
+impl CurveEditorState {
+    fn mouse(&self, mouse: CurveMouse) {
+        self.mouse.set(Some(mouse));
+    }
+}
+
+/// Returns where a point on the curve is drawn in an editor with its top left corner at `origin`.
+fn to_editor(point: (f32, f32), origin: Point) -> Point {
+    Point::new(
+        origin.x + point.0 as f64 * EDITOR_WIDTH,
+        origin.y + (1. - point.1 as f64) * EDITOR_HEIGHT,
+    )
+}
+
+pub struct CurveEditorRenderObject;
+
+impl RenderObject for CurveEditorRenderObject {
+    fn render_self(&self, ctx: &mut Context<'_>, global_position: &Point) {
+        let (bounds, curve, origin) = {
+            let widget = ctx.widget();
+            (
+                *widget.get::<Rectangle>("bounds"),
+                widget.clone::<Curve>("curve"),
+                *widget.get::<Point>("origin"),
+            )
+        };
+
+        let left = global_position.x + bounds.x();
+        let top = global_position.y + bounds.y();
+
+        // Mouse positions arrive in window coordinates, the state needs to know where the editor
+        // is to find the point under the cursor.
+        if origin != Point::new(left, top) {
+            ctx.widget().set::<Point>("origin", Point::new(left, top));
+        }
+        let origin = Point::new(left, top);
+
+        let rc2d = ctx.render_context_2_d();
+        rc2d.set_fill_style(Brush::SolidColor(BACKGROUND_COLOR));
+        rc2d.fill_rect(left, top, EDITOR_WIDTH, EDITOR_HEIGHT);
+
+        rc2d.begin_path();
+        rc2d.set_line_width(1.);
+        rc2d.set_stroke_style(Brush::SolidColor(GUIDE_COLOR));
+        for quarter in 1..4 {
+            let fraction = quarter as f64 / 4.;
+            rc2d.move_to(left + fraction * EDITOR_WIDTH, top);
+            rc2d.line_to(left + fraction * EDITOR_WIDTH, top + EDITOR_HEIGHT);
+            rc2d.move_to(left, top + fraction * EDITOR_HEIGHT);
+            rc2d.line_to(left + EDITOR_WIDTH, top + fraction * EDITOR_HEIGHT);
+        }
+        rc2d.move_to(left, top + EDITOR_HEIGHT);
+        rc2d.line_to(left + EDITOR_WIDTH, top);
+        rc2d.stroke();
+
+        rc2d.begin_path();
+        rc2d.set_line_width(2.);
+        rc2d.set_stroke_style(Brush::SolidColor(CURVE_COLOR));
+        for (i, value) in curve.sampled(DRAW_SEGMENTS + 1).into_iter().enumerate() {
+            let point = to_editor(
+                (i as f32 / DRAW_SEGMENTS as f32, value.max(0.).min(1.)),
+                origin,
+            );
+            if i == 0 {
+                rc2d.move_to(point.x, point.y);
+            } else {
+                rc2d.line_to(point.x, point.y);
+            }
+        }
+        rc2d.stroke();
+
+        rc2d.set_fill_style(Brush::SolidColor(CURVE_COLOR));
+        for point in &curve.points {
+            let point = to_editor(*point, origin);
+            rc2d.fill_rect(
+                point.x - POINT_SIZE / 2.,
+                point.y - POINT_SIZE / 2.,
+                POINT_SIZE,
+                POINT_SIZE,
+            );
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{line::Line, shared::*};
+use crate::{connection::SlotType, line::Line, shared::*, view::View};
 use orbtk::prelude::*;
 
 widget!(
@@ -9,7 +9,11 @@ widget!(
         output_node: u32,
         input_node: u32,
         output_slot: u32,
-        input_slot: u32
+        input_slot: u32,
+        view: View,
+        slot_type: SlotType,
+        hovered: bool,
+        selected: bool
     }
 );
 
@@ -18,10 +22,17 @@ impl Template for Edge {
         self.name("Edge")
             .id("edge")
             .widget_type(WidgetType::Edge)
+            .slot_type(SlotType::default())
+            .hovered(false)
+            .selected(false)
             .child(
                 Line::create()
                     .start_point(("output_point", id))
                     .end_point(("input_point", id))
+                    .view(("view", id))
+                    .slot_type(("slot_type", id))
+                    .hovered(("hovered", id))
+                    .selected(("selected", id))
                     .build(ctx),
             )
     }
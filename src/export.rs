@@ -0,0 +1,462 @@
+use crate::{
+    material_preview::MaterialMap,
+    node_graph_spatial::{NodeGraphSpatial, NodeSettings},
+    processing::{process_graph, NodeChannels, ProcessError},
+    shared::Indexable,
+};
+use image::{ImageBuffer, ImageFormat, Luma, Rgb, Rgba};
+use kanter_core::{node::NodeType, node_graph::NodeId};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+const DEFAULT_FILE_NAME: &str = "{graph}_{id}";
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    Png,
+    Tga,
+    Exr,
+    Tiff,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Tga => "tga",
+            Self::Exr => "exr",
+            Self::Tiff => "tif",
+        }
+    }
+
+    fn supports(self, bit_depth: BitDepth) -> bool {
+        match self {
+            Self::Png | Self::Tiff => bit_depth != BitDepth::Float32,
+            Self::Tga => bit_depth == BitDepth::Eight,
+            Self::Exr => bit_depth == BitDepth::Float32,
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Png => write!(f, "PNG"),
+            Self::Tga => write!(f, "TGA"),
+            Self::Exr => write!(f, "EXR"),
+            Self::Tiff => write!(f, "TIFF"),
+        }
+    }
+}
+
+impl Indexable for ExportFormat {
+    fn index(&self) -> usize {
+        match self {
+            Self::Png => 0,
+            Self::Tga => 1,
+            Self::Exr => 2,
+            Self::Tiff => 3,
+        }
+    }
+
+    fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(Self::Png),
+            1 => Some(Self::Tga),
+            2 => Some(Self::Exr),
+            3 => Some(Self::Tiff),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+    Float32,
+}
+
+impl fmt::Display for BitDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Eight => write!(f, "8 bit"),
+            Self::Sixteen => write!(f, "16 bit"),
+            Self::Float32 => write!(f, "32 bit float"),
+        }
+    }
+}
+
+impl Indexable for BitDepth {
+    fn index(&self) -> usize {
+        match self {
+            Self::Eight => 0,
+            Self::Sixteen => 1,
+            Self::Float32 => 2,
+        }
+    }
+
+    fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(Self::Eight),
+            1 => Some(Self::Sixteen),
+            2 => Some(Self::Float32),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ChannelLayout {
+    Gray,
+    Rgb,
+    Rgba,
+}
+
+impl ChannelLayout {
+    fn channel_count(self) -> usize {
+        match self {
+            Self::Gray => 1,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+}
+
+impl Indexable for ChannelLayout {
+    fn index(&self) -> usize {
+        match self {
+            Self::Gray => 0,
+            Self::Rgb => 1,
+            Self::Rgba => 2,
+        }
+    }
+
+    fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(Self::Gray),
+            1 => Some(Self::Rgb),
+            2 => Some(Self::Rgba),
+            _ => None,
+        }
+    }
+}
+
+/// How an output node is written to disk, and what it is shown as in the material preview.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OutputSettings {
+    /// The file name without extension. `{graph}` is replaced with the name of the graph file and
+    /// `{id}` with the id of the output node.
+    pub file_name: String,
+    pub format: ExportFormat,
+    pub bit_depth: BitDepth,
+    pub channels: ChannelLayout,
+    #[serde(default)]
+    pub preview_map: MaterialMap,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        Self {
+            file_name: DEFAULT_FILE_NAME.to_string(),
+            format: ExportFormat::Png,
+            bit_depth: BitDepth::Eight,
+            channels: ChannelLayout::Rgba,
+            preview_map: MaterialMap::None,
+        }
+    }
+}
+
+impl OutputSettings {
+    /// Returns where the output node is written in `dir`. File names that are empty or would
+    /// lead out of `dir` are refused.
+    fn path(&self, dir: &Path, graph_name: &str, node_id: NodeId) -> Result<PathBuf, ExportError> {
+        let file_name = self
+            .file_name
+            .replace("{graph}", graph_name)
+            .replace("{id}", &node_id.0.to_string());
+
+        // Both kinds of slashes are refused everywhere, so graphs export the same on every
+        // platform.
+        let mut components = Path::new(&file_name).components();
+        let is_plain = matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        );
+        if !is_plain || file_name.contains(&['/', '\\'][..]) {
+            return Err(ExportError::FileName(node_id.0, file_name));
+        }
+
+        Ok(dir.join(format!("{}.{}", file_name, self.format.extension())))
+    }
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(PathBuf, io::Error),
+    Image(PathBuf, image::ImageError),
+    Exr(PathBuf, exr::error::Error),
+    NoOutput(u32),
+    Unsupported(u32, ExportFormat, BitDepth),
+    FileName(u32, String),
+    /// Two output nodes would be written to the same file.
+    SamePath(u32, u32, PathBuf),
+    Process(ProcessError),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Image(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Exr(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::NoOutput(node_id) => write!(f, "output node {} produced no image", node_id),
+            Self::Unsupported(node_id, format, bit_depth) => write!(
+                f,
+                "output node {}: {} can not be written as {}",
+                node_id, format, bit_depth
+            ),
+            Self::FileName(node_id, file_name) => write!(
+                f,
+                "output node {}: \"{}\" is not a valid file name",
+                node_id, file_name
+            ),
+            Self::SamePath(node_id, other_node_id, path) => write!(
+                f,
+                "output nodes {} and {} would both be written to {}",
+                node_id,
+                other_node_id,
+                path.display()
+            ),
+            Self::Process(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+/// Returns the settings of the given output node, or the default settings if it has none yet.
+pub(crate) fn output_settings(
+    node_graph_spatial: &NodeGraphSpatial,
+    node_id: NodeId,
+) -> OutputSettings {
+    match node_graph_spatial.node_settings.get(&node_id) {
+        Some(NodeSettings::Output(output_settings)) => output_settings.clone(),
+        _ => OutputSettings::default(),
+    }
+}
+
+/// Replaces the settings of an output node.
+pub(crate) fn set_output_settings(
+    node_graph_spatial: &mut NodeGraphSpatial,
+    node_id: NodeId,
+    settings: OutputSettings,
+) {
+    node_graph_spatial
+        .node_settings
+        .insert(node_id, NodeSettings::Output(settings));
+}
+
+/// Processes the graph and writes every output node to `dir` according to its settings. Returns
+/// the paths of the written files.
+pub fn export_outputs(
+    node_graph_spatial: &NodeGraphSpatial,
+    dir: &Path,
+    graph_name: &str,
+) -> Result<Vec<PathBuf>, ExportError> {
+    let node_graph = &node_graph_spatial.node_graph;
+
+    let outputs: Vec<(NodeId, OutputSettings)> = node_graph
+        .node_ids()
+        .into_iter()
+        .filter(|node_id| {
+            node_graph.node_with_id(*node_id).map_or(false, |node| {
+                matches!(node.node_type, NodeType::OutputGray | NodeType::OutputRgba)
+            })
+        })
+        .map(|node_id| (node_id, output_settings(node_graph_spatial, node_id)))
+        .collect();
+
+    // Check everything that can be checked before spending time on processing.
+    let mut paths: Vec<(NodeId, PathBuf)> = Vec::with_capacity(outputs.len());
+    for (node_id, settings) in &outputs {
+        if !settings.format.supports(settings.bit_depth) {
+            return Err(ExportError::Unsupported(
+                node_id.0,
+                settings.format,
+                settings.bit_depth,
+            ));
+        }
+
+        let path = settings.path(dir, graph_name, *node_id)?;
+        if let Some((other_node_id, _)) = paths.iter().find(|(_, other)| *other == path) {
+            return Err(ExportError::SamePath(other_node_id.0, node_id.0, path));
+        }
+        paths.push((*node_id, path));
+    }
+
+    fs::create_dir_all(dir).map_err(|e| ExportError::Io(dir.to_owned(), e))?;
+
+    let mut processed = process_graph(node_graph_spatial);
+    if !processed.errors.is_empty() {
+        return Err(ExportError::Process(processed.errors.remove(0)));
+    }
+
+    let mut written = Vec::new();
+    for ((node_id, settings), (_, path)) in outputs.into_iter().zip(paths) {
+        let node_channels = processed
+            .node_channels(node_id)
+            .ok_or(ExportError::NoOutput(node_id.0))?;
+
+        write_output(&node_channels, &settings, &path)?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// Returns the pixels of the image interleaved in the given channel layout.
+fn samples(node_channels: &NodeChannels, channels: ChannelLayout) -> Vec<f32> {
+    let pixel_count = (node_channels.width * node_channels.height) as usize;
+    let mut samples = Vec::with_capacity(pixel_count * channels.channel_count());
+
+    for i in 0..pixel_count {
+        let (r, g, b, a) = node_channels.rgba(i);
+
+        match channels {
+            ChannelLayout::Gray => samples.push(0.2126 * r + 0.7152 * g + 0.0722 * b),
+            ChannelLayout::Rgb => samples.extend_from_slice(&[r, g, b]),
+            ChannelLayout::Rgba => samples.extend_from_slice(&[r, g, b, a]),
+        }
+    }
+
+    samples
+}
+
+fn write_output(
+    node_channels: &NodeChannels,
+    settings: &OutputSettings,
+    path: &Path,
+) -> Result<(), ExportError> {
+    let width = node_channels.width;
+    let height = node_channels.height;
+    let samples = samples(node_channels, settings.channels);
+
+    let image_format = match settings.format {
+        ExportFormat::Png => ImageFormat::Png,
+        ExportFormat::Tga => ImageFormat::Tga,
+        ExportFormat::Tiff => ImageFormat::Tiff,
+        ExportFormat::Exr => return write_exr(&samples, width, height, settings.channels, path),
+    };
+
+    let result = match settings.bit_depth {
+        BitDepth::Eight => {
+            let data: Vec<u8> = samples
+                .iter()
+                .map(|sample| (sample.max(0.).min(1.) * 255.).round() as u8)
+                .collect();
+            save(data, width, height, settings.channels, path, image_format)
+        }
+        BitDepth::Sixteen => {
+            let data: Vec<u16> = samples
+                .iter()
+                .map(|sample| (sample.max(0.).min(1.) * 65535.).round() as u16)
+                .collect();
+            save(data, width, height, settings.channels, path, image_format)
+        }
+        BitDepth::Float32 => unreachable!("only EXR supports 32 bit float"),
+    };
+
+    result.map_err(|e| ExportError::Image(path.to_owned(), e))
+}
+
+/// Saves interleaved 8 or 16 bit samples with the `image` crate.
+fn save<T>(
+    data: Vec<T>,
+    width: u32,
+    height: u32,
+    channels: ChannelLayout,
+    path: &Path,
+    image_format: ImageFormat,
+) -> image::ImageResult<()>
+where
+    T: image::Primitive + 'static,
+    [T]: image::EncodableLayout,
+{
+    match channels {
+        ChannelLayout::Gray => ImageBuffer::<Luma<T>, _>::from_raw(width, height, data)
+            .expect("buffer size matches the image size")
+            .save_with_format(path, image_format),
+        ChannelLayout::Rgb => ImageBuffer::<Rgb<T>, _>::from_raw(width, height, data)
+            .expect("buffer size matches the image size")
+            .save_with_format(path, image_format),
+        ChannelLayout::Rgba => ImageBuffer::<Rgba<T>, _>::from_raw(width, height, data)
+            .expect("buffer size matches the image size")
+            .save_with_format(path, image_format),
+    }
+}
+
+fn write_exr(
+    samples: &[f32],
+    width: u32,
+    height: u32,
+    channels: ChannelLayout,
+    path: &Path,
+) -> Result<(), ExportError> {
+    let channel_count = channels.channel_count();
+    let sample = |x: usize, y: usize, channel: usize| {
+        samples[(y * width as usize + x) * channel_count + channel.min(channel_count - 1)]
+    };
+
+    // EXR has no dedicated grayscale writer, so gray is written with the same value in each of
+    // the RGB channels.
+    let result = match channels {
+        ChannelLayout::Gray | ChannelLayout::Rgb => {
+            exr::prelude::write_rgb_file(path, width as usize, height as usize, |x, y| {
+                (sample(x, y, 0), sample(x, y, 1), sample(x, y, 2))
+            })
+        }
+        ChannelLayout::Rgba => {
+            exr::prelude::write_rgba_file(path, width as usize, height as usize, |x, y| {
+                (sample(x, y, 0), sample(x, y, 1), sample(x, y, 2), sample(x, y, 3))
+            })
+        }
+    };
+
+    result.map_err(|e| ExportError::Exr(path.to_owned(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(file_name: &str) -> Result<PathBuf, ExportError> {
+        let settings = OutputSettings {
+            file_name: file_name.to_string(),
+            ..OutputSettings::default()
+        };
+        settings.path(Path::new("export"), "graph", NodeId(3))
+    }
+
+    #[test]
+    fn path_fills_in_the_pattern() {
+        assert_eq!(
+            path("{graph}_{id}").unwrap(),
+            Path::new("export").join("graph_3.png")
+        );
+    }
+
+    #[test]
+    fn path_refuses_names_leaving_the_directory() {
+        for file_name in &["", "../x", "/tmp/x", "a/b", "a\\b", "..", "."] {
+            assert!(
+                matches!(path(file_name), Err(ExportError::FileName(3, _))),
+                "{:?} was accepted",
+                file_name
+            );
+        }
+    }
+}
@@ -0,0 +1,411 @@
+use crate::shared::*;
+use orbtk::prelude::*;
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+const BROWSER_WIDTH: f64 = 400.;
+const LIST_HEIGHT: f64 = 300.;
+const GRAPH_EXTENSIONS: &[&str] = &["json"];
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "tga", "tif", "tiff", "bmp"];
+
+/// What a file is being picked for.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FileBrowserMode {
+    Open,
+    Save,
+    Image,
+}
+into_property_source!(FileBrowserMode);
+
+impl Default for FileBrowserMode {
+    fn default() -> Self {
+        Self::Open
+    }
+}
+
+impl FileBrowserMode {
+    fn title(self) -> &'static str {
+        match self {
+            Self::Open => "Open graph",
+            Self::Save => "Save graph as",
+            Self::Image => "Choose image",
+        }
+    }
+
+    fn confirm_text(self) -> &'static str {
+        match self {
+            Self::Open => "Open",
+            Self::Save => "Save",
+            Self::Image => "Choose",
+        }
+    }
+
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Open | Self::Save => GRAPH_EXTENSIONS,
+            Self::Image => IMAGE_EXTENSIONS,
+        }
+    }
+}
+
+pub type OptionFileBrowserMode = Option<FileBrowserMode>;
+
+/// A command from one of the buttons in the file browser.
+#[derive(Clone, Debug)]
+enum BrowserCommand {
+    Up,
+    Enter(PathBuf),
+    Select(String),
+    Confirm,
+    Cancel,
+}
+
+// Lets the user pick a file without relying on the dialogs of the operating system. The chosen
+// file is sent to the `target` widget as an `ActionMain::FileChosen`.
+widget!(
+    FileBrowser<FileBrowserState> {
+        open: bool,
+        mode: FileBrowserMode,
+        directory: String16,
+        file_name: String16,
+        target: u32
+    }
+);
+
+impl Template for FileBrowser {
+    fn template(mut self, id: Entity, ctx: &mut BuildContext) -> Self {
+        let title = TextBlock::create()
+            .element("text-block")
+            .margin((5., 5., 5., 5.))
+            .text("")
+            .build(ctx);
+        let directory = TextBlock::create()
+            .element("text-block")
+            .margin((5., 5., 5., 5.))
+            .text(("directory", id))
+            .build(ctx);
+        let list = Stack::create().orientation("vertical").build(ctx);
+        let message = TextBlock::create()
+            .element("text-block")
+            .foreground("#ff6666")
+            .margin((5., 0., 5., 0.))
+            .text("")
+            .build(ctx);
+        let confirm = Button::create()
+            .element("button")
+            .on_click(move |states, _| {
+                states
+                    .get_mut::<FileBrowserState>(id)
+                    .command(BrowserCommand::Confirm);
+                true
+            })
+            .text("Open")
+            .width(100.)
+            .build(ctx);
+
+        let state = self.state_mut();
+        state.title = title;
+        state.list = list;
+        state.message = message;
+        state.confirm = confirm;
+
+        self.name("FileBrowser")
+            .open(false)
+            .mode(FileBrowserMode::default())
+            .directory("")
+            .file_name("")
+            .width(BROWSER_WIDTH)
+            .horizontal_alignment("center")
+            .vertical_alignment("center")
+            .visibility(Visibility::Collapsed)
+            .child(
+                Container::create()
+                    .background("#444444")
+                    .child(
+                        Stack::create()
+                            .orientation("vertical")
+                            .child(title)
+                            .child(
+                                Stack::create()
+                                    .orientation("horizontal")
+                                    .child(
+                                        Button::create()
+                                            .element("button")
+                                            .on_click(move |states, _| {
+                                                states
+                                                    .get_mut::<FileBrowserState>(id)
+                                                    .command(BrowserCommand::Up);
+                                                true
+                                            })
+                                            .text("Up")
+                                            .width(60.)
+                                            .build(ctx),
+                                    )
+                                    .child(directory)
+                                    .build(ctx),
+                            )
+                            .child(
+                                ScrollViewer::create()
+                                    .height(LIST_HEIGHT)
+                                    .child(list)
+                                    .build(ctx),
+                            )
+                            .child(
+                                TextBox::create()
+                                    .margin((5., 5., 5., 5.))
+                                    .water_mark("File name")
+                                    .text(("file_name", id))
+                                    .build(ctx),
+                            )
+                            .child(message)
+                            .child(
+                                Stack::create()
+                                    .orientation("horizontal")
+                                    .child(
+                                        Button::create()
+                                            .element("button")
+                                            .on_click(move |states, _| {
+                                                states
+                                                    .get_mut::<FileBrowserState>(id)
+                                                    .command(BrowserCommand::Cancel);
+                                                true
+                                            })
+                                            .text("Cancel")
+                                            .width(100.)
+                                            .build(ctx),
+                                    )
+                                    .child(confirm)
+                                    .build(ctx),
+                            )
+                            .build(ctx),
+                    )
+                    .build(ctx),
+            )
+    }
+}
+
+#[derive(Default, AsAny)]
+pub struct FileBrowserState {
+    command: Option<BrowserCommand>,
+    title: Entity,
+    list: Entity,
+    message: Entity,
+    confirm: Entity,
+    /// The directory and mode the list was last built for.
+    shown: Option<(String, FileBrowserMode)>,
+}
+
+impl State for FileBrowserState {
+    fn update(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
+        if !*ctx.widget().get::<bool>("open") {
+            ctx.widget()
+                .set::<Visibility>("visibility", Visibility::Collapsed);
+            self.shown = None;
+            return;
+        }
+        ctx.widget()
+            .set::<Visibility>("visibility", Visibility::Visible);
+
+        if let Some(command) = self.command.take() {
+            self.handle_command(ctx, command);
+        }
+        self.refresh(ctx);
+    }
+}
+
+impl FileBrowserState {
+    fn command(&mut self, command: BrowserCommand) {
+        self.command = Some(command);
+    }
+
+    fn directory(ctx: &mut Context) -> PathBuf {
+        let directory = ctx.widget().get::<String16>("directory").to_string();
+
+        if directory.is_empty() {
+            PathBuf::from(".")
+        } else {
+            PathBuf::from(directory)
+        }
+    }
+
+    fn set_directory(ctx: &mut Context, directory: &Path) {
+        ctx.widget().set::<String16>(
+            "directory",
+            String16::from(
+                relative_to_current_dir(directory)
+                    .to_string_lossy()
+                    .to_string(),
+            ),
+        );
+    }
+
+    fn show_message(&self, ctx: &mut Context, message: &str) {
+        ctx.get_widget(self.message)
+            .set::<String16>("text", String16::from(message));
+    }
+
+    fn handle_command(&mut self, ctx: &mut Context, command: BrowserCommand) {
+        match command {
+            BrowserCommand::Up => {
+                let directory = fs::canonicalize(Self::directory(ctx))
+                    .ok()
+                    .and_then(|directory| directory.parent().map(Path::to_path_buf));
+
+                if let Some(directory) = directory {
+                    Self::set_directory(ctx, &directory);
+                }
+            }
+            BrowserCommand::Enter(directory) => Self::set_directory(ctx, &directory),
+            BrowserCommand::Select(file_name) => {
+                ctx.widget()
+                    .set::<String16>("file_name", String16::from(file_name));
+            }
+            BrowserCommand::Confirm => self.confirm(ctx),
+            BrowserCommand::Cancel => ctx.widget().set::<bool>("open", false),
+        }
+    }
+
+    /// Sends the chosen file to the target and closes the browser, unless the file name is not
+    /// usable.
+    fn confirm(&mut self, ctx: &mut Context) {
+        let mode = *ctx.widget().get::<FileBrowserMode>("mode");
+        let file_name = ctx.widget().get::<String16>("file_name").to_string();
+        if file_name.trim().is_empty() {
+            self.show_message(ctx, "Enter a file name.");
+            return;
+        }
+
+        let mut path = Self::directory(ctx).join(file_name.trim());
+        if mode == FileBrowserMode::Save && path.extension().is_none() {
+            path.set_extension(GRAPH_EXTENSIONS[0]);
+        }
+        if mode != FileBrowserMode::Save && !path.is_file() {
+            self.show_message(ctx, "There is no such file.");
+            return;
+        }
+
+        let path = relative_to_current_dir(&path).to_string_lossy().to_string();
+        let target = Entity(*ctx.widget().get::<u32>("target"));
+        ctx.get_widget(target)
+            .set::<OptionActionMain>("action_main", Some(ActionMain::FileChosen(mode, path)));
+        ctx.widget().set::<bool>("open", false);
+    }
+
+    /// Rebuilds the list of files when the directory or mode has changed.
+    fn refresh(&mut self, ctx: &mut Context) {
+        let mode = *ctx.widget().get::<FileBrowserMode>("mode");
+        let directory = ctx.widget().get::<String16>("directory").to_string();
+        if self.shown.as_ref() == Some(&(directory.clone(), mode)) {
+            return;
+        }
+
+        ctx.get_widget(self.title)
+            .set::<String16>("text", String16::from(mode.title()));
+        ctx.get_widget(self.confirm)
+            .set::<String16>("text", String16::from(mode.confirm_text()));
+        self.show_message(ctx, "");
+        ctx.clear_children_of(self.list);
+
+        let directory_path = Self::directory(ctx);
+        let (directories, files) = match list_directory(&directory_path, mode.extensions()) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.show_message(ctx, &format!("Could not read directory: {}", e));
+                (Vec::new(), Vec::new())
+            }
+        };
+
+        let id = ctx.entity;
+        let list = self.list;
+        let bc = &mut ctx.build_context();
+
+        for name in directories {
+            let path = directory_path.join(&name);
+            let button = Button::create()
+                .element("button")
+                .on_click(move |states, _| {
+                    states
+                        .get_mut::<FileBrowserState>(id)
+                        .command(BrowserCommand::Enter(path.clone()));
+                    true
+                })
+                .text(format!("{}/", name))
+                .build(bc);
+            bc.append_child(list, button);
+        }
+        for name in files {
+            let button = Button::create()
+                .element("button")
+                .on_click({
+                    let name = name.clone();
+                    move |states, _| {
+                        states
+                            .get_mut::<FileBrowserState>(id)
+                            .command(BrowserCommand::Select(name.clone()));
+                        true
+                    }
+                })
+                .text(name)
+                .build(bc);
+            bc.append_child(list, button);
+        }
+
+        self.shown = Some((directory, mode));
+    }
+}
+
+/// Returns the names of the visible directories and the files with one of the given extensions
+/// in `directory`, sorted by name.
+fn list_directory(directory: &Path, extensions: &[&str]) -> io::Result<(Vec<String>, Vec<String>)> {
+    let mut directories = Vec::new();
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => continue,
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+
+        if path.is_dir() {
+            directories.push(name);
+        } else if path.extension().map_or(false, |extension| {
+            extensions.contains(&extension.to_string_lossy().to_lowercase().as_str())
+        }) {
+            files.push(name);
+        }
+    }
+
+    directories.sort();
+    files.sort();
+
+    Ok((directories, files))
+}
+
+/// Makes paths inside the current directory relative to it, so graphs keep working when the
+/// project directory is moved.
+fn relative_to_current_dir(path: &Path) -> PathBuf {
+    let absolute = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    env::current_dir()
+        .ok()
+        .and_then(|current_dir| {
+            absolute
+                .strip_prefix(current_dir)
+                .ok()
+                .map(Path::to_path_buf)
+        })
+        .map(|relative| {
+            if relative.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                relative
+            }
+        })
+        .unwrap_or(absolute)
+}
@@ -0,0 +1,735 @@
+use crate::{
+    node_graph_spatial::{NodeGraphSpatial, NodeSettings},
+    shared::Indexable,
+};
+use kanter_core::node_graph::NodeId;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+/// How many values of a curve are computed up front when filtering.
+const CURVE_SAMPLES: usize = 1024;
+/// The closest two points on a curve can get horizontally.
+const CURVE_POINT_SPACING: f32 = 0.01;
+
+/// An operation on a single channel. Every pixel reading a neighbor wraps around the edges, so
+/// filtering a tiling texture keeps it tiling.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Filter {
+    GaussianBlur {
+        radius: f32,
+    },
+    DirectionalBlur {
+        radius: f32,
+        /// In degrees, counter clockwise from pointing right.
+        angle: f32,
+    },
+    Sharpen {
+        radius: f32,
+        amount: f32,
+    },
+    Levels {
+        in_black: f32,
+        in_white: f32,
+        gamma: f32,
+        out_black: f32,
+        out_white: f32,
+    },
+    Curves(Curve),
+    Invert,
+    Clamp {
+        min: f32,
+        max: f32,
+    },
+    Sobel {
+        strength: f32,
+    },
+}
+
+pub type OptionFilter = Option<Filter>;
+
+impl Filter {
+    /// Every filter with its default settings, in the order they are listed in the palette and
+    /// property menu.
+    pub fn defaults() -> Vec<Filter> {
+        vec![
+            Filter::GaussianBlur { radius: 4. },
+            Filter::DirectionalBlur {
+                radius: 8.,
+                angle: 0.,
+            },
+            Filter::Sharpen {
+                radius: 2.,
+                amount: 1.,
+            },
+            Filter::Levels {
+                in_black: 0.,
+                in_white: 1.,
+                gamma: 1.,
+                out_black: 0.,
+                out_white: 1.,
+            },
+            Filter::Curves(Curve::default()),
+            Filter::Invert,
+            Filter::Clamp { min: 0., max: 1. },
+            Filter::Sobel { strength: 1. },
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::GaussianBlur { .. } => "Gaussian blur",
+            Self::DirectionalBlur { .. } => "Directional blur",
+            Self::Sharpen { .. } => "Sharpen",
+            Self::Levels { .. } => "Levels",
+            Self::Curves(_) => "Curves",
+            Self::Invert => "Invert",
+            Self::Clamp { .. } => "Clamp",
+            Self::Sobel { .. } => "Edge detect",
+        }
+    }
+
+    /// Returns the name and value of each number the filter is set up with. Curves are edited
+    /// with a curve editor instead.
+    pub fn parameters(&self) -> Vec<(&'static str, f32)> {
+        match *self {
+            Self::GaussianBlur { radius } => vec![("Radius", radius)],
+            Self::DirectionalBlur { radius, angle } => vec![("Radius", radius), ("Angle", angle)],
+            Self::Sharpen { radius, amount } => vec![("Radius", radius), ("Amount", amount)],
+            Self::Levels {
+                in_black,
+                in_white,
+                gamma,
+                out_black,
+                out_white,
+            } => vec![
+                ("In black", in_black),
+                ("In white", in_white),
+                ("Gamma", gamma),
+                ("Out black", out_black),
+                ("Out white", out_white),
+            ],
+            Self::Curves(_) | Self::Invert => Vec::new(),
+            Self::Clamp { min, max } => vec![("Min", min), ("Max", max)],
+            Self::Sobel { strength } => vec![("Strength", strength)],
+        }
+    }
+
+    /// Returns the filter with its numbers replaced by `values`, in the order of `parameters`.
+    /// Values that would break the filter are moved into range.
+    pub fn with_parameters(&self, values: &[f32]) -> Filter {
+        // Values that aren't finite keep the current setting, an infinite radius can't be blurred
+        // with.
+        let current = self.parameters();
+        let value = |index: usize| {
+            values
+                .get(index)
+                .copied()
+                .filter(|value| value.is_finite())
+                .or_else(|| current.get(index).map(|(_, value)| *value))
+                .unwrap_or_default()
+        };
+        let radius = |index: usize| value(index).max(0.);
+
+        match self {
+            Self::GaussianBlur { .. } => Self::GaussianBlur { radius: radius(0) },
+            Self::DirectionalBlur { .. } => Self::DirectionalBlur {
+                radius: radius(0),
+                angle: value(1),
+            },
+            Self::Sharpen { .. } => Self::Sharpen {
+                radius: radius(0),
+                amount: value(1),
+            },
+            Self::Levels { .. } => Self::Levels {
+                in_black: value(0),
+                in_white: value(1),
+                gamma: value(2).max(0.01),
+                out_black: value(3),
+                out_white: value(4),
+            },
+            Self::Curves(_) | Self::Invert => self.clone(),
+            Self::Clamp { .. } => Self::Clamp {
+                min: value(0),
+                max: value(1),
+            },
+            Self::Sobel { .. } => Self::Sobel { strength: value(0) },
+        }
+    }
+
+    /// Filters a `width` x `height` channel.
+    pub fn apply(&self, width: u32, height: u32, values: &[f32]) -> Vec<f32> {
+        let channel = Channel {
+            width: width as i32,
+            height: height as i32,
+            values,
+        };
+
+        match *self {
+            Self::GaussianBlur { radius } => gaussian_blur(&channel, radius),
+            Self::DirectionalBlur { radius, angle } => directional_blur(&channel, radius, angle),
+            Self::Sharpen { radius, amount } => {
+                let blurred = gaussian_blur(&channel, radius);
+                values
+                    .iter()
+                    .zip(&blurred)
+                    .map(|(value, blurred)| value + (value - blurred) * amount)
+                    .collect()
+            }
+            Self::Levels {
+                in_black,
+                in_white,
+                gamma,
+                out_black,
+                out_white,
+            } => {
+                let in_range = match in_white - in_black {
+                    in_range if in_range.abs() < f32::EPSILON => f32::EPSILON,
+                    in_range => in_range,
+                };
+                values
+                    .iter()
+                    .map(|value| {
+                        let t = ((value - in_black) / in_range).max(0.).min(1.);
+                        out_black + t.powf(1. / gamma) * (out_white - out_black)
+                    })
+                    .collect()
+            }
+            Self::Curves(ref curve) => {
+                let samples = curve.sampled(CURVE_SAMPLES);
+                let last = (CURVE_SAMPLES - 1) as f32;
+                values
+                    .iter()
+                    .map(|value| {
+                        let position = value.max(0.).min(1.) * last;
+                        let index = position.floor() as usize;
+                        let next = (index + 1).min(CURVE_SAMPLES - 1);
+                        let t = position - index as f32;
+                        samples[index] + (samples[next] - samples[index]) * t
+                    })
+                    .collect()
+            }
+            Self::Invert => values.iter().map(|value| 1. - value).collect(),
+            Self::Clamp { min, max } => {
+                values.iter().map(|value| value.max(min).min(max)).collect()
+            }
+            Self::Sobel { strength } => sobel(&channel, strength),
+        }
+    }
+}
+
+impl Indexable for Filter {
+    fn index(&self) -> usize {
+        match self {
+            Self::GaussianBlur { .. } => 0,
+            Self::DirectionalBlur { .. } => 1,
+            Self::Sharpen { .. } => 2,
+            Self::Levels { .. } => 3,
+            Self::Curves(_) => 4,
+            Self::Invert => 5,
+            Self::Clamp { .. } => 6,
+            Self::Sobel { .. } => 7,
+        }
+    }
+
+    /// Returns the filter with its default settings.
+    fn from_index(index: usize) -> Option<Self> {
+        Self::defaults().into_iter().nth(index)
+    }
+}
+
+/// A curve mapping input values to output values, going through a number of points sorted from
+/// left to right. The curve is a monotone cubic spline, so it never overshoots its points.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Curve {
+    pub points: Vec<(f32, f32)>,
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Self {
+            points: vec![(0., 0.), (1., 1.)],
+        }
+    }
+}
+
+impl Curve {
+    /// Returns `count` values evenly spread over the curve from 0 to 1.
+    pub fn sampled(&self, count: usize) -> Vec<f32> {
+        let points = &self.points;
+        let last = count.max(2) - 1;
+
+        if points.len() < 2 {
+            let constant = points.first().map(|point| point.1);
+            return (0..=last)
+                .map(|i| constant.unwrap_or(i as f32 / last as f32))
+                .collect();
+        }
+
+        let tangents = self.tangents();
+        (0..=last)
+            .map(|i| {
+                let x = i as f32 / last as f32;
+                let segment = points
+                    .windows(2)
+                    .position(|pair| x < pair[1].0)
+                    .unwrap_or(points.len() - 2);
+                let ((x0, y0), (x1, y1)) = (points[segment], points[segment + 1]);
+
+                if x <= x0 {
+                    return y0;
+                } else if x >= x1 {
+                    return y1;
+                }
+
+                // Cubic Hermite interpolation between the two points.
+                let width = x1 - x0;
+                let t = (x - x0) / width;
+                let (t2, t3) = (t * t, t * t * t);
+                (2. * t3 - 3. * t2 + 1.) * y0
+                    + (t3 - 2. * t2 + t) * width * tangents[segment]
+                    + (-2. * t3 + 3. * t2) * y1
+                    + (t3 - t2) * width * tangents[segment + 1]
+            })
+            .collect()
+    }
+
+    /// Returns the slope of the curve at each point, limited as described by Fritsch and Carlson
+    /// to keep the curve monotone between the points.
+    fn tangents(&self) -> Vec<f32> {
+        let points = &self.points;
+        let secants: Vec<f32> = points
+            .windows(2)
+            .map(|pair| (pair[1].1 - pair[0].1) / (pair[1].0 - pair[0].0).max(f32::EPSILON))
+            .collect();
+
+        let mut tangents = Vec::with_capacity(points.len());
+        tangents.push(secants[0]);
+        for pair in secants.windows(2) {
+            tangents.push(if pair[0] * pair[1] <= 0. {
+                0.
+            } else {
+                (pair[0] + pair[1]) / 2.
+            });
+        }
+        tangents.push(secants[secants.len() - 1]);
+
+        for (segment, secant) in secants.iter().enumerate() {
+            if secant.abs() < f32::EPSILON {
+                tangents[segment] = 0.;
+                tangents[segment + 1] = 0.;
+                continue;
+            }
+
+            let a = tangents[segment] / secant;
+            let b = tangents[segment + 1] / secant;
+            let length = (a * a + b * b).sqrt();
+            if length > 3. {
+                tangents[segment] = 3. / length * a * secant;
+                tangents[segment + 1] = 3. / length * b * secant;
+            }
+        }
+
+        tangents
+    }
+
+    /// Moves the point at `index` to `point`, keeping it between its neighbors so the points
+    /// stay sorted. The first and last points stay at the left and right edges.
+    pub fn move_point(&mut self, index: usize, point: (f32, f32)) {
+        let last = self.points.len() - 1;
+        let x = if index == 0 {
+            0.
+        } else if index == last {
+            1.
+        } else {
+            point
+                .0
+                .max(self.points[index - 1].0 + CURVE_POINT_SPACING)
+                .min(self.points[index + 1].0 - CURVE_POINT_SPACING)
+        };
+
+        self.points[index] = (x, point.1.max(0.).min(1.));
+    }
+
+    /// Adds a point where it belongs between the others, returning its index. Returns `None` if
+    /// it would be too close to an existing point.
+    pub fn insert_point(&mut self, point: (f32, f32)) -> Option<usize> {
+        if self
+            .points
+            .iter()
+            .any(|other| (other.0 - point.0).abs() < CURVE_POINT_SPACING)
+        {
+            return None;
+        }
+
+        let index = self
+            .points
+            .iter()
+            .position(|other| other.0 > point.0)
+            .unwrap_or_else(|| self.points.len());
+        self.points
+            .insert(index, (point.0.max(0.).min(1.), point.1.max(0.).min(1.)));
+
+        Some(index)
+    }
+
+    /// Removes a point, unless it is the first or last one.
+    pub fn remove_point(&mut self, index: usize) {
+        if index > 0 && index + 1 < self.points.len() {
+            self.points.remove(index);
+        }
+    }
+}
+
+/// Returns the filter of a node, if it is a filter node. In the `NodeGraph` filter nodes are
+/// Graph nodes passing their input straight through, processing replaces them with the filtered
+/// result between passes.
+pub(crate) fn filter(node_graph_spatial: &NodeGraphSpatial, node_id: NodeId) -> Option<&Filter> {
+    match node_graph_spatial.node_settings.get(&node_id)? {
+        NodeSettings::Filter(filter) => Some(filter),
+        _ => None,
+    }
+}
+
+/// Replaces the filter of a filter node.
+pub(crate) fn set_filter(
+    node_graph_spatial: &mut NodeGraphSpatial,
+    node_id: NodeId,
+    filter: Filter,
+) {
+    node_graph_spatial
+        .node_settings
+        .insert(node_id, NodeSettings::Filter(filter));
+}
+
+/// The settings of a height to normal node. Only nodes with another strength than 1 have them
+/// stored. `kanter_core` has no strength, so every height to normal node is computed between
+/// passes like filter nodes, which keeps the result the same kind of map at any strength.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HeightToNormal {
+    /// How steep the slopes of the height map are made.
+    pub strength: f32,
+}
+
+impl Default for HeightToNormal {
+    fn default() -> Self {
+        Self { strength: 1. }
+    }
+}
+
+impl HeightToNormal {
+    /// Whether these are the settings of nodes that have none stored.
+    fn is_default(&self) -> bool {
+        (self.strength - 1.).abs() < f32::EPSILON
+    }
+
+    /// Turns a height map into the red, green, blue and alpha channel of a normal map, with the
+    /// slopes wrapping around the edges.
+    pub fn apply(&self, width: u32, height: u32, values: &[f32]) -> Vec<Vec<f32>> {
+        let channel = Channel {
+            width: width as i32,
+            height: height as i32,
+            values,
+        };
+        let mut channels = vec![Vec::with_capacity(values.len()); 4];
+
+        for (x, y) in channel.pixels() {
+            let (gradient_x, gradient_y) = gradient(&channel, x, y);
+            let (slope_x, slope_y) = (gradient_x * self.strength, gradient_y * self.strength);
+            let length = (slope_x * slope_x + slope_y * slope_y + 1.).sqrt();
+
+            channels[0].push(-slope_x / length * 0.5 + 0.5);
+            channels[1].push(-slope_y / length * 0.5 + 0.5);
+            channels[2].push(1. / length * 0.5 + 0.5);
+            channels[3].push(1.);
+        }
+
+        channels
+    }
+}
+
+/// Returns the settings of a height to normal node.
+pub(crate) fn height_to_normal(
+    node_graph_spatial: &NodeGraphSpatial,
+    node_id: NodeId,
+) -> HeightToNormal {
+    match node_graph_spatial.node_settings.get(&node_id) {
+        Some(NodeSettings::HeightToNormal(height_to_normal)) => height_to_normal.clone(),
+        _ => HeightToNormal::default(),
+    }
+}
+
+/// Replaces the settings of a height to normal node.
+pub(crate) fn set_height_to_normal(
+    node_graph_spatial: &mut NodeGraphSpatial,
+    node_id: NodeId,
+    height_to_normal: HeightToNormal,
+) {
+    if height_to_normal.is_default() {
+        node_graph_spatial.node_settings.remove(&node_id);
+    } else {
+        node_graph_spatial
+            .node_settings
+            .insert(node_id, NodeSettings::HeightToNormal(height_to_normal));
+    }
+}
+
+/// A channel being filtered, read with coordinates wrapping around its edges.
+struct Channel<'a> {
+    width: i32,
+    height: i32,
+    values: &'a [f32],
+}
+
+impl Channel<'_> {
+    fn get(&self, x: i32, y: i32) -> f32 {
+        self.values[(y.rem_euclid(self.height) * self.width + x.rem_euclid(self.width)) as usize]
+    }
+
+    /// Reads between pixels by blending the four closest ones.
+    fn sample(&self, x: f32, y: f32) -> f32 {
+        let (left, top) = (x.floor(), y.floor());
+        let (tx, ty) = (x - left, y - top);
+        let (left, top) = (left as i32, top as i32);
+
+        let upper = self.get(left, top) * (1. - tx) + self.get(left + 1, top) * tx;
+        let lower = self.get(left, top + 1) * (1. - tx) + self.get(left + 1, top + 1) * tx;
+        upper * (1. - ty) + lower * ty
+    }
+
+    /// Limits a blur radius to the size of the channel, reaching further only wraps around onto
+    /// the same pixels again and takes longer.
+    fn clamp_radius(&self, radius: f32) -> f32 {
+        if radius.is_nan() {
+            0.
+        } else {
+            radius.min(self.width.max(self.height) as f32)
+        }
+    }
+
+    fn pixels(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        (0..self.height).flat_map(move |y| (0..self.width).map(move |x| (x, y)))
+    }
+}
+
+/// Blurs horizontally and then vertically with a kernel reaching `radius` pixels out, which is
+/// three standard deviations.
+fn gaussian_blur(channel: &Channel, radius: f32) -> Vec<f32> {
+    let radius = channel.clamp_radius(radius);
+    let reach = radius.ceil() as i32;
+    if reach < 1 {
+        return channel.values.to_vec();
+    }
+
+    let sigma = radius / 3.;
+    let weights: Vec<f32> = (-reach..=reach)
+        .map(|offset| (-(offset * offset) as f32 / (2. * sigma * sigma)).exp())
+        .collect();
+    let weight_sum: f32 = weights.iter().sum();
+
+    let blur = |channel: &Channel, horizontal: bool| -> Vec<f32> {
+        channel
+            .pixels()
+            .map(|(x, y)| {
+                (-reach..=reach)
+                    .zip(&weights)
+                    .map(|(offset, weight)| {
+                        let value = if horizontal {
+                            channel.get(x + offset, y)
+                        } else {
+                            channel.get(x, y + offset)
+                        };
+                        value * weight
+                    })
+                    .sum::<f32>()
+                    / weight_sum
+            })
+            .collect()
+    };
+
+    let horizontal = blur(channel, true);
+    blur(
+        &Channel {
+            values: &horizontal,
+            ..*channel
+        },
+        false,
+    )
+}
+
+/// Averages the pixels on a line through each pixel, reaching `radius` pixels in both
+/// directions.
+fn directional_blur(channel: &Channel, radius: f32, angle: f32) -> Vec<f32> {
+    let radius = channel.clamp_radius(radius);
+    let reach = radius.ceil() as i32;
+    if reach < 1 {
+        return channel.values.to_vec();
+    }
+
+    let angle = angle * PI / 180.;
+    let step = (
+        angle.cos() * radius / reach as f32,
+        -angle.sin() * radius / reach as f32,
+    );
+    let sample_count = (reach * 2 + 1) as f32;
+
+    channel
+        .pixels()
+        .map(|(x, y)| {
+            (-reach..=reach)
+                .map(|i| channel.sample(x as f32 + step.0 * i as f32, y as f32 + step.1 * i as f32))
+                .sum::<f32>()
+                / sample_count
+        })
+        .collect()
+}
+
+/// Returns how steep the channel is at each pixel.
+fn sobel(channel: &Channel, strength: f32) -> Vec<f32> {
+    channel
+        .pixels()
+        .map(|(x, y)| {
+            let (gradient_x, gradient_y) = gradient(channel, x, y);
+
+            (gradient_x * gradient_x + gradient_y * gradient_y).sqrt() * strength
+        })
+        .collect()
+}
+
+/// Returns the Sobel gradient of the channel at a pixel, pointing towards higher values.
+fn gradient(channel: &Channel, x: i32, y: i32) -> (f32, f32) {
+    let p = |offset_x: i32, offset_y: i32| channel.get(x + offset_x, y + offset_y);
+
+    let gradient_x = p(1, -1) + 2. * p(1, 0) + p(1, 1) - p(-1, -1) - 2. * p(-1, 0) - p(-1, 1);
+    let gradient_y = p(-1, 1) + 2. * p(0, 1) + p(1, 1) - p(-1, -1) - 2. * p(0, -1) - p(1, -1);
+
+    (gradient_x, gradient_y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} is not close to {}", a, b);
+    }
+
+    #[test]
+    fn blur_wraps_around_the_edges() {
+        let values = [1., 0., 0., 0., 0., 0.];
+        let blurred = Filter::GaussianBlur { radius: 2. }.apply(6, 1, &values);
+
+        assert_close(blurred[1], blurred[5]);
+        assert_close(blurred[2], blurred[4]);
+        assert_close(blurred.iter().sum(), 1.);
+    }
+
+    #[test]
+    fn directional_blur_wraps_around_the_edges() {
+        let values = [0., 0., 0., 0., 0., 1.];
+        let blurred = Filter::DirectionalBlur {
+            radius: 1.,
+            angle: 0.,
+        }
+        .apply(6, 1, &values);
+
+        assert_close(blurred[0], 1. / 3.);
+        assert_close(blurred[4], 1. / 3.);
+        assert_close(blurred[2], 0.);
+    }
+
+    #[test]
+    fn flat_channels_stay_flat_across_the_edges() {
+        let values = [0.5; 16];
+        let blur = Filter::GaussianBlur { radius: 3. };
+        let sharpen = Filter::Sharpen {
+            radius: 2.,
+            amount: 1.,
+        };
+        let sobel = Filter::Sobel { strength: 1. };
+
+        for value in blur.apply(4, 4, &values) {
+            assert_close(value, 0.5);
+        }
+        for value in sharpen.apply(4, 4, &values) {
+            assert_close(value, 0.5);
+        }
+        for value in sobel.apply(4, 4, &values) {
+            assert_close(value, 0.);
+        }
+    }
+
+    #[test]
+    fn infinite_radius_is_limited_to_the_channel() {
+        let values = [1., 0., 0., 0.];
+        let gaussian = Filter::GaussianBlur {
+            radius: f32::INFINITY,
+        };
+        let directional = Filter::DirectionalBlur {
+            radius: f32::INFINITY,
+            angle: 45.,
+        };
+
+        for filter in &[gaussian, directional] {
+            let blurred = filter.apply(2, 2, &values);
+            assert_eq!(blurred.len(), 4);
+            assert!(blurred.iter().all(|value| value.is_finite()));
+        }
+    }
+
+    #[test]
+    fn parameters_that_are_not_finite_are_rejected() {
+        let filter = Filter::GaussianBlur { radius: 4. };
+
+        assert_eq!(filter.with_parameters(&[f32::INFINITY]), filter);
+        assert_eq!(filter.with_parameters(&[f32::NAN]), filter);
+        assert_eq!(
+            filter.with_parameters(&[8.]),
+            Filter::GaussianBlur { radius: 8. }
+        );
+    }
+
+    #[test]
+    fn levels_remaps_the_range() {
+        let levels = Filter::Levels {
+            in_black: 0.25,
+            in_white: 0.75,
+            gamma: 1.,
+            out_black: 0.,
+            out_white: 1.,
+        };
+        let leveled = levels.apply(4, 1, &[0., 0.25, 0.5, 1.]);
+
+        assert_eq!(leveled, vec![0., 0., 0.5, 1.]);
+    }
+
+    #[test]
+    fn default_curve_is_the_identity() {
+        let samples = Curve::default().sampled(5);
+
+        assert_eq!(samples, vec![0., 0.25, 0.5, 0.75, 1.]);
+    }
+
+    #[test]
+    fn curve_goes_through_its_points_without_overshooting() {
+        let curve = Curve {
+            points: vec![(0., 0.), (0.5, 0.9), (1., 1.)],
+        };
+        let samples = curve.sampled(101);
+
+        assert_close(samples[50], 0.9);
+        assert_close(samples[100], 1.);
+        for pair in samples.windows(2) {
+            assert!(pair[0] <= pair[1] + f32::EPSILON);
+        }
+        assert!(samples.iter().all(|sample| *sample <= 1.));
+    }
+
+    #[test]
+    fn curve_with_one_point_is_flat() {
+        let curve = Curve {
+            points: vec![(0.5, 0.3)],
+        };
+
+        assert_eq!(curve.sampled(3), vec![0.3; 3]);
+    }
+}
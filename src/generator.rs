@@ -0,0 +1,589 @@
+use crate::{
+    node_graph_spatial::{NodeGraphSpatial, NodeSettings},
+    shared::Indexable,
+    subgraph::{enter, leave},
+};
+use image::{ImageBuffer, Luma};
+use kanter_core::{node::NodeType, node_graph::NodeId};
+use serde::{Deserialize, Serialize};
+use std::{
+    f32::consts::{PI, SQRT_2},
+    fmt, fs, io,
+    path::PathBuf,
+};
+
+/// The width and height of generated textures.
+const GENERATOR_SIZE: u32 = 512;
+/// The highest scale a generator can have, at which each repeat of the pattern is one pixel.
+pub const MAX_SCALE: u32 = GENERATOR_SIZE;
+const FBM_OCTAVES: u32 = 6;
+/// The gap between bricks and between tiles, as a fraction of their height.
+const MORTAR_WIDTH: f32 = 0.08;
+/// The distance from the center of a polygon to its sides, as a fraction of its cell.
+const POLYGON_RADIUS: f32 = 0.4;
+
+/// The patterns generator nodes can make. They all tile seamlessly.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GeneratorType {
+    Perlin,
+    Simplex,
+    Worley,
+    Fbm,
+    LinearGradient,
+    RadialGradient,
+    Bricks,
+    Tiles,
+    Checker,
+    Polygon,
+}
+
+pub type OptionGeneratorType = Option<GeneratorType>;
+
+impl GeneratorType {
+    /// Every generator, in the order they are listed in the palette and property menu.
+    pub const ALL: [GeneratorType; 10] = [
+        GeneratorType::Perlin,
+        GeneratorType::Simplex,
+        GeneratorType::Worley,
+        GeneratorType::Fbm,
+        GeneratorType::LinearGradient,
+        GeneratorType::RadialGradient,
+        GeneratorType::Bricks,
+        GeneratorType::Tiles,
+        GeneratorType::Checker,
+        GeneratorType::Polygon,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Perlin => "Perlin noise",
+            Self::Simplex => "Simplex noise",
+            Self::Worley => "Worley noise",
+            Self::Fbm => "fBm noise",
+            Self::LinearGradient => "Linear gradient",
+            Self::RadialGradient => "Radial gradient",
+            Self::Bricks => "Bricks",
+            Self::Tiles => "Tiles",
+            Self::Checker => "Checker",
+            Self::Polygon => "Polygon",
+        }
+    }
+}
+
+impl Indexable for GeneratorType {
+    fn index(&self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|generator_type| generator_type == self)
+            .unwrap_or_default()
+    }
+
+    fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+}
+
+/// The settings of a generator node. In the `NodeGraph` the node is an image node reading the
+/// texture generated from these settings, so processing and exporting don't need to know about
+/// generators.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Generator {
+    pub generator_type: GeneratorType,
+    /// Picks a different variation of the noises and of the brightness of bricks and tiles.
+    pub seed: u32,
+    /// How many times the pattern repeats across the texture, from 1 to `MAX_SCALE`. Whole
+    /// numbers keep it tiling.
+    pub scale: u32,
+    /// The number of sides of polygons.
+    pub sides: u32,
+}
+
+impl Generator {
+    pub fn new(generator_type: GeneratorType) -> Self {
+        Self {
+            generator_type,
+            seed: 0,
+            scale: 4,
+            sides: 6,
+        }
+    }
+
+    /// Where the texture for these settings is cached, generators with the same settings share
+    /// it.
+    fn path(&self) -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| {
+            dir.join("kanter").join("generated").join(format!(
+                "{:?}_{}_{}_{}_{}.png",
+                self.generator_type, self.seed, self.scale, self.sides, GENERATOR_SIZE
+            ))
+        })
+    }
+
+    /// Returns the value of the pattern at `u`, `v`, which go from 0 to 1 across the texture.
+    fn value(&self, u: f32, v: f32) -> f32 {
+        let scale = clamp_scale(self.scale) as i32;
+        let seed = self.seed;
+
+        let value = match self.generator_type {
+            GeneratorType::Perlin => perlin(u, v, scale, seed) * SQRT_2 * 0.5 + 0.5,
+            GeneratorType::Simplex => simplex(u, v, scale, seed) * 0.5 + 0.5,
+            GeneratorType::Worley => worley(u, v, scale, seed),
+            GeneratorType::Fbm => fbm(u, v, scale, seed) * SQRT_2 * 0.5 + 0.5,
+            GeneratorType::LinearGradient => 1. - (2. * (u * scale as f32).fract() - 1.).abs(),
+            GeneratorType::RadialGradient => {
+                let (x, y) = cell_position(u, v, scale);
+                1. - (x * x + y * y).sqrt() * 2.
+            }
+            GeneratorType::Bricks => bricks(u, v, scale, seed),
+            GeneratorType::Tiles => tiles(u, v, scale, seed),
+            GeneratorType::Checker => {
+                let x = (u * scale as f32 * 2.).floor() as i32;
+                let y = (v * scale as f32 * 2.).floor() as i32;
+                ((x + y).rem_euclid(2)) as f32
+            }
+            GeneratorType::Polygon => polygon(u, v, scale, self.sides.max(3)),
+        };
+
+        value.max(0.).min(1.)
+    }
+
+    fn generate(&self) -> Vec<u16> {
+        let size = GENERATOR_SIZE as f32;
+
+        (0..GENERATOR_SIZE * GENERATOR_SIZE)
+            .map(|i| {
+                let u = ((i % GENERATOR_SIZE) as f32 + 0.5) / size;
+                let v = ((i / GENERATOR_SIZE) as f32 + 0.5) / size;
+
+                (self.value(u, v) * u16::MAX as f32).round() as u16
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum GeneratorError {
+    NoCacheDir,
+    Io(PathBuf, io::Error),
+    Image(PathBuf, image::ImageError),
+    /// The generated pixels don't fill the texture.
+    Size,
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoCacheDir => write!(f, "there is no cache directory to generate textures in"),
+            Self::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Image(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Size => write!(f, "the generated texture has the wrong size"),
+        }
+    }
+}
+
+impl std::error::Error for GeneratorError {}
+
+/// Returns the generator settings of a node, if it is a generator node.
+pub(crate) fn generator(
+    node_graph_spatial: &NodeGraphSpatial,
+    node_id: NodeId,
+) -> Option<&Generator> {
+    match node_graph_spatial.node_settings.get(&node_id)? {
+        NodeSettings::Generator(generator) => Some(generator),
+        _ => None,
+    }
+}
+
+/// Replaces the settings of a generator node.
+pub(crate) fn set_generator(
+    node_graph_spatial: &mut NodeGraphSpatial,
+    node_id: NodeId,
+    generator: Generator,
+) {
+    node_graph_spatial
+        .node_settings
+        .insert(node_id, NodeSettings::Generator(generator));
+}
+
+/// Limits a scale to the range generators support. Larger scales would overflow the lattice
+/// coordinates of the patterns.
+pub fn clamp_scale(scale: u32) -> u32 {
+    scale.max(1).min(MAX_SCALE)
+}
+
+/// Writes the texture of the generator to the cache unless it is already there, and points the
+/// image node of the generator at it. If that fails the node outputs black instead.
+pub(crate) fn bake(
+    node_graph_spatial: &mut NodeGraphSpatial,
+    node_id: NodeId,
+    generator: &Generator,
+) -> Result<(), GeneratorError> {
+    let result = cached_texture(generator);
+
+    if let Some(node) = node_graph_spatial.node_graph.node_with_id_mut(node_id) {
+        node.node_type = match &result {
+            Ok(path) => NodeType::Image(path.to_string_lossy().to_string()),
+            Err(_) => NodeType::Value(0.),
+        };
+    }
+
+    result.map(|_| ())
+}
+
+/// Returns the path of the texture of the generator, generating it first if it isn't cached.
+fn cached_texture(generator: &Generator) -> Result<PathBuf, GeneratorError> {
+    let path = generator.path().ok_or(GeneratorError::NoCacheDir)?;
+
+    if !path.is_file() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| GeneratorError::Io(dir.to_owned(), e))?;
+        }
+
+        let image: ImageBuffer<Luma<u16>, Vec<u16>> =
+            ImageBuffer::from_raw(GENERATOR_SIZE, GENERATOR_SIZE, generator.generate())
+                .ok_or(GeneratorError::Size)?;
+        image
+            .save(&path)
+            .map_err(|e| GeneratorError::Image(path.clone(), e))?;
+    }
+
+    Ok(path)
+}
+
+/// Bakes every generator in the graph and its subgraphs. Graph files only store the settings of
+/// generators, so their textures are generated when a graph is opened.
+pub(crate) fn bake_all(node_graph_spatial: &mut NodeGraphSpatial) -> Vec<GeneratorError> {
+    let node_ids = node_graph_spatial.node_graph.node_ids();
+    bake_nodes(node_graph_spatial, &node_ids)
+}
+
+/// Bakes the given generator nodes, and the generators in the given Graph nodes.
+pub(crate) fn bake_nodes(
+    node_graph_spatial: &mut NodeGraphSpatial,
+    node_ids: &[NodeId],
+) -> Vec<GeneratorError> {
+    let mut errors = Vec::new();
+
+    for node_id in node_ids {
+        match node_graph_spatial.node_settings.get(node_id) {
+            Some(NodeSettings::Generator(generator)) => {
+                // Graph files and clips can have any scale in them.
+                let generator = Generator {
+                    scale: clamp_scale(generator.scale),
+                    ..generator.clone()
+                };
+                set_generator(node_graph_spatial, *node_id, generator.clone());
+                if let Err(e) = bake(node_graph_spatial, *node_id, &generator) {
+                    errors.push(e);
+                }
+            }
+            Some(NodeSettings::Subgraph(_)) => {
+                if let Some(mut inner) = enter(node_graph_spatial, *node_id) {
+                    errors.append(&mut bake_all(&mut inner));
+                    leave(node_graph_spatial, *node_id, inner);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    errors
+}
+
+/// Returns a copy of the graph where generator nodes in it and its subgraphs don't point at
+/// their textures, so paths into the cache of this machine don't end up in graph files.
+pub(crate) fn unbaked(node_graph_spatial: &NodeGraphSpatial) -> NodeGraphSpatial {
+    let mut unbaked_graph = node_graph_spatial.clone();
+
+    for (node_id, node_settings) in &node_graph_spatial.node_settings {
+        match node_settings {
+            NodeSettings::Generator(_) => {
+                if let Some(node) = unbaked_graph.node_graph.node_with_id_mut(*node_id) {
+                    node.node_type = NodeType::Image(String::new());
+                }
+            }
+            NodeSettings::Subgraph(_) => {
+                if let Some(inner) = enter(node_graph_spatial, *node_id) {
+                    leave(&mut unbaked_graph, *node_id, unbaked(&inner));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    unbaked_graph
+}
+
+/// Hashes a lattice point to a pseudo random number.
+fn hash(x: i32, y: i32, seed: u32) -> u32 {
+    let mut h = seed
+        .wrapping_mul(0x27d4_eb2d)
+        .wrapping_add((x as u32).wrapping_mul(0x8da6_b343))
+        .wrapping_add((y as u32).wrapping_mul(0xd816_3841));
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^ (h >> 16)
+}
+
+/// Turns a hash into a number between 0 and 1.
+fn unit(hash: u32) -> f32 {
+    (hash >> 8) as f32 / (1 << 24) as f32
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Gradient noise on a lattice that repeats every `period` cells, between about -0.7 and 0.7.
+fn perlin(u: f32, v: f32, period: i32, seed: u32) -> f32 {
+    let (x, y) = (u * period as f32, v * period as f32);
+    let (cell_x, cell_y) = (x.floor() as i32, y.floor() as i32);
+    let (fx, fy) = (x - x.floor(), y - y.floor());
+
+    let gradient = |offset_x: i32, offset_y: i32| {
+        let angle = unit(hash(
+            (cell_x + offset_x).rem_euclid(period),
+            (cell_y + offset_y).rem_euclid(period),
+            seed,
+        )) * PI
+            * 2.;
+
+        angle.cos() * (fx - offset_x as f32) + angle.sin() * (fy - offset_y as f32)
+    };
+
+    let (sx, sy) = (fade(fx), fade(fy));
+    lerp(
+        lerp(gradient(0, 0), gradient(1, 0), sx),
+        lerp(gradient(0, 1), gradient(1, 1), sx),
+        sy,
+    )
+}
+
+/// Sums octaves of Perlin noise with doubling frequency and halving amplitude.
+fn fbm(u: f32, v: f32, period: i32, seed: u32) -> f32 {
+    let mut total = 0.;
+    let mut amplitude = 1.;
+    let mut amplitude_sum = 0.;
+
+    for octave in 0..FBM_OCTAVES {
+        total += perlin(u, v, period << octave, seed.wrapping_add(octave)) * amplitude;
+        amplitude_sum += amplitude;
+        amplitude *= 0.5;
+    }
+
+    total / amplitude_sum
+}
+
+/// Simplex noise between about -1 and 1. The simplex grid doesn't line up with the edges of the
+/// texture, so the texture is wrapped around a torus in 4D and 4D noise is sampled on it.
+fn simplex(u: f32, v: f32, period: i32, seed: u32) -> f32 {
+    let radius = period as f32 / (PI * 2.);
+    let (angle_u, angle_v) = (u * PI * 2., v * PI * 2.);
+
+    simplex_4d(
+        [
+            angle_u.cos() * radius,
+            angle_u.sin() * radius,
+            angle_v.cos() * radius,
+            angle_v.sin() * radius,
+        ],
+        seed,
+    )
+}
+
+fn simplex_4d(point: [f32; 4], seed: u32) -> f32 {
+    let skew = (5f32.sqrt() - 1.) / 4.;
+    let unskew = (5. - 5f32.sqrt()) / 20.;
+
+    let s = point.iter().sum::<f32>() * skew;
+    let mut cell = [0; 4];
+    for (cell, point) in cell.iter_mut().zip(&point) {
+        *cell = (point + s).floor() as i32;
+    }
+    let t = cell.iter().sum::<i32>() as f32 * unskew;
+    let mut first = [0.; 4];
+    for ((first, point), cell) in first.iter_mut().zip(&point).zip(&cell) {
+        *first = point - (*cell as f32 - t);
+    }
+
+    // The order of the axes by distance decides which corners of the simplex the point is in.
+    let mut rank = [0; 4];
+    for (axis, rank) in rank.iter_mut().enumerate() {
+        *rank = first
+            .iter()
+            .enumerate()
+            .filter(|(other_axis, other)| (**other, *other_axis) < (first[axis], axis))
+            .count();
+    }
+
+    let mut total = 0.;
+    for corner in 0..5 {
+        let mut offset = [0; 4];
+        for (offset, rank) in offset.iter_mut().zip(&rank) {
+            *offset = if corner > 0 && *rank >= 4 - corner {
+                1
+            } else {
+                0
+            };
+        }
+        let mut distance = [0.; 4];
+        for ((distance, first), offset) in distance.iter_mut().zip(&first).zip(&offset) {
+            *distance = first - *offset as f32 + corner as f32 * unskew;
+        }
+
+        let falloff = 0.6 - distance.iter().map(|d| d * d).sum::<f32>();
+        if falloff <= 0. {
+            continue;
+        }
+
+        // The gradients point to the middles of the edges of a 4D cube: one axis is zero and the
+        // others are plus or minus one.
+        let h = hash(
+            cell[0] + offset[0] + (cell[2] + offset[2]) * 1619,
+            cell[1] + offset[1] + (cell[3] + offset[3]) * 31337,
+            seed,
+        ) % 32;
+        let zero_axis = (h / 8) as usize;
+        let dot: f32 = distance
+            .iter()
+            .enumerate()
+            .filter(|(axis, _)| *axis != zero_axis)
+            .enumerate()
+            .map(|(sign_bit, (_, distance))| {
+                if h & (1 << sign_bit) == 0 {
+                    *distance
+                } else {
+                    -distance
+                }
+            })
+            .sum();
+
+        total += falloff * falloff * falloff * falloff * dot;
+    }
+
+    total * 27.
+}
+
+/// The distance to the nearest of one random point per cell, the cells repeat every `period`.
+fn worley(u: f32, v: f32, period: i32, seed: u32) -> f32 {
+    let (x, y) = (u * period as f32, v * period as f32);
+    let (cell_x, cell_y) = (x.floor() as i32, y.floor() as i32);
+    let mut nearest = f32::MAX;
+
+    for offset_y in -1..=1 {
+        for offset_x in -1..=1 {
+            let (neighbor_x, neighbor_y) = (cell_x + offset_x, cell_y + offset_y);
+            let h = hash(
+                neighbor_x.rem_euclid(period),
+                neighbor_y.rem_euclid(period),
+                seed,
+            );
+            let point_x = neighbor_x as f32 + unit(h);
+            let point_y = neighbor_y as f32 + unit(hash(h as i32, 0, seed));
+
+            let distance = ((point_x - x).powi(2) + (point_y - y).powi(2)).sqrt();
+            nearest = nearest.min(distance);
+        }
+    }
+
+    nearest
+}
+
+/// Returns the position in the current cell of a `period` x `period` grid, relative to the
+/// center of the cell.
+fn cell_position(u: f32, v: f32, period: i32) -> (f32, f32) {
+    (
+        (u * period as f32).fract() - 0.5,
+        (v * period as f32).fract() - 0.5,
+    )
+}
+
+/// Rows of bricks twice as wide as they are high, every other row shifted by half a brick. Each
+/// brick gets a random brightness.
+fn bricks(u: f32, v: f32, period: i32, seed: u32) -> f32 {
+    let rows = period * 2;
+    let y = v * rows as f32;
+    let row = y.floor() as i32;
+    let shift = if row % 2 == 0 { 0. } else { 0.5 };
+    let x = u * period as f32 + shift;
+    let column = x.floor() as i32;
+
+    let in_mortar = y.fract() < MORTAR_WIDTH * 0.5
+        || y.fract() > 1. - MORTAR_WIDTH * 0.5
+        || x.fract() < MORTAR_WIDTH * 0.25
+        || x.fract() > 1. - MORTAR_WIDTH * 0.25;
+    if in_mortar {
+        return 0.;
+    }
+
+    0.5 + unit(hash(column.rem_euclid(period), row.rem_euclid(rows), seed)) * 0.5
+}
+
+/// A grid of square tiles, each with a random brightness.
+fn tiles(u: f32, v: f32, period: i32, seed: u32) -> f32 {
+    let (x, y) = (u * period as f32, v * period as f32);
+
+    let in_mortar = [x.fract(), y.fract()]
+        .iter()
+        .any(|f| *f < MORTAR_WIDTH * 0.5 || *f > 1. - MORTAR_WIDTH * 0.5);
+    if in_mortar {
+        return 0.;
+    }
+
+    0.5 + unit(hash(x.floor() as i32, y.floor() as i32, seed)) * 0.5
+}
+
+/// A regular polygon in each cell of a `period` x `period` grid, with a corner pointing up and
+/// edges smoothed over one pixel.
+fn polygon(u: f32, v: f32, period: i32, sides: u32) -> f32 {
+    let (x, y) = cell_position(u, v, period);
+    let sector = PI * 2. / sides as f32;
+    let angle = x.atan2(-y);
+    let distance = ((angle / sector + 0.5).floor() * sector - angle).cos() * (x * x + y * y).sqrt();
+
+    let pixel = period as f32 / GENERATOR_SIZE as f32;
+    (POLYGON_RADIUS - distance) / pixel + 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_generator_bakes_at_the_maximum_scale() {
+        for generator_type in &GeneratorType::ALL {
+            let generator = Generator {
+                scale: MAX_SCALE,
+                ..Generator::new(*generator_type)
+            };
+
+            assert_eq!(
+                generator.generate().len(),
+                (GENERATOR_SIZE * GENERATOR_SIZE) as usize
+            );
+        }
+    }
+
+    #[test]
+    fn larger_scales_are_clamped() {
+        for generator_type in &GeneratorType::ALL {
+            let generator = Generator {
+                scale: u32::MAX,
+                ..Generator::new(*generator_type)
+            };
+            let clamped = Generator {
+                scale: MAX_SCALE,
+                ..Generator::new(*generator_type)
+            };
+
+            assert_eq!(generator.value(0.3, 0.7), clamped.value(0.3, 0.7));
+        }
+        assert_eq!(clamp_scale(0), 1);
+    }
+}
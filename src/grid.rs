@@ -0,0 +1,73 @@
+use crate::{layout::GRID_SIZE, view::View};
+use orbtk::prelude::*;
+
+const GRID_COLOR: Color = Color::rgb(70, 70, 70);
+/// Grid lines closer together than this on screen are not drawn, they would only be noise.
+const SPACING_MIN: f64 = 6.;
+
+widget!(
+    Grid<GridState> {
+        view: View
+    }
+);
+
+impl Template for Grid {
+    fn template(self, _id: Entity, _ctx: &mut BuildContext) -> Self {
+        self.name("Grid").view(View::default())
+    }
+
+    fn render_object(&self) -> Box<dyn RenderObject> {
+        Box::new(GridRenderObject)
+    }
+}
+
+#[derive(AsAny, Default)]
+pub struct GridState {}
+
+impl State for GridState {}
+
+pub struct GridRenderObject;
+
+impl RenderObject for GridRenderObject {
+    fn render_self(&self, ctx: &mut Context<'_>, global_position: &Point) {
+        let (view, bounds) = {
+            let widget = ctx.widget();
+
+            (
+                *widget.get::<View>("view"),
+                *widget.get::<Rectangle>("bounds"),
+            )
+        };
+
+        let spacing = GRID_SIZE * view.zoom;
+        if spacing < SPACING_MIN {
+            return;
+        }
+
+        let left = global_position.x + bounds.x();
+        let top = global_position.y + bounds.y();
+        let width = bounds.width();
+        let height = bounds.height();
+
+        let rc2d = ctx.render_context_2_d();
+        rc2d.begin_path();
+        rc2d.set_line_width(1.);
+        rc2d.set_stroke_style(Brush::SolidColor(GRID_COLOR));
+
+        let mut x = view.offset.x.rem_euclid(spacing);
+        while x < width {
+            rc2d.move_to(left + x, top);
+            rc2d.line_to(left + x, top + height);
+            x += spacing;
+        }
+
+        let mut y = view.offset.y.rem_euclid(spacing);
+        while y < height {
+            rc2d.move_to(left, top + y);
+            rc2d.line_to(left + width, top + y);
+            y += spacing;
+        }
+
+        rc2d.stroke();
+    }
+}
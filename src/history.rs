@@ -0,0 +1,211 @@
+use crate::node_graph_spatial::NodeGraphSpatial;
+use kanter_core::node_graph::NodeId;
+use std::path::PathBuf;
+
+const HISTORY_LENGTH: usize = 100;
+
+/// The kind of change an entry in the `History` reverts.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Edit {
+    AddNode(NodeId),
+    DeleteNode,
+    MoveNode(NodeId),
+    Connect,
+    Disconnect,
+    SetProperty(NodeId),
+    Paste,
+    Collapse,
+    EditSubgraph(NodeId),
+    Arrange,
+    LoadGraph,
+}
+
+impl Edit {
+    /// Whether `next` can be folded into an entry of this kind instead of getting its own entry.
+    ///
+    /// A node is dragged into place right after being added, and text properties change once
+    /// per keystroke, neither of those should take more than one undo step.
+    fn absorbs(self, next: Edit) -> bool {
+        match (self, next) {
+            (Edit::AddNode(added), Edit::MoveNode(moved)) => added == moved,
+            (Edit::SetProperty(previous), Edit::SetProperty(next)) => previous == next,
+            _ => false,
+        }
+    }
+}
+
+struct Entry {
+    edit: Edit,
+    node_graph_spatial: NodeGraphSpatial,
+    /// The file the graph belonged to, which changes when another graph is loaded.
+    graph_path: Option<PathBuf>,
+}
+
+/// Snapshots of the graph taken before each edit, so edits can be undone and redone.
+pub struct History {
+    undo_stack: Vec<Entry>,
+    redo_stack: Vec<Entry>,
+    /// The length of `undo_stack` when the graph was last saved, `None` if that state can't be
+    /// reached by undoing or redoing anymore. A new history starts out saved.
+    saved: Option<usize>,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            saved: Some(0),
+        }
+    }
+}
+
+impl History {
+    /// Records the state of the graph from right before `edit` was made.
+    pub fn push(&mut self, edit: Edit, before: NodeGraphSpatial, graph_path: Option<PathBuf>) {
+        self.redo_stack.clear();
+        let len = self.undo_stack.len();
+        // A saved state that was undone can't be redone anymore.
+        if self.saved.map_or(false, |saved| saved > len) {
+            self.saved = None;
+        }
+
+        if let Some(last) = self.undo_stack.last_mut() {
+            if last.edit.absorbs(edit) {
+                last.edit = edit;
+                // The edit changes the state after the last entry instead of adding one.
+                if self.saved == Some(len) {
+                    self.saved = None;
+                }
+                return;
+            }
+        }
+
+        self.undo_stack.push(Entry {
+            edit,
+            node_graph_spatial: before,
+            graph_path,
+        });
+
+        if self.undo_stack.len() > HISTORY_LENGTH {
+            self.undo_stack.remove(0);
+            self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+        }
+    }
+
+    /// Remembers the current state as the one written to the graph file.
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo_stack.len());
+    }
+
+    /// Whether undoing and redoing has led back to the state that was last saved.
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.undo_stack.len())
+    }
+
+    /// Returns the graph and its file as they were before the most recent edit, `current` and
+    /// `graph_path` are kept for redoing.
+    pub fn undo(
+        &mut self,
+        current: NodeGraphSpatial,
+        graph_path: Option<PathBuf>,
+    ) -> Option<(NodeGraphSpatial, Option<PathBuf>)> {
+        let entry = self.undo_stack.pop()?;
+
+        self.redo_stack.push(Entry {
+            edit: entry.edit,
+            node_graph_spatial: current,
+            graph_path,
+        });
+
+        Some((entry.node_graph_spatial, entry.graph_path))
+    }
+
+    /// Returns the graph and its file as they were before the most recent undo, `current` and
+    /// `graph_path` are kept for undoing.
+    pub fn redo(
+        &mut self,
+        current: NodeGraphSpatial,
+        graph_path: Option<PathBuf>,
+    ) -> Option<(NodeGraphSpatial, Option<PathBuf>)> {
+        let entry = self.redo_stack.pop()?;
+
+        self.undo_stack.push(Entry {
+            edit: entry.edit,
+            node_graph_spatial: current,
+            graph_path,
+        });
+
+        Some((entry.node_graph_spatial, entry.graph_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_an_added_node_is_absorbed() {
+        assert!(Edit::AddNode(NodeId(1)).absorbs(Edit::MoveNode(NodeId(1))));
+        assert!(!Edit::AddNode(NodeId(1)).absorbs(Edit::MoveNode(NodeId(2))));
+        assert!(!Edit::MoveNode(NodeId(1)).absorbs(Edit::MoveNode(NodeId(1))));
+    }
+
+    #[test]
+    fn property_edits_on_one_node_are_absorbed() {
+        assert!(Edit::SetProperty(NodeId(1)).absorbs(Edit::SetProperty(NodeId(1))));
+        assert!(!Edit::SetProperty(NodeId(1)).absorbs(Edit::SetProperty(NodeId(2))));
+        assert!(!Edit::SetProperty(NodeId(1)).absorbs(Edit::Connect));
+    }
+
+    #[test]
+    fn absorbed_edits_take_one_undo_step() {
+        let mut history = History::default();
+        let edit = Edit::SetProperty(NodeId(1));
+        history.push(edit, NodeGraphSpatial::default(), None);
+        history.push(edit, NodeGraphSpatial::default(), None);
+
+        assert!(history.undo(NodeGraphSpatial::default(), None).is_some());
+        assert!(history.undo(NodeGraphSpatial::default(), None).is_none());
+    }
+
+    #[test]
+    fn undoing_back_to_the_save_is_saved() {
+        let mut history = History::default();
+        history.push(Edit::Connect, NodeGraphSpatial::default(), None);
+        history.mark_saved();
+        history.push(Edit::Disconnect, NodeGraphSpatial::default(), None);
+        assert!(!history.is_saved());
+
+        history.undo(NodeGraphSpatial::default(), None);
+        assert!(history.is_saved());
+        history.undo(NodeGraphSpatial::default(), None);
+        assert!(!history.is_saved());
+        history.redo(NodeGraphSpatial::default(), None);
+        assert!(history.is_saved());
+    }
+
+    #[test]
+    fn saved_state_is_lost_when_edited_over() {
+        let mut history = History::default();
+        history.push(Edit::Connect, NodeGraphSpatial::default(), None);
+        history.mark_saved();
+        history.undo(NodeGraphSpatial::default(), None);
+        history.push(Edit::Disconnect, NodeGraphSpatial::default(), None);
+
+        assert!(!history.is_saved());
+        history.undo(NodeGraphSpatial::default(), None);
+        assert!(!history.is_saved());
+    }
+
+    #[test]
+    fn absorbed_edits_are_not_saved() {
+        let mut history = History::default();
+        let edit = Edit::SetProperty(NodeId(1));
+        history.push(edit, NodeGraphSpatial::default(), None);
+        history.mark_saved();
+        history.push(edit, NodeGraphSpatial::default(), None);
+
+        assert!(!history.is_saved());
+    }
+}
@@ -0,0 +1,393 @@
+use crate::config::config_dir;
+use orbtk::shell::Key;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::PathBuf};
+
+/// Everything that can be bound to a key.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    Open,
+    OpenRecent,
+    Save,
+    SaveAs,
+    ExportOutputs,
+    Quit,
+    AddNode,
+    Delete,
+    Duplicate,
+    Copy,
+    Cut,
+    Paste,
+    Undo,
+    Redo,
+    FrameAll,
+    FrameSelected,
+    CollapseSelection,
+    EnterSubgraph,
+    LeaveSubgraph,
+    ToggleGrid,
+    AlignLeft,
+    AlignRight,
+    AlignTop,
+    AlignBottom,
+    DistributeHorizontally,
+    DistributeVertically,
+    AutoLayout,
+    ToggleViewer,
+    TogglePreview,
+    ShowShortcuts,
+}
+
+impl Command {
+    /// Every command, in the order they are listed in the shortcut overview.
+    pub const ALL: [Command; 30] = [
+        Command::Open,
+        Command::OpenRecent,
+        Command::Save,
+        Command::SaveAs,
+        Command::ExportOutputs,
+        Command::Quit,
+        Command::AddNode,
+        Command::Delete,
+        Command::Duplicate,
+        Command::Copy,
+        Command::Cut,
+        Command::Paste,
+        Command::Undo,
+        Command::Redo,
+        Command::FrameAll,
+        Command::FrameSelected,
+        Command::CollapseSelection,
+        Command::EnterSubgraph,
+        Command::LeaveSubgraph,
+        Command::ToggleGrid,
+        Command::AlignLeft,
+        Command::AlignRight,
+        Command::AlignTop,
+        Command::AlignBottom,
+        Command::DistributeHorizontally,
+        Command::DistributeVertically,
+        Command::AutoLayout,
+        Command::ToggleViewer,
+        Command::TogglePreview,
+        Command::ShowShortcuts,
+    ];
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Open => "Open graph",
+            Self::OpenRecent => "Show recent files",
+            Self::Save => "Save graph",
+            Self::SaveAs => "Save graph as",
+            Self::ExportOutputs => "Export all outputs",
+            Self::Quit => "Quit",
+            Self::AddNode => "Add node",
+            Self::Delete => "Delete selection",
+            Self::Duplicate => "Duplicate selection",
+            Self::Copy => "Copy",
+            Self::Cut => "Cut",
+            Self::Paste => "Paste",
+            Self::Undo => "Undo",
+            Self::Redo => "Redo",
+            Self::FrameAll => "Frame all",
+            Self::FrameSelected => "Frame selected",
+            Self::CollapseSelection => "Collapse selection into graph",
+            Self::EnterSubgraph => "Enter graph node",
+            Self::LeaveSubgraph => "Leave graph node",
+            Self::ToggleGrid => "Toggle grid snapping",
+            Self::AlignLeft => "Align left",
+            Self::AlignRight => "Align right",
+            Self::AlignTop => "Align top",
+            Self::AlignBottom => "Align bottom",
+            Self::DistributeHorizontally => "Distribute horizontally",
+            Self::DistributeVertically => "Distribute vertically",
+            Self::AutoLayout => "Lay out graph automatically",
+            Self::ToggleViewer => "Show texture viewer",
+            Self::TogglePreview => "Show material preview",
+            Self::ShowShortcuts => "Show shortcuts",
+        }
+    }
+}
+
+/// A key together with the modifier keys that have to be held.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyChord {
+    ctrl: bool,
+    shift: bool,
+    key: &'static str,
+}
+
+impl KeyChord {
+    /// Returns the chord for a key press, or `None` if the key can't be bound.
+    pub fn new(key: Key, ctrl: bool, shift: bool) -> Option<Self> {
+        Some(Self {
+            ctrl,
+            shift,
+            key: key_name(key)?,
+        })
+    }
+
+    /// Parses chords like "Ctrl+Shift+Z", ignoring case and whitespace.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut ctrl = false;
+        let mut shift = false;
+        let mut key = None;
+
+        for part in text.split('+').map(|part| part.trim().to_lowercase()) {
+            match part.as_str() {
+                "ctrl" | "control" => ctrl = true,
+                "shift" => shift = true,
+                _ if key.is_none() => {
+                    key = KEY_NAMES
+                        .iter()
+                        .map(|(_, name)| *name)
+                        .find(|name| name.to_lowercase() == part);
+                    key?;
+                }
+                _ => return None,
+            }
+        }
+
+        Some(Self {
+            ctrl,
+            shift,
+            key: key?,
+        })
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// The keys that can be bound, with the names used for them in the keymap file.
+const KEY_NAMES: &[(Key, &str)] = &[
+    (Key::A(false), "A"),
+    (Key::B(false), "B"),
+    (Key::C(false), "C"),
+    (Key::D(false), "D"),
+    (Key::E(false), "E"),
+    (Key::F(false), "F"),
+    (Key::G(false), "G"),
+    (Key::H(false), "H"),
+    (Key::I(false), "I"),
+    (Key::J(false), "J"),
+    (Key::K(false), "K"),
+    (Key::L(false), "L"),
+    (Key::M(false), "M"),
+    (Key::N(false), "N"),
+    (Key::O(false), "O"),
+    (Key::P(false), "P"),
+    (Key::Q(false), "Q"),
+    (Key::R(false), "R"),
+    (Key::S(false), "S"),
+    (Key::T(false), "T"),
+    (Key::U(false), "U"),
+    (Key::V(false), "V"),
+    (Key::W(false), "W"),
+    (Key::X(false), "X"),
+    (Key::Y(false), "Y"),
+    (Key::Z(false), "Z"),
+    (Key::Space, "Space"),
+    (Key::Tab, "Tab"),
+    (Key::Enter, "Enter"),
+    (Key::Escape, "Escape"),
+    (Key::Backspace, "Backspace"),
+    (Key::Delete, "Delete"),
+    (Key::Home, "Home"),
+    (Key::End, "End"),
+    (Key::Up, "Up"),
+    (Key::Down, "Down"),
+    (Key::Left, "Left"),
+    (Key::Right, "Right"),
+];
+
+fn key_name(key: Key) -> Option<&'static str> {
+    // Letter keys carry whether they were typed in upper case, which doesn't matter here.
+    let key = match key {
+        Key::A(_) => Key::A(false),
+        Key::B(_) => Key::B(false),
+        Key::C(_) => Key::C(false),
+        Key::D(_) => Key::D(false),
+        Key::E(_) => Key::E(false),
+        Key::F(_) => Key::F(false),
+        Key::G(_) => Key::G(false),
+        Key::H(_) => Key::H(false),
+        Key::I(_) => Key::I(false),
+        Key::J(_) => Key::J(false),
+        Key::K(_) => Key::K(false),
+        Key::L(_) => Key::L(false),
+        Key::M(_) => Key::M(false),
+        Key::N(_) => Key::N(false),
+        Key::O(_) => Key::O(false),
+        Key::P(_) => Key::P(false),
+        Key::Q(_) => Key::Q(false),
+        Key::R(_) => Key::R(false),
+        Key::S(_) => Key::S(false),
+        Key::T(_) => Key::T(false),
+        Key::U(_) => Key::U(false),
+        Key::V(_) => Key::V(false),
+        Key::W(_) => Key::W(false),
+        Key::X(_) => Key::X(false),
+        Key::Y(_) => Key::Y(false),
+        Key::Z(_) => Key::Z(false),
+        key => key,
+    };
+
+    KEY_NAMES
+        .iter()
+        .find(|(named_key, _)| *named_key == key)
+        .map(|(_, name)| *name)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Binding {
+    keys: String,
+    command: Command,
+}
+
+impl Binding {
+    fn new(keys: &str, command: Command) -> Self {
+        Self {
+            keys: keys.to_string(),
+            command,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum KeymapError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, serde_json::Error),
+    InvalidKeys(String),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Parse(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::InvalidKeys(keys) => write!(f, "\"{}\" is not a valid key combination", keys),
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+/// Which keys trigger which commands. A command can have several keys.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Keymap {
+    bindings: Vec<Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            bindings: vec![
+                Binding::new("Ctrl+O", Command::Open),
+                Binding::new("Ctrl+R", Command::OpenRecent),
+                Binding::new("Ctrl+S", Command::Save),
+                Binding::new("Ctrl+Shift+S", Command::SaveAs),
+                Binding::new("Ctrl+P", Command::ExportOutputs),
+                Binding::new("Ctrl+Q", Command::Quit),
+                Binding::new("Tab", Command::AddNode),
+                Binding::new("Shift+A", Command::AddNode),
+                Binding::new("Delete", Command::Delete),
+                Binding::new("Ctrl+D", Command::Duplicate),
+                Binding::new("Ctrl+C", Command::Copy),
+                Binding::new("Ctrl+X", Command::Cut),
+                Binding::new("Ctrl+V", Command::Paste),
+                Binding::new("Ctrl+Z", Command::Undo),
+                Binding::new("Ctrl+Shift+Z", Command::Redo),
+                Binding::new("Ctrl+Y", Command::Redo),
+                Binding::new("Home", Command::FrameAll),
+                Binding::new("F", Command::FrameSelected),
+                Binding::new("Ctrl+G", Command::CollapseSelection),
+                Binding::new("Ctrl+E", Command::EnterSubgraph),
+                Binding::new("Ctrl+Shift+E", Command::LeaveSubgraph),
+                Binding::new("Ctrl+Shift+G", Command::ToggleGrid),
+                Binding::new("Shift+Left", Command::AlignLeft),
+                Binding::new("Shift+Right", Command::AlignRight),
+                Binding::new("Shift+Up", Command::AlignTop),
+                Binding::new("Shift+Down", Command::AlignBottom),
+                Binding::new("Ctrl+Shift+H", Command::DistributeHorizontally),
+                Binding::new("Ctrl+Shift+V", Command::DistributeVertically),
+                Binding::new("Ctrl+L", Command::AutoLayout),
+                Binding::new("Ctrl+T", Command::ToggleViewer),
+                Binding::new("Ctrl+M", Command::TogglePreview),
+                Binding::new("Ctrl+H", Command::ShowShortcuts),
+            ],
+        }
+    }
+}
+
+impl Keymap {
+    fn path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("keymap.json"))
+    }
+
+    /// Loads the keymap from the config directory. If there is no keymap file yet, one with the
+    /// default bindings is written there so it can be edited. Without a config directory the
+    /// default bindings are used.
+    pub fn load() -> Result<Self, KeymapError> {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Ok(Self::default()),
+        };
+
+        if !path.is_file() {
+            let keymap = Self::default();
+            keymap.save(path)?;
+            return Ok(keymap);
+        }
+
+        let json = fs::read_to_string(&path).map_err(|e| KeymapError::Io(path.clone(), e))?;
+        let keymap: Self =
+            serde_json::from_str(&json).map_err(|e| KeymapError::Parse(path.clone(), e))?;
+
+        if let Some(binding) = keymap
+            .bindings
+            .iter()
+            .find(|binding| KeyChord::parse(&binding.keys).is_none())
+        {
+            return Err(KeymapError::InvalidKeys(binding.keys.clone()));
+        }
+
+        Ok(keymap)
+    }
+
+    fn save(&self, path: PathBuf) -> Result<(), KeymapError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| KeymapError::Io(dir.to_owned(), e))?;
+        }
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| KeymapError::Parse(path.clone(), e))?;
+        fs::write(&path, json).map_err(|e| KeymapError::Io(path, e))
+    }
+
+    /// Returns the command bound to the chord, if any.
+    pub fn command(&self, chord: &KeyChord) -> Option<Command> {
+        self.bindings
+            .iter()
+            .find(|binding| KeyChord::parse(&binding.keys).as_ref() == Some(chord))
+            .map(|binding| binding.command)
+    }
+
+    /// Returns the chords bound to a command, written the same way for every binding.
+    pub fn chords(&self, command: Command) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.command == command)
+            .filter_map(|binding| KeyChord::parse(&binding.keys))
+            .map(|chord| chord.to_string())
+            .collect()
+    }
+}
@@ -0,0 +1,340 @@
+use crate::{
+    node_graph_spatial::{Location, NodeGraphSpatial},
+    shared::*,
+};
+use kanter_core::node_graph::{NodeGraph, NodeId};
+use orbtk::prelude::*;
+use std::cmp::Ordering;
+
+/// The distance between grid lines in graph space, nodes snap to these.
+pub const GRID_SIZE: f64 = 15.;
+/// The horizontal distance between the left sides of two neighboring layers in the automatic
+/// layout.
+const LAYER_SPACING: f64 = NODE_WIDTH * 2.;
+/// The vertical distance between the tops of two nodes in the same layer.
+const ROW_SPACING: f64 = NODE_HEIGHT * 1.5;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Alignment {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// Returns the grid point closest to `point`.
+pub fn snap(point: Point) -> Point {
+    Point::new(
+        (point.x / GRID_SIZE).round() * GRID_SIZE,
+        (point.y / GRID_SIZE).round() * GRID_SIZE,
+    )
+}
+
+fn selected_locations<'a>(
+    graph: &'a mut NodeGraphSpatial,
+    node_ids: &'a [NodeId],
+) -> impl Iterator<Item = &'a mut Location> {
+    graph
+        .locations
+        .iter_mut()
+        .filter(move |location| node_ids.contains(&location.node_id))
+}
+
+/// Lines up the nodes with the node furthest in the direction of `alignment`.
+pub fn align(graph: &mut NodeGraphSpatial, node_ids: &[NodeId], alignment: Alignment) {
+    let coordinate = |location: &Location| match alignment {
+        Alignment::Left | Alignment::Right => location.point.0,
+        Alignment::Top | Alignment::Bottom => location.point.1,
+    };
+
+    let coordinates = selected_locations(graph, node_ids).map(|location| coordinate(location));
+    let target = match alignment {
+        Alignment::Left | Alignment::Top => coordinates.fold(f64::MAX, f64::min),
+        Alignment::Right | Alignment::Bottom => coordinates.fold(f64::MIN, f64::max),
+    };
+
+    for location in selected_locations(graph, node_ids) {
+        match alignment {
+            Alignment::Left | Alignment::Right => location.point.0 = target,
+            Alignment::Top | Alignment::Bottom => location.point.1 = target,
+        }
+    }
+}
+
+/// Spaces the nodes evenly between the two outermost nodes along `axis`.
+pub fn distribute(graph: &mut NodeGraphSpatial, node_ids: &[NodeId], axis: Axis) {
+    let mut locations: Vec<&mut Location> = selected_locations(graph, node_ids).collect();
+    if locations.len() < 3 {
+        return;
+    }
+
+    let coordinate = |location: &Location| match axis {
+        Axis::Horizontal => location.point.0,
+        Axis::Vertical => location.point.1,
+    };
+    locations.sort_by(|a, b| {
+        coordinate(a)
+            .partial_cmp(&coordinate(b))
+            .unwrap_or(Ordering::Equal)
+    });
+
+    let first = coordinate(&locations[0]);
+    let last = coordinate(&locations[locations.len() - 1]);
+    let step = (last - first) / (locations.len() - 1) as f64;
+
+    for (i, location) in locations.into_iter().enumerate() {
+        let position = first + step * i as f64;
+
+        match axis {
+            Axis::Horizontal => location.point.0 = position,
+            Axis::Vertical => location.point.1 = position,
+        }
+    }
+}
+
+/// Returns a position for every node so that edges go from left to right. Each node is put in
+/// the layer after the furthest layer of the nodes feeding into it, and nodes in a layer are
+/// ordered by the average row of those nodes to keep edges from crossing.
+fn layered_positions(node_graph: &NodeGraph) -> Vec<(NodeId, (f64, f64))> {
+    let mut layers: Vec<(NodeId, usize)> = node_graph
+        .node_ids()
+        .into_iter()
+        .map(|node_id| (node_id, 0))
+        .collect();
+    let layer_of = |layers: &[(NodeId, usize)], node_id: NodeId| {
+        layers
+            .iter()
+            .find(|(layer_node_id, _)| *layer_node_id == node_id)
+            .map_or(0, |(_, layer)| *layer)
+    };
+
+    // The graph has no cycles, so this settles after at most one pass per node.
+    for _ in 0..layers.len() {
+        let mut changed = false;
+
+        for edge in &node_graph.edges {
+            let layer = layer_of(&layers, edge.output_id) + 1;
+
+            if let Some((_, input_layer)) = layers
+                .iter_mut()
+                .find(|(node_id, _)| *node_id == edge.input_id)
+            {
+                if *input_layer < layer {
+                    *input_layer = layer;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let layer_count = layers.iter().map(|(_, layer)| layer + 1).max().unwrap_or(0);
+    let mut rows: Vec<(NodeId, f64)> = Vec::new();
+    let mut positions = Vec::new();
+
+    for layer in 0..layer_count {
+        let mut layer_nodes: Vec<(NodeId, f64)> = layers
+            .iter()
+            .filter(|(_, node_layer)| *node_layer == layer)
+            .map(|(node_id, _)| {
+                let upstream_rows: Vec<f64> = node_graph
+                    .edges
+                    .iter()
+                    .filter(|edge| edge.input_id == *node_id)
+                    .filter_map(|edge| {
+                        rows.iter()
+                            .find(|(row_node_id, _)| *row_node_id == edge.output_id)
+                            .map(|(_, row)| *row)
+                    })
+                    .collect();
+
+                let barycenter = if upstream_rows.is_empty() {
+                    f64::MAX
+                } else {
+                    upstream_rows.iter().sum::<f64>() / upstream_rows.len() as f64
+                };
+
+                (*node_id, barycenter)
+            })
+            .collect();
+        layer_nodes.sort_by(|(a_id, a), (b_id, b)| {
+            a.partial_cmp(b)
+                .unwrap_or(Ordering::Equal)
+                .then(a_id.0.cmp(&b_id.0))
+        });
+
+        let center = (layer_nodes.len() as f64 - 1.) / 2.;
+        for (row, (node_id, _)) in layer_nodes.into_iter().enumerate() {
+            rows.push((node_id, row as f64));
+            positions.push((
+                node_id,
+                (
+                    layer as f64 * LAYER_SPACING,
+                    (row as f64 - center) * ROW_SPACING,
+                ),
+            ));
+        }
+    }
+
+    positions
+}
+
+/// Moves every node into a left to right layout based on how they are connected.
+pub fn auto_layout(graph: &mut NodeGraphSpatial) {
+    graph.locations = layered_positions(&graph.node_graph)
+        .into_iter()
+        .map(|(node_id, point)| Location { node_id, point })
+        .collect();
+}
+
+/// Gives nodes without a location one from the automatic layout, below the nodes that have
+/// one. Graphs with no locations at all get laid out entirely.
+pub fn layout_missing(graph: &mut NodeGraphSpatial) {
+    let node_ids = graph.node_graph.node_ids();
+    let missing: Vec<NodeId> = node_ids
+        .iter()
+        .copied()
+        .filter(|node_id| {
+            !graph
+                .locations
+                .iter()
+                .any(|location| location.node_id == *node_id)
+        })
+        .collect();
+
+    if missing.is_empty() {
+        return;
+    }
+    if missing.len() == node_ids.len() {
+        auto_layout(graph);
+        return;
+    }
+
+    let left = graph
+        .locations
+        .iter()
+        .map(|location| location.point.0)
+        .fold(f64::MAX, f64::min);
+    let bottom = graph
+        .locations
+        .iter()
+        .map(|location| location.point.1 + NODE_HEIGHT)
+        .fold(f64::MIN, f64::max);
+
+    let positions: Vec<(NodeId, (f64, f64))> = layered_positions(&graph.node_graph)
+        .into_iter()
+        .filter(|(node_id, _)| missing.contains(node_id))
+        .collect();
+    let missing_left = positions
+        .iter()
+        .map(|(_, point)| point.0)
+        .fold(f64::MAX, f64::min);
+    let missing_top = positions
+        .iter()
+        .map(|(_, point)| point.1)
+        .fold(f64::MAX, f64::min);
+
+    for (node_id, point) in positions {
+        graph.locations.push(Location {
+            node_id,
+            point: (
+                left + point.0 - missing_left,
+                bottom + ROW_SPACING - NODE_HEIGHT + point.1 - missing_top,
+            ),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kanter_core::{
+        node::{MixType, Node as CoreNode, NodeType, Side},
+        node_graph::SlotId,
+    };
+
+    fn add_node(graph: &mut NodeGraphSpatial, node_type: NodeType) -> NodeId {
+        graph.node_graph.add_node(CoreNode::new(node_type)).unwrap()
+    }
+
+    fn connect(graph: &mut NodeGraphSpatial, output_id: NodeId, input_id: NodeId, slot: u32) {
+        graph
+            .node_graph
+            .connect_arbitrary(
+                output_id,
+                Side::Output,
+                SlotId(0),
+                input_id,
+                Side::Input,
+                SlotId(slot),
+            )
+            .unwrap();
+    }
+
+    fn point(graph: &NodeGraphSpatial, node_id: NodeId) -> (f64, f64) {
+        graph
+            .locations
+            .iter()
+            .find(|location| location.node_id == node_id)
+            .unwrap()
+            .point
+    }
+
+    #[test]
+    fn auto_layout_puts_nodes_after_their_inputs() {
+        let mut graph = NodeGraphSpatial::default();
+        let value = add_node(&mut graph, NodeType::Value(0.5));
+        let add = add_node(&mut graph, NodeType::Mix(MixType::Add));
+        let other_value = add_node(&mut graph, NodeType::Value(1.));
+        let mix = add_node(&mut graph, NodeType::Mix(MixType::Multiply));
+        connect(&mut graph, value, add, 0);
+        connect(&mut graph, add, mix, 0);
+        connect(&mut graph, other_value, mix, 1);
+
+        auto_layout(&mut graph);
+
+        assert_eq!(graph.locations.len(), 4);
+        assert_eq!(point(&graph, value).0, 0.);
+        assert_eq!(point(&graph, other_value).0, 0.);
+        assert_eq!(point(&graph, add).0, LAYER_SPACING);
+        assert_eq!(point(&graph, mix).0, LAYER_SPACING * 2.);
+        assert_ne!(point(&graph, value).1, point(&graph, other_value).1);
+    }
+
+    #[test]
+    fn auto_layout_centers_layers_vertically() {
+        let mut graph = NodeGraphSpatial::default();
+        let top = add_node(&mut graph, NodeType::Value(0.));
+        let bottom = add_node(&mut graph, NodeType::Value(1.));
+
+        auto_layout(&mut graph);
+
+        assert_eq!(point(&graph, top).1, -ROW_SPACING / 2.);
+        assert_eq!(point(&graph, bottom).1, ROW_SPACING / 2.);
+    }
+
+    #[test]
+    fn layout_missing_keeps_existing_locations() {
+        let mut graph = NodeGraphSpatial::default();
+        let placed = add_node(&mut graph, NodeType::Value(0.));
+        let missing = add_node(&mut graph, NodeType::Value(1.));
+        graph.locations.push(Location {
+            node_id: placed,
+            point: (30., 45.),
+        });
+
+        layout_missing(&mut graph);
+
+        assert_eq!(point(&graph, placed), (30., 45.));
+        assert_eq!(point(&graph, missing), (30., 45. + ROW_SPACING));
+    }
+}
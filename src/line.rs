@@ -1,9 +1,23 @@
+use crate::{connection::SlotType, view::View};
 use orbtk::prelude::*;
 
+const LINE_WIDTH: f64 = 3.;
+const LINE_WIDTH_HIGHLIGHTED: f64 = 5.;
+const SELECTED_COLOR: Color = Color::rgb(255, 255, 255);
+/// The shortest distance from an end point to its control point, so short and backwards edges
+/// still leave their slots horizontally.
+const TANGENT_MIN: f64 = 30.;
+/// How many straight segments a curve is split into when hit testing it.
+const HIT_TEST_SEGMENTS: usize = 32;
+
 widget!(
     Line<LineState> {
         start_point: Point,
-        end_point: Point
+        end_point: Point,
+        view: View,
+        slot_type: SlotType,
+        hovered: bool,
+        selected: bool
     }
 );
 
@@ -12,6 +26,10 @@ impl Template for Line {
         self.name("Line")
             .start_point(Point { x: 0., y: 0. })
             .end_point(Point { x: 0., y: 0. })
+            .view(View::default())
+            .slot_type(SlotType::default())
+            .hovered(false)
+            .selected(false)
     }
 
     fn render_object(&self) -> Box<dyn RenderObject> {
@@ -24,29 +42,108 @@ pub struct LineState {}
 
 impl State for LineState {}
 
+/// Returns the two control points of the cubic bezier curve from `start` to `end`. The curve
+/// leaves `start` to the right and enters `end` from the left, like an edge from an output slot
+/// to an input slot.
+pub fn control_points(start: Point, end: Point) -> (Point, Point) {
+    let tangent = ((end.x - start.x).abs() * 0.5).max(TANGENT_MIN);
+
+    (
+        Point::new(start.x + tangent, start.y),
+        Point::new(end.x - tangent, end.y),
+    )
+}
+
+fn point_on_curve(start: Point, end: Point, t: f64) -> Point {
+    let (control_1, control_2) = control_points(start, end);
+    let u = 1. - t;
+
+    let a = u * u * u;
+    let b = 3. * u * u * t;
+    let c = 3. * u * t * t;
+    let d = t * t * t;
+
+    Point::new(
+        a * start.x + b * control_1.x + c * control_2.x + d * end.x,
+        a * start.y + b * control_1.y + c * control_2.y + d * end.y,
+    )
+}
+
+fn distance_to_segment(point: Point, a: Point, b: Point) -> f64 {
+    let length_squared = (b.x - a.x).powi(2) + (b.y - a.y).powi(2);
+    if length_squared == 0. {
+        return point.distance(a);
+    }
+
+    let t = (((point.x - a.x) * (b.x - a.x) + (point.y - a.y) * (b.y - a.y)) / length_squared)
+        .max(0.)
+        .min(1.);
+
+    point.distance(Point::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t))
+}
+
+/// Returns the approximate shortest distance from `point` to the curve from `start` to `end`.
+pub fn distance_to_curve(point: Point, start: Point, end: Point) -> f64 {
+    let mut distance = f64::MAX;
+    let mut previous = start;
+
+    for i in 1..=HIT_TEST_SEGMENTS {
+        let next = point_on_curve(start, end, i as f64 / HIT_TEST_SEGMENTS as f64);
+        distance = distance.min(distance_to_segment(point, previous, next));
+        previous = next;
+    }
+
+    distance
+}
+
 pub struct LineRenderObject;
 
 impl RenderObject for LineRenderObject {
     fn render_self(&self, ctx: &mut Context<'_>, global_position: &Point) {
-        let (start_point, end_point) = {
+        let (start_point, end_point, control_1, control_2, slot_type, hovered, selected) = {
             let widget = ctx.widget();
+            let view = widget.get::<View>("view");
+            let start_point = *widget.get::<Point>("start_point");
+            let end_point = *widget.get::<Point>("end_point");
+            let (control_1, control_2) = control_points(start_point, end_point);
+
             (
-                *widget.get::<Point>("start_point"),
-                *widget.get::<Point>("end_point"),
+                view.to_screen(start_point),
+                view.to_screen(end_point),
+                view.to_screen(control_1),
+                view.to_screen(control_2),
+                *widget.get::<SlotType>("slot_type"),
+                *widget.get::<bool>("hovered"),
+                *widget.get::<bool>("selected"),
             )
         };
 
+        let line_width = if hovered || selected {
+            LINE_WIDTH_HIGHLIGHTED
+        } else {
+            LINE_WIDTH
+        };
+        let color = if selected {
+            SELECTED_COLOR
+        } else {
+            slot_type.color()
+        };
+
+        let x = global_position.x;
+        let y = global_position.y;
+
         let rc2d = ctx.render_context_2_d();
         rc2d.begin_path();
-        rc2d.set_line_width(3.);
-        rc2d.set_stroke_style(Brush::SolidColor(Color::rgb(0, 0, 0)));
-        rc2d.move_to(
-            global_position.x + start_point.x,
-            global_position.y + start_point.y,
-        );
-        rc2d.line_to(
-            global_position.x + end_point.x,
-            global_position.y + end_point.y,
+        rc2d.set_line_width(line_width);
+        rc2d.set_stroke_style(Brush::SolidColor(color));
+        rc2d.move_to(x + start_point.x, y + start_point.y);
+        rc2d.bezier_curve_to(
+            x + control_1.x,
+            y + control_1.y,
+            x + control_2.x,
+            y + control_2.y,
+            x + end_point.x,
+            y + end_point.y,
         );
         rc2d.stroke();
     }
@@ -1,5 +1,15 @@
-use crate::{shared::*, workspace::Workspace};
+use crate::{
+    config::Config,
+    file_browser::{FileBrowser, FileBrowserMode, OptionFileBrowserMode},
+    material_preview::MaterialPreview,
+    rasterizer::Material,
+    recovery,
+    shared::*,
+    texture_viewer::{TextureViewer, ViewerSource},
+    workspace::Workspace,
+};
 use orbtk::prelude::*;
+use std::{env, path::Path, process};
 
 mod shared;
 mod workspace;
@@ -7,31 +17,103 @@ mod node_container;
 mod menu_property;
 mod node;
 mod slot;
+mod blend;
+mod channels;
+mod subgraph;
+mod texture_viewer;
 mod edge;
+mod export;
+mod config;
+mod file_browser;
+mod filter;
+mod generator;
+mod layout;
 mod line;
+mod material_preview;
+mod clipboard;
+mod connection;
+mod curve_editor;
+mod grid;
+mod history;
+mod keymap;
+mod node_graph_spatial;
+mod node_registry;
+mod processing;
+mod rasterizer;
+mod recovery;
+mod render;
+mod thumbnail;
+mod view;
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    // `kanter render` processes a graph file without opening a window.
+    if args.get(1).map(String::as_str) == Some("render") {
+        match render::run(&args[2..]) {
+            Ok(written) => {
+                for path in written {
+                    println!("{}", path.display());
+                }
+            }
+            Err(e) => {
+                eprintln!("error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
     Application::new()
         .window(|ctx| {
+            let main_view = MainView::create().build(ctx);
+
             Window::create()
                 .title("Kanter")
                 .position((300., 300.))
                 .size(1024., 768.)
                 .resizeable(true)
-                .child(MainView::create().build(ctx))
+                // Closing the window goes through the same prompt as quitting from the menu, the
+                // window is only closed once `quit_action` has run.
+                .on_window_event(move |states, event| match event {
+                    WindowEvent::CloseRequest => {
+                        states.get_mut::<MainState>(main_view).action_main(ActionMain::Quit);
+                        true
+                    }
+                    _ => false,
+                })
+                .child(main_view)
                 .build(ctx)
         })
         .run();
 }
 
 widget!(MainView<MainState> {
-    node_container_entity: u32
+    node_container_entity: u32,
+    action_main: OptionActionMain
 });
 
+/// A question the user has to answer before going on.
+enum Prompt {
+    /// The action would throw away unsaved changes.
+    DiscardChanges(ActionMain),
+    /// An earlier session was closed without saving its changes.
+    Recover,
+}
+
 #[derive(Default, AsAny)]
 pub struct MainState {
     pub option_action_main: OptionActionMain,
     workspace: Entity,
+    texture_viewer: Entity,
+    material_preview: Entity,
+    file_browser: Entity,
+    recent_files: Entity,
+    recent_files_list: Entity,
+    config: Config,
+    /// The graph path the recent files and title bar were last updated for.
+    graph_path: String,
+    prompt: Option<Prompt>,
 }
 
 impl MainState {
@@ -41,11 +123,31 @@ impl MainState {
 
     fn node_container_action(&mut self, ctx: &mut Context) {
         if let Some(action_main) = &self.option_action_main {
-            let path = ctx.child("graph_path").clone::<String16>("text");
+            let export_dir = ctx.child("export_dir").clone::<String16>("text");
 
             let action_to_send = match action_main {
-                ActionMain::LoadGraph(_) => Some(ActionMain::LoadGraph(path.to_string())),
-                ActionMain::SaveGraph(_) => Some(ActionMain::SaveGraph(path.to_string())),
+                ActionMain::LoadGraph(path) => {
+                    ctx.get_widget(self.recent_files).set::<bool>("open", false);
+                    Some(ActionMain::LoadGraph(path.clone()))
+                }
+                ActionMain::Save if !self.graph_path.is_empty() => {
+                    Some(ActionMain::SaveGraph(self.graph_path.clone()))
+                }
+                ActionMain::FileChosen(FileBrowserMode::Open, path) => {
+                    Some(ActionMain::LoadGraph(path.clone()))
+                }
+                ActionMain::FileChosen(FileBrowserMode::Save, path) => {
+                    Some(ActionMain::SaveGraph(path.clone()))
+                }
+                ActionMain::FileChosen(FileBrowserMode::Image, path) => {
+                    Some(ActionMain::SetImagePath(path.clone()))
+                }
+                ActionMain::Recover => Some(ActionMain::Recover),
+                ActionMain::ExportOutputs(_) => {
+                    Some(ActionMain::ExportOutputs(export_dir.to_string()))
+                }
+                ActionMain::FrameAll => Some(ActionMain::FrameAll),
+                ActionMain::FrameSelected => Some(ActionMain::FrameSelected),
                 _ => None,
             };
 
@@ -56,20 +158,252 @@ impl MainState {
         }
     }
 
+    fn show_error(&mut self, ctx: &mut Context, message: String) {
+        ctx.get_widget(self.workspace)
+            .set::<String16>("error_message", String16::from(message));
+    }
+
+    /// Holds back actions that would throw away unsaved changes until the user has confirmed
+    /// them, and carries out the answers to prompts.
+    fn prompt_action(&mut self, ctx: &mut Context) {
+        let modified = *ctx.get_widget(self.workspace).get::<bool>("modified");
+
+        match self.option_action_main.take() {
+            Some(ActionMain::PromptAnswer(accepted)) => match self.prompt.take() {
+                Some(Prompt::DiscardChanges(action_main)) if accepted => {
+                    self.option_action_main = Some(action_main);
+                }
+                Some(Prompt::Recover) if accepted => {
+                    self.option_action_main = Some(ActionMain::Recover);
+                }
+                Some(Prompt::Recover) => {
+                    if let Err(e) = recovery::discard_previous() {
+                        self.show_error(ctx, format!("Could not remove the recovery file: {}", e));
+                    }
+                }
+                _ => {}
+            },
+            Some(action_main) if action_main.discards_changes() && modified => {
+                self.prompt = Some(Prompt::DiscardChanges(action_main));
+            }
+            option_action_main => self.option_action_main = option_action_main,
+        }
+
+        let (text, accept, reject) = match self.prompt {
+            Some(Prompt::DiscardChanges(_)) => {
+                ("The graph has unsaved changes, discard them?", "Discard", "Cancel")
+            }
+            Some(Prompt::Recover) => (
+                "Kanter was closed with unsaved changes, recover them?",
+                "Recover",
+                "Discard",
+            ),
+            None => {
+                ctx.child("prompt")
+                    .set::<Visibility>("visibility", Visibility::Collapsed);
+                return;
+            }
+        };
+
+        ctx.child("prompt_text")
+            .set::<String16>("text", String16::from(text));
+        ctx.child("prompt_accept")
+            .set::<String16>("text", String16::from(accept));
+        ctx.child("prompt_reject")
+            .set::<String16>("text", String16::from(reject));
+        ctx.child("prompt")
+            .set::<Visibility>("visibility", Visibility::Visible);
+    }
+
+    fn quit_action(&mut self, ctx: &mut Context) {
+        if let Some(ActionMain::Quit) = self.option_action_main {
+            if let Err(e) = recovery::discard() {
+                self.show_error(ctx, format!("Could not remove the recovery file: {}", e));
+                return;
+            }
+            ctx.send_window_request(WindowRequest::Close);
+        }
+    }
+
+    /// Shows the error banner while there is an error message, and clears the message when the
+    /// banner is dismissed.
+    fn error_banner(&mut self, ctx: &mut Context) {
+        if let Some(ActionMain::DismissError) = self.option_action_main {
+            ctx.get_widget(self.workspace)
+                .set::<String16>("error_message", String16::default());
+        }
+
+        let visibility = if ctx
+            .get_widget(self.workspace)
+            .get::<String16>("error_message")
+            .is_empty()
+        {
+            Visibility::Collapsed
+        } else {
+            Visibility::Visible
+        };
+        ctx.child("error_banner")
+            .set::<Visibility>("visibility", visibility);
+    }
+
+    /// Opens the file browser when a button asks for it, or when the node container wants an
+    /// image path.
+    fn file_browser_action(&mut self, ctx: &mut Context) {
+        let mode = match (
+            &self.option_action_main,
+            ctx.get_widget(self.workspace)
+                .clone::<OptionFileBrowserMode>("browse_request"),
+        ) {
+            (Some(ActionMain::Browse(mode)), _) => *mode,
+            (Some(ActionMain::Save), _) if self.graph_path.is_empty() => FileBrowserMode::Save,
+            (_, Some(mode)) => mode,
+            _ => return,
+        };
+        ctx.get_widget(self.workspace)
+            .set::<OptionFileBrowserMode>("browse_request", None);
+
+        let graph_path = Path::new(&self.graph_path);
+        let directory = graph_path
+            .parent()
+            .map_or(String::new(), |parent| parent.to_string_lossy().to_string());
+        let file_name = match mode {
+            FileBrowserMode::Save => graph_path
+                .file_name()
+                .map_or(String::new(), |file_name| file_name.to_string_lossy().to_string()),
+            _ => String::new(),
+        };
+
+        let mut file_browser = ctx.get_widget(self.file_browser);
+        file_browser.set::<FileBrowserMode>("mode", mode);
+        file_browser.set::<String16>("directory", String16::from(directory));
+        file_browser.set::<String16>("file_name", String16::from(file_name));
+        file_browser.set::<bool>("open", true);
+    }
+
+    /// Adds newly opened or saved graphs to the recent files and shows the current file and
+    /// whether it has unsaved changes in the title bar.
+    fn refresh_graph_path(&mut self, ctx: &mut Context) {
+        let graph_path = ctx
+            .get_widget(self.workspace)
+            .get::<String16>("graph_path")
+            .to_string();
+
+        if graph_path != self.graph_path {
+            self.graph_path = graph_path;
+
+            if !self.graph_path.is_empty() {
+                self.config.add_recent_file(&self.graph_path);
+                if let Err(e) = self.config.save() {
+                    self.show_error(ctx, format!("Could not save recent files: {}", e));
+                }
+                self.refresh_recent_files(ctx);
+            }
+        }
+
+        let file_name = if self.graph_path.is_empty() {
+            "Untitled"
+        } else {
+            self.graph_path.as_str()
+        };
+        let modified_marker = if *ctx.get_widget(self.workspace).get::<bool>("modified") {
+            "*"
+        } else {
+            ""
+        };
+        let title = format!("Kanter - {}{}", file_name, modified_marker);
+
+        let mut title_bar = ctx.child("title_bar");
+        if title_bar.get::<String16>("text").to_string() != title {
+            title_bar.set::<String16>("text", String16::from(title));
+        }
+    }
+
+    fn refresh_recent_files(&mut self, ctx: &mut Context) {
+        ctx.clear_children_of(self.recent_files_list);
+
+        let id = ctx.entity;
+        let recent_files_list = self.recent_files_list;
+        let bc = &mut ctx.build_context();
+
+        for recent_file in &self.config.recent_files {
+            let path = recent_file.clone();
+            let button = Button::create()
+                .element("button")
+                .on_click(move |states, _| {
+                    states
+                        .get_mut::<MainState>(id)
+                        .action_main(ActionMain::LoadGraph(path.clone()));
+                    true
+                })
+                .text(recent_file.as_str())
+                .build(bc);
+            bc.append_child(recent_files_list, button);
+        }
+    }
+
+    fn recent_files_action(&mut self, ctx: &mut Context) {
+        if let Some(ActionMain::ToggleRecentFiles) = self.option_action_main {
+            let mut recent_files = ctx.get_widget(self.recent_files);
+            let open = *recent_files.get::<bool>("open");
+            recent_files.set::<bool>("open", !open);
+        }
+    }
+
+    fn texture_viewer_action(&mut self, ctx: &mut Context) {
+        if let Some(ActionMain::ToggleViewer) = self.option_action_main {
+            let mut texture_viewer = ctx.get_widget(self.texture_viewer);
+            let open = *texture_viewer.get::<bool>("open");
+            texture_viewer.set::<bool>("open", !open);
+        }
+    }
+
+    fn material_preview_action(&mut self, ctx: &mut Context) {
+        if let Some(ActionMain::TogglePreview) = self.option_action_main {
+            let mut material_preview = ctx.get_widget(self.material_preview);
+            let open = *material_preview.get::<bool>("open");
+            material_preview.set::<bool>("open", !open);
+        }
+    }
+
     fn workspace_action(&mut self, ctx: &mut Context) {
         if let Some(action_main) = &self.option_action_main {
-            if let ActionMain::MenuNode(p) = action_main {
-                ctx.get_widget(self.workspace)
-                    .set::<OptionActionMain>("action_main", Some(ActionMain::MenuNode(*p)));
+            match action_main {
+                ActionMain::MenuNode(_) | ActionMain::ShowShortcuts => {
+                    ctx.get_widget(self.workspace)
+                        .set::<OptionActionMain>("action_main", Some(action_main.clone()));
+                }
+                _ => {}
             }
         }
     }
 }
 
 impl State for MainState {
+    fn init(&mut self, _: &mut Registry, ctx: &mut Context) {
+        self.config = Config::load();
+        self.refresh_recent_files(ctx);
+
+        if recovery::take_previous() {
+            self.prompt = Some(Prompt::Recover);
+        }
+    }
+
     fn update(&mut self, _: &mut Registry, ctx: &mut Context) {
+        if let Some(action_main) = ctx.widget().clone::<OptionActionMain>("action_main") {
+            self.option_action_main = Some(action_main);
+            ctx.widget().set::<OptionActionMain>("action_main", None);
+        }
+
+        self.prompt_action(ctx);
+        self.quit_action(ctx);
         self.node_container_action(ctx);
+        self.file_browser_action(ctx);
+        self.recent_files_action(ctx);
+        self.texture_viewer_action(ctx);
+        self.material_preview_action(ctx);
         self.workspace_action(ctx);
+        self.refresh_graph_path(ctx);
+        self.error_banner(ctx);
 
         self.option_action_main = None;
     }
@@ -77,58 +411,308 @@ impl State for MainState {
 
 impl Template for MainView {
     fn template(mut self, id: Entity, ctx: &mut BuildContext) -> Self {
-        let workspace = Workspace::create().build(ctx);
+        let workspace = Workspace::create()
+            .error_message("")
+            .graph_path("")
+            .modified(false)
+            .viewer_source(ViewerSource::default())
+            .preview_material(Material::default())
+            .build(ctx);
         self.state_mut().workspace = workspace;
 
-        self.name("MainView").child(workspace).child(
-            Stack::create()
-                .orientation(Orientation::Horizontal)
-                .child(
-                    Button::create()
-                        .element("button")
-                        .on_click(move |states, _| {
-                            states
-                                .get_mut::<MainState>(id)
-                                .action_main(ActionMain::LoadGraph("".to_string()));
-                            true
-                        })
-                        .text("Load graph")
-                        .width(100.)
-                        .build(ctx),
-                )
-                .child(
-                    Button::create()
-                        .element("button")
-                        .on_click(move |states, _| {
-                            states
-                                .get_mut::<MainState>(id)
-                                .action_main(ActionMain::SaveGraph("".to_string()));
-                            true
-                        })
-                        .text("Save graph")
-                        .width(100.)
-                        .build(ctx),
-                )
-                .child(
-                    TextBox::create()
-                        .id("graph_path")
-                        .text("data/invert_graph.json")
-                        .build(ctx),
-                )
-                .child(
-                    Button::create()
-                        .element("button")
-                        .on_click(move |states, p| {
-                            states
-                                .get_mut::<MainState>(id)
-                                .action_main(ActionMain::MenuNode(p));
-                            true
-                        })
-                        .text("Add node")
-                        .width(100.)
-                        .build(ctx),
-                )
-                .build(ctx),
-        )
+        let texture_viewer = TextureViewer::create()
+            .source(("viewer_source", workspace))
+            .build(ctx);
+        self.state_mut().texture_viewer = texture_viewer;
+
+        let material_preview = MaterialPreview::create()
+            .material(("preview_material", workspace))
+            .build(ctx);
+        self.state_mut().material_preview = material_preview;
+
+        let file_browser = FileBrowser::create().target(id.0).build(ctx);
+        self.state_mut().file_browser = file_browser;
+
+        let recent_files_button = Button::create()
+            .element("button")
+            .on_click(move |states, _| {
+                states
+                    .get_mut::<MainState>(id)
+                    .action_main(ActionMain::ToggleRecentFiles);
+                true
+            })
+            .text("Recent")
+            .width(100.)
+            .build(ctx);
+        let recent_files_list = Stack::create().orientation("vertical").build(ctx);
+        let recent_files = Popup::create()
+            .target(recent_files_button)
+            .open(false)
+            .child(recent_files_list)
+            .build(ctx);
+        self.state_mut().recent_files = recent_files;
+        self.state_mut().recent_files_list = recent_files_list;
+
+        let error_banner = Container::create()
+            .id("error_banner")
+            .vertical_alignment("end")
+            .background("#aa0000")
+            .visibility(Visibility::Collapsed)
+            .child(
+                Stack::create()
+                    .orientation(Orientation::Horizontal)
+                    .child(
+                        Button::create()
+                            .element("button")
+                            .on_click(move |states, _| {
+                                states
+                                    .get_mut::<MainState>(id)
+                                    .action_main(ActionMain::DismissError);
+                                true
+                            })
+                            .text("Dismiss")
+                            .width(100.)
+                            .build(ctx),
+                    )
+                    .child(
+                        TextBlock::create()
+                            .text(("error_message", workspace))
+                            .element("text-block")
+                            .foreground("#ffffff")
+                            .margin((5., 5., 5., 5.))
+                            .build(ctx),
+                    )
+                    .build(ctx),
+            )
+            .build(ctx);
+
+        let toolbar = Stack::create()
+            .orientation(Orientation::Horizontal)
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, _| {
+                        states
+                            .get_mut::<MainState>(id)
+                            .action_main(ActionMain::Browse(FileBrowserMode::Open));
+                        true
+                    })
+                    .text("Open...")
+                    .width(100.)
+                    .build(ctx),
+            )
+            .child(recent_files_button)
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, _| {
+                        states
+                            .get_mut::<MainState>(id)
+                            .action_main(ActionMain::Save);
+                        true
+                    })
+                    .text("Save")
+                    .width(100.)
+                    .build(ctx),
+            )
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, _| {
+                        states
+                            .get_mut::<MainState>(id)
+                            .action_main(ActionMain::Browse(FileBrowserMode::Save));
+                        true
+                    })
+                    .text("Save as...")
+                    .width(100.)
+                    .build(ctx),
+            )
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, p| {
+                        states
+                            .get_mut::<MainState>(id)
+                            .action_main(ActionMain::MenuNode(p));
+                        true
+                    })
+                    .text("Add node")
+                    .width(100.)
+                    .build(ctx),
+            )
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, _| {
+                        states
+                            .get_mut::<MainState>(id)
+                            .action_main(ActionMain::FrameAll);
+                        true
+                    })
+                    .text("Frame all")
+                    .width(100.)
+                    .build(ctx),
+            )
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, _| {
+                        states
+                            .get_mut::<MainState>(id)
+                            .action_main(ActionMain::FrameSelected);
+                        true
+                    })
+                    .text("Frame selected")
+                    .width(100.)
+                    .build(ctx),
+            )
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, _| {
+                        states
+                            .get_mut::<MainState>(id)
+                            .action_main(ActionMain::ExportOutputs("".to_string()));
+                        true
+                    })
+                    .text("Export all outputs")
+                    .width(150.)
+                    .build(ctx),
+            )
+            .child(
+                TextBox::create()
+                    .id("export_dir")
+                    .text("output")
+                    .build(ctx),
+            )
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, _| {
+                        states
+                            .get_mut::<MainState>(id)
+                            .action_main(ActionMain::ShowShortcuts);
+                        true
+                    })
+                    .text("Shortcuts")
+                    .width(100.)
+                    .build(ctx),
+            )
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, _| {
+                        states
+                            .get_mut::<MainState>(id)
+                            .action_main(ActionMain::ToggleViewer);
+                        true
+                    })
+                    .text("Viewer")
+                    .width(100.)
+                    .build(ctx),
+            )
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, _| {
+                        states
+                            .get_mut::<MainState>(id)
+                            .action_main(ActionMain::TogglePreview);
+                        true
+                    })
+                    .text("3D preview")
+                    .width(100.)
+                    .build(ctx),
+            )
+            .child(
+                Button::create()
+                    .element("button")
+                    .on_click(move |states, _| {
+                        states.get_mut::<MainState>(id).action_main(ActionMain::Quit);
+                        true
+                    })
+                    .text("Quit")
+                    .width(100.)
+                    .build(ctx),
+            )
+            .build(ctx);
+
+        let prompt = Container::create()
+            .id("prompt")
+            .horizontal_alignment("center")
+            .vertical_alignment("center")
+            .background("#444444")
+            .visibility(Visibility::Collapsed)
+            .child(
+                Stack::create()
+                    .orientation(Orientation::Vertical)
+                    .child(
+                        TextBlock::create()
+                            .id("prompt_text")
+                            .element("text-block")
+                            .margin((10., 10., 10., 10.))
+                            .text("")
+                            .build(ctx),
+                    )
+                    .child(
+                        Stack::create()
+                            .orientation(Orientation::Horizontal)
+                            .child(
+                                Button::create()
+                                    .id("prompt_accept")
+                                    .element("button")
+                                    .on_click(move |states, _| {
+                                        states
+                                            .get_mut::<MainState>(id)
+                                            .action_main(ActionMain::PromptAnswer(true));
+                                        true
+                                    })
+                                    .text("")
+                                    .width(100.)
+                                    .build(ctx),
+                            )
+                            .child(
+                                Button::create()
+                                    .id("prompt_reject")
+                                    .element("button")
+                                    .on_click(move |states, _| {
+                                        states
+                                            .get_mut::<MainState>(id)
+                                            .action_main(ActionMain::PromptAnswer(false));
+                                        true
+                                    })
+                                    .text("")
+                                    .width(100.)
+                                    .build(ctx),
+                            )
+                            .build(ctx),
+                    )
+                    .build(ctx),
+            )
+            .build(ctx);
+
+        let title_bar = TextBlock::create()
+            .id("title_bar")
+            .element("text-block")
+            .margin((5., 2., 5., 2.))
+            .text("Kanter - Untitled")
+            .build(ctx);
+
+        self.name("MainView")
+            .child(workspace)
+            .child(texture_viewer)
+            .child(material_preview)
+            .child(error_banner)
+            .child(
+                Stack::create()
+                    .orientation(Orientation::Vertical)
+                    .child(title_bar)
+                    .child(toolbar)
+                    .build(ctx),
+            )
+            .child(recent_files)
+            .child(file_browser)
+            .child(prompt)
     }
 }
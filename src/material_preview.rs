@@ -0,0 +1,365 @@
+use crate::{
+    processing::ImageData,
+    rasterizer::{render, Material, OrbitCamera, Shape},
+    shared::Indexable,
+    thumbnail::ThumbnailRenderObject,
+};
+use orbtk::{prelude::*, shell::MouseButton};
+use serde::{Deserialize, Serialize};
+use std::{cell::Cell, f32::consts::PI};
+
+const PANEL_WIDTH: f64 = 276.;
+const PREVIEW_SIZE: u32 = 256;
+/// Keeps the panel clear of the title bar and toolbar of the main view.
+const PANEL_TOP: f64 = 60.;
+/// How far the camera turns per pixel the mouse is dragged, in radians.
+const ORBIT_SPEED: f32 = 0.01;
+const ZOOM_STEP: f32 = 1.1;
+const DISTANCE_MIN: f32 = 2.5;
+const DISTANCE_MAX: f32 = 12.;
+/// Keeps the camera from flipping over when looking straight down or up.
+const PITCH_LIMIT: f32 = PI / 2. - 0.01;
+
+/// What an output node is used as in the material preview.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MaterialMap {
+    None,
+    Albedo,
+    Height,
+    Normal,
+    Roughness,
+    Metallic,
+}
+
+impl Default for MaterialMap {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl Indexable for MaterialMap {
+    fn index(&self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Albedo => 1,
+            Self::Height => 2,
+            Self::Normal => 3,
+            Self::Roughness => 4,
+            Self::Metallic => 5,
+        }
+    }
+
+    fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(Self::None),
+            1 => Some(Self::Albedo),
+            2 => Some(Self::Height),
+            3 => Some(Self::Normal),
+            4 => Some(Self::Roughness),
+            5 => Some(Self::Metallic),
+            _ => None,
+        }
+    }
+}
+
+into_property_source!(Material);
+
+/// A command from one of the buttons in the material preview.
+#[derive(Copy, Clone, Debug)]
+enum PreviewCommand {
+    Shape(Shape),
+    ResetCamera,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum PreviewMouse {
+    Press(Mouse),
+    Release,
+    Move(Point),
+    Scroll(Point),
+}
+
+// Shows the outputs of the graph as a material on a sphere, cube or plane. The image is
+// rendered on the CPU so the preview works on machines without a GPU.
+widget!(
+    MaterialPreview<MaterialPreviewState> {
+        open: bool,
+        material: Material
+    }
+);
+
+impl Template for MaterialPreview {
+    fn template(mut self, id: Entity, ctx: &mut BuildContext) -> Self {
+        let canvas = PreviewCanvas::create()
+            .width(PREVIEW_SIZE as f64)
+            .height(PREVIEW_SIZE as f64)
+            .margin((10., 5., 10., 5.))
+            .on_mouse_down(move |states, m| {
+                states
+                    .get::<MaterialPreviewState>(id)
+                    .mouse(PreviewMouse::Press(m));
+                true
+            })
+            .on_mouse_up(move |states, _| {
+                states
+                    .get::<MaterialPreviewState>(id)
+                    .mouse(PreviewMouse::Release);
+                true
+            })
+            .on_mouse_move(move |states, p| {
+                states
+                    .get::<MaterialPreviewState>(id)
+                    .mouse(PreviewMouse::Move(p));
+                true
+            })
+            .on_scroll(move |states, p| {
+                states
+                    .get::<MaterialPreviewState>(id)
+                    .mouse(PreviewMouse::Scroll(p));
+                true
+            })
+            .build(ctx);
+        let maps = TextBlock::create()
+            .element("text-block")
+            .margin((5., 2., 5., 2.))
+            .text("")
+            .build(ctx);
+
+        let state = self.state_mut();
+        state.canvas = canvas;
+        state.maps = maps;
+
+        self.name("MaterialPreview")
+            .open(false)
+            .material(Material::default())
+            .width(PANEL_WIDTH)
+            .horizontal_alignment("start")
+            .vertical_alignment("start")
+            .margin((0., PANEL_TOP, 0., 0.))
+            .visibility(Visibility::Collapsed)
+            .child(
+                Container::create()
+                    .background("#333333")
+                    .child(
+                        Stack::create()
+                            .orientation("vertical")
+                            .child(
+                                TextBlock::create()
+                                    .element("text-block")
+                                    .margin((5., 5., 5., 5.))
+                                    .text("Material preview")
+                                    .build(ctx),
+                            )
+                            .child(
+                                Stack::create()
+                                    .orientation("horizontal")
+                                    .child(Self::command_button(
+                                        ctx,
+                                        id,
+                                        "Sphere",
+                                        PreviewCommand::Shape(Shape::Sphere),
+                                    ))
+                                    .child(Self::command_button(
+                                        ctx,
+                                        id,
+                                        "Cube",
+                                        PreviewCommand::Shape(Shape::Cube),
+                                    ))
+                                    .child(Self::command_button(
+                                        ctx,
+                                        id,
+                                        "Plane",
+                                        PreviewCommand::Shape(Shape::Plane),
+                                    ))
+                                    .child(Self::command_button(
+                                        ctx,
+                                        id,
+                                        "Reset",
+                                        PreviewCommand::ResetCamera,
+                                    ))
+                                    .build(ctx),
+                            )
+                            .child(canvas)
+                            .child(maps)
+                            .child(
+                                TextBlock::create()
+                                    .element("text-block")
+                                    .margin((5., 2., 5., 5.))
+                                    .text("Drag to orbit, scroll to zoom")
+                                    .build(ctx),
+                            )
+                            .build(ctx),
+                    )
+                    .build(ctx),
+            )
+    }
+}
+
+impl MaterialPreview {
+    fn command_button(
+        ctx: &mut BuildContext,
+        id: Entity,
+        text: &str,
+        command: PreviewCommand,
+    ) -> Entity {
+        Button::create()
+            .element("button")
+            .on_click(move |states, _| {
+                states.get_mut::<MaterialPreviewState>(id).command(command);
+                true
+            })
+            .text(text)
+            .width(64.)
+            .build(ctx)
+    }
+}
+
+#[derive(Default, AsAny)]
+pub struct MaterialPreviewState {
+    command: Option<PreviewCommand>,
+    mouse: Cell<Option<PreviewMouse>>,
+    canvas: Entity,
+    maps: Entity,
+    material: Material,
+    shape: Shape,
+    camera: OrbitCamera,
+    /// Where an orbit started, and the camera at that point.
+    orbit_start: Option<(Point, OrbitCamera)>,
+    /// Whether the image has to be rendered again.
+    stale: bool,
+}
+
+impl State for MaterialPreviewState {
+    fn init(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
+        self.stale = true;
+        self.refresh_maps(ctx);
+    }
+
+    fn update(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
+        if !*ctx.widget().get::<bool>("open") {
+            ctx.widget()
+                .set::<Visibility>("visibility", Visibility::Collapsed);
+            self.orbit_start = None;
+            return;
+        }
+        ctx.widget()
+            .set::<Visibility>("visibility", Visibility::Visible);
+
+        let material = ctx.widget().clone::<Material>("material");
+        if !material.is_same(&self.material) {
+            self.material = material;
+            self.stale = true;
+            self.refresh_maps(ctx);
+        }
+
+        if let Some(command) = self.command.take() {
+            match command {
+                PreviewCommand::Shape(shape) => self.shape = shape,
+                PreviewCommand::ResetCamera => self.camera = OrbitCamera::default(),
+            }
+            self.stale = true;
+        }
+        if let Some(mouse) = self.mouse.take() {
+            self.handle_mouse(mouse);
+        }
+
+        if self.stale {
+            self.stale = false;
+
+            let image = render(
+                self.shape,
+                &self.material,
+                &self.camera,
+                PREVIEW_SIZE,
+                PREVIEW_SIZE,
+            );
+            ctx.get_widget(self.canvas).set::<ImageData>("image", image);
+        }
+    }
+}
+
+impl MaterialPreviewState {
+    fn command(&mut self, command: PreviewCommand) {
+        self.command = Some(command);
+    }
+
+    fn mouse(&self, mouse: PreviewMouse) {
+        self.mouse.set(Some(mouse));
+    }
+
+    fn handle_mouse(&mut self, mouse: PreviewMouse) {
+        match mouse {
+            PreviewMouse::Press(mouse) => {
+                if let MouseButton::Left | MouseButton::Middle = mouse.button {
+                    self.orbit_start = Some((mouse.position, self.camera));
+                }
+            }
+            PreviewMouse::Release => self.orbit_start = None,
+            PreviewMouse::Move(position) => {
+                if let Some((start, start_camera)) = self.orbit_start {
+                    self.camera.yaw =
+                        start_camera.yaw - (position.x - start.x) as f32 * ORBIT_SPEED;
+                    self.camera.pitch = (start_camera.pitch
+                        + (position.y - start.y) as f32 * ORBIT_SPEED)
+                        .max(-PITCH_LIMIT)
+                        .min(PITCH_LIMIT);
+                    self.stale = true;
+                }
+            }
+            PreviewMouse::Scroll(delta) => {
+                if delta.y > 0. {
+                    self.camera.distance /= ZOOM_STEP;
+                } else if delta.y < 0. {
+                    self.camera.distance *= ZOOM_STEP;
+                }
+                self.camera.distance = self.camera.distance.max(DISTANCE_MIN).min(DISTANCE_MAX);
+                self.stale = true;
+            }
+        }
+    }
+
+    /// Lists the maps the material has, or explains how to give it some.
+    fn refresh_maps(&mut self, ctx: &mut Context) {
+        let material = &self.material;
+        let maps: Vec<&str> = [
+            (material.albedo.is_some(), "albedo"),
+            (material.height.is_some(), "height"),
+            (material.normal.is_some(), "normal"),
+            (material.roughness.is_some(), "roughness"),
+            (material.metallic.is_some(), "metallic"),
+        ]
+        .iter()
+        .filter(|(present, _)| *present)
+        .map(|(_, name)| *name)
+        .collect();
+
+        let text = if maps.is_empty() {
+            "Choose a preview map on output nodes".to_string()
+        } else {
+            format!("Maps: {}", maps.join(", "))
+        };
+        ctx.get_widget(self.maps)
+            .set::<String16>("text", String16::from(text));
+    }
+}
+
+widget!(
+    PreviewCanvas<PreviewCanvasState>: MouseHandler {
+        image: ImageData
+    }
+);
+
+impl Template for PreviewCanvas {
+    fn template(self, _id: Entity, _ctx: &mut BuildContext) -> Self {
+        self.name("PreviewCanvas").image(ImageData::default())
+    }
+
+    fn render_object(&self) -> Box<dyn RenderObject> {
+        Box::new(ThumbnailRenderObject)
+    }
+}
+
+#[derive(AsAny, Default)]
+pub struct PreviewCanvasState {}
+
+impl State for PreviewCanvasState {}
@@ -1,3 +1,4 @@
+use crate::shared::Indexable;
 use orbtk::prelude::*;
 
 widget!(MenuProperty<MenuPropertyState> {});
@@ -29,6 +30,26 @@ impl MenuProperty {
             .selected_index(selected_index)
     }
 
+    /// Returns the index of `value` in a combo box with the given items, or -1 if the value has
+    /// no item.
+    pub fn combo_box_index<T: Indexable>(value: &T, items: &[String]) -> i32 {
+        let index = value.index();
+
+        if index < items.len() {
+            index as i32
+        } else {
+            -1
+        }
+    }
+
+    /// A label naming the property below it.
+    pub fn label(text: &str) -> TextBlock {
+        TextBlock::create()
+            .element("text-block")
+            .margin((5., 5., 5., 0.))
+            .text(text)
+    }
+
     pub fn text_box(text: String) -> TextBox {
         TextBox::create()
             .margin((5., 5., 5., 5.))
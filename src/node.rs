@@ -1,4 +1,8 @@
-use crate::shared::*;
+use crate::{
+    processing::{ImageData, THUMBNAIL_SIZE},
+    shared::*,
+    thumbnail::Thumbnail,
+};
 use orbtk::{behaviors::MouseBehavior, prelude::*};
 
 const SELECTED_BRUSH: Brush = Brush::SolidColor(Color::rgb(255, 255, 255));
@@ -18,14 +22,21 @@ widget!(
         node_id: u32,
         slot_count_input: usize,
         slot_count_output: usize,
-        selected: bool
+        selected: bool,
+        thumbnail: ImageData,
+        zoom: f64
     }
 );
 
 impl Template for Node {
     fn template(mut self, id: Entity, ctx: &mut BuildContext) -> Self {
-        let property_stack = Stack::create().build(ctx);
-        self.state_mut().property_stack = property_stack;
+        let thumbnail = Thumbnail::create()
+            .image(("thumbnail", id))
+            .horizontal_alignment("center")
+            .width(THUMBNAIL_SIZE as f64)
+            .height(THUMBNAIL_SIZE as f64)
+            .build(ctx);
+        self.state_mut().thumbnail = thumbnail;
 
         let frame = Container::create()
             .background(Color::rgb(0, 255, 0))
@@ -44,7 +55,7 @@ impl Template for Node {
                             .height(14.)
                             .build(ctx),
                     )
-                    .child(property_stack)
+                    .child(thumbnail)
                     .build(ctx),
             )
             .build(ctx);
@@ -54,7 +65,7 @@ impl Template for Node {
             .widget_type(WidgetType::Node)
             .width(NODE_WIDTH)
             .height(NODE_HEIGHT)
-            .margin(("my_margin", id))
+            .zoom(1.)
             .child(MouseBehavior::create().enabled(id).target(id.0).build(ctx))
             .child(frame)
     }
@@ -65,10 +76,19 @@ pub struct NodeState {
     pub title: String16,
     pub builder: WidgetBuildContext,
     frame: Entity,
-    property_stack: Entity,
+    thumbnail: Entity,
 }
 
 impl State for NodeState {
+    fn update(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
+        let thumbnail_size = THUMBNAIL_SIZE as f64 * *ctx.widget().get::<f64>("zoom");
+
+        let mut thumbnail_widget = ctx.get_widget(self.thumbnail);
+        let constraint = thumbnail_widget.get_mut::<Constraint>("constraint");
+        constraint.set_width(thumbnail_size);
+        constraint.set_height(thumbnail_size);
+    }
+
     fn update_post_layout(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
         if *ctx.widget().get::<bool>("selected") {
             ctx.get_widget(self.frame)
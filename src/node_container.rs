@@ -1,32 +1,77 @@
-use crate::{edge::Edge, menu_property::MenuProperty, node::Node, shared::*, slot::Slot};
+use crate::{
+    blend::{mix_node, set_mix_node, BlendMode, MixNode},
+    channels::{
+        bundled_slots, channel_node, is_bundled, set_channel_node, ChannelNode,
+        OptionChannelNodeKind,
+    },
+    clipboard::{copy_nodes, from_system_clipboard, paste_nodes, to_system_clipboard, top_left},
+    connection::{check_connection, node_slot_type, ConnectionError, SlotType},
+    curve_editor::CurveEditor,
+    edge::Edge,
+    export::{export_outputs, output_settings, set_output_settings, OutputSettings},
+    file_browser::{FileBrowserMode, OptionFileBrowserMode},
+    filter::{
+        filter, height_to_normal, set_filter, set_height_to_normal, Curve, Filter, HeightToNormal,
+        OptionFilter,
+    },
+    generator::{
+        bake, bake_all, bake_nodes, clamp_scale, generator, set_generator, Generator,
+        GeneratorType, OptionGeneratorType,
+    },
+    grid::Grid,
+    history::{Edit, History},
+    layout::{align, auto_layout, distribute, layout_missing, snap},
+    line::distance_to_curve,
+    material_preview::MaterialMap,
+    menu_property::MenuProperty,
+    node::Node,
+    node_registry::node_title,
+    node_graph_spatial::{Location, NodeGraphSpatial, NodeSettings},
+    processing::{ImageData, NodeChannels, Processor},
+    rasterizer::Material,
+    recovery::{self, Autosaver},
+    shared::*,
+    slot::{Slot, SlotHighlight},
+    subgraph::{collapse, enter, leave, link, linked_path, sync_linked},
+    texture_viewer::ViewerSource,
+    view::{rectangle_from_points, rectangles_intersect, union_rectangles, View},
+};
 use kanter_core::{
-    node::{Node as CoreNode, MixType, NodeType, Side},
-    node_graph::{Edge as CoreEdge, NodeGraph, NodeId, SlotId},
+    node::{Node as CoreNode, NodeType, ResizeFilter, ResizePolicy, Side},
+    node_graph::{Edge as CoreEdge, NodeId, SlotId},
+};
+use orbtk::{
+    prelude::*,
+    shell::{Key, MouseButton},
+};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
 };
-use orbtk::{prelude::*, shell::MouseButton};
-use serde::{Deserialize, Serialize};
-use std::fs::File;
 
 const DRAG_THRESHOLD: f64 = 5.;
-
-#[derive(Default, Serialize, Deserialize)]
-struct NodeGraphSpatial {
-    locations: Vec<Location>,
-    node_graph: NodeGraph,
-}
-
-#[derive(Clone, Serialize, Deserialize)]
-struct Location {
-    node_id: NodeId,
-    point: (f64, f64),
-}
+const ZOOM_STEP: f64 = 1.1;
+const DUPLICATE_OFFSET: f64 = 20.;
+/// How close to an edge the mouse has to be to hit it, in screen space.
+const EDGE_HIT_DISTANCE: f64 = 6.;
 
 type List = Vec<String>;
 widget!(NodeContainer<NodeContainerState> {
     action: OptionAction,
     action_main: OptionActionMain,
     add_node: OptionNodeType,
-    menu_property_list: List
+    add_generator: OptionGeneratorType,
+    add_filter: OptionFilter,
+    add_channels: OptionChannelNodeKind,
+    add_node_position: Point,
+    menu_property_list: List,
+    error_message: String16,
+    graph_path: String16,
+    modified: bool,
+    browse_request: OptionFileBrowserMode,
+    viewer_source: ViewerSource,
+    preview_material: Material
 });
 
 impl Template for NodeContainer {
@@ -40,6 +85,35 @@ const DRAG_OFFSET_DEFAULT: Point = Point {
     y: NODE_WIDTH / 2.,
 };
 
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum SelectMode {
+    Replace,
+    Add,
+    Toggle,
+}
+
+/// Commands from the buttons in the property menu of a node.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum MenuCommand {
+    BrowseImage,
+    DisconnectAll,
+    EnterSubgraph,
+    LinkSubgraph,
+}
+
+/// A graph the user has entered a subgraph of.
+struct SubgraphLevel {
+    node_id: NodeId,
+    parent: NodeGraphSpatial,
+    history: History,
+}
+
+struct BoxSelect {
+    start: Point,
+    mode: SelectMode,
+    entity: Entity,
+}
+
 #[derive(Default, AsAny)]
 struct NodeContainerState {
     node_graph_spatial: NodeGraphSpatial,
@@ -49,20 +123,50 @@ struct NodeContainerState {
     dragging: bool,
     dragged_entity: OptionDragDropEntity,
     dropped_on_entity: OptionDragDropEntity,
-    selected_entity: OptionDragDropEntity,
+    selected_entities: Vec<DragDropEntity>,
+    click_to_select: OptionDragDropEntity,
+    drag_group: Vec<(Entity, Point)>,
+    box_select: Option<BoxSelect>,
     menu_property: Entity,
     menu_property_node: Option<Entity>,
     menu_property_list: Vec<Entity>,
+    processor: Option<Processor>,
+    process_queued: bool,
+    view: View,
+    mouse_position_screen: Point,
+    panning: bool,
+    history: History,
+    clip: Option<NodeGraphSpatial>,
+    menu_command: Option<MenuCommand>,
+    subgraph_stack: Vec<SubgraphLevel>,
+    graph_path: Option<PathBuf>,
+    /// Whether the graph has changed since it was loaded or saved.
+    modified: bool,
+    /// Whether the graph has changed since it was last written to the recovery file.
+    autosave_pending: bool,
+    autosaver: Option<Autosaver>,
+    grid: Entity,
+    snap_to_grid: bool,
+    /// The full resolution output of every node from the last processing run.
+    node_channels: Vec<(NodeId, Arc<NodeChannels>)>,
+    /// The node shown in the texture viewer.
+    viewed_node: Option<NodeId>,
+    /// The processing error shown last, so it is not shown again after every edit.
+    process_error: Option<String>,
 }
 
 impl State for NodeContainerState {
     fn init(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
         self.drag_offset = DRAG_OFFSET_DEFAULT;
+        self.processor = Some(Processor::new());
+        self.autosaver = Some(Autosaver::new());
+        self.init_grid(ctx);
         self.init_menu_property(ctx);
     }
 
     fn update(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
         self.sync_properties(ctx);
+        self.handle_menu_command(ctx);
 
         self.handle_action(ctx);
         self.handle_add_node(ctx);
@@ -72,10 +176,28 @@ impl State for NodeContainerState {
         self.reset_mouse_action(ctx);
 
         self.handle_action_main(ctx);
+        self.handle_processing(ctx);
+        self.refresh_viewer(ctx);
+        self.refresh_preview(ctx);
+        self.autosave(ctx);
+
+        if *ctx.widget().get::<bool>("modified") != self.modified {
+            ctx.widget().set::<bool>("modified", self.modified);
+        }
     }
 }
 
 impl NodeContainerState {
+    fn init_grid(&mut self, ctx: &mut Context) {
+        let self_entity = ctx.widget().entity();
+        let bc = &mut ctx.build_context();
+
+        self.grid = Grid::create().build(bc);
+        bc.append_child(self_entity, self.grid);
+
+        self.refresh_grid(ctx);
+    }
+
     fn init_menu_property(&mut self, ctx: &mut Context) {
         let self_entity = ctx.widget().entity();
         let bc = &mut ctx.build_context();
@@ -86,13 +208,11 @@ impl NodeContainerState {
         bc.append_child(self_entity, menu_property);
     }
 
-    fn get_clicked_child(&self, ctx: &mut Context, mouse: Mouse) -> Option<Entity> {
+    /// Returns the topmost clickable child at the given position in graph space.
+    fn get_clicked_child(&self, ctx: &mut Context, position: Point) -> Option<Entity> {
         for child_entity in child_entities(ctx).iter().rev() {
-            if ctx
-                .get_widget(*child_entity)
-                .get::<Rectangle>("bounds")
-                .contains((mouse.x, mouse.y))
-                && Self::is_clickable(ctx, *child_entity)
+            if Self::is_clickable(ctx, *child_entity)
+                && Self::graph_bounds(ctx, *child_entity).contains((position.x, position.y))
             {
                 return Some(*child_entity);
             }
@@ -100,6 +220,49 @@ impl NodeContainerState {
         None
     }
 
+    /// Returns the topmost edge whose curve passes close to the given position in graph space.
+    fn get_edge_at(&self, ctx: &mut Context, position: Point) -> Option<Entity> {
+        let hit_distance = EDGE_HIT_DISTANCE / self.view.zoom;
+
+        Self::children_type(ctx, WidgetType::Edge)
+            .into_iter()
+            .rev()
+            .find(|edge_entity| {
+                let edge_widget = ctx.get_widget(*edge_entity);
+
+                distance_to_curve(
+                    position,
+                    *edge_widget.get::<Point>("output_point"),
+                    *edge_widget.get::<Point>("input_point"),
+                ) <= hit_distance
+            })
+    }
+
+    /// Highlights the edge under the mouse, unless a node or slot is in front of it.
+    fn refresh_hovered_edge(&mut self, ctx: &mut Context) {
+        let hovered_edge = if self.dragged_entity.is_none()
+            && self.get_clicked_child(ctx, self.mouse_position).is_none()
+        {
+            self.get_edge_at(ctx, self.mouse_position)
+        } else {
+            None
+        };
+
+        for edge_entity in Self::children_type(ctx, WidgetType::Edge) {
+            let hovered = Some(edge_entity) == hovered_edge;
+            let mut edge_widget = ctx.get_widget(edge_entity);
+
+            if *edge_widget.get::<bool>("hovered") != hovered {
+                edge_widget.set::<bool>("hovered", hovered);
+            }
+        }
+    }
+
+    /// Returns the type of data going through the given slot.
+    fn slot_type_of(&self, node_id: u32, side: WidgetSide, slot_id: u32) -> SlotType {
+        node_slot_type(&self.node_graph_spatial, NodeId(node_id), side, slot_id)
+    }
+
     fn sync_properties(&mut self, ctx: &mut Context) {
         let menu_property_node = if let Some(menu_property_node) = self.menu_property_node {
             menu_property_node
@@ -114,26 +277,376 @@ impl NodeContainerState {
         let node_type = self.node_type_of_entity(ctx, menu_property_node).clone();
         let node_id = NodeId(*ctx.get_widget(menu_property_node).get::<u32>("node_id"));
 
-        match node_type {
-            NodeType::Mix(mix_type) => {
-                let mix_type_index = *ctx.get_widget(self.menu_property_list[0]).get::<i32>("selected_index");
-                let mix_type_menu = MixType::from_index(mix_type_index as usize).unwrap();
+        if let Some(generator) = generator(&self.node_graph_spatial, node_id).cloned() {
+            self.sync_generator(ctx, menu_property_node, node_id, generator);
+            return;
+        }
+        if let Some(filter) = filter(&self.node_graph_spatial, node_id).cloned() {
+            self.sync_filter(ctx, menu_property_node, node_id, filter);
+            return;
+        }
+        if let Some(mix_node) = mix_node(&self.node_graph_spatial, node_id) {
+            self.sync_mix(ctx, menu_property_node, node_id, mix_node);
+            return;
+        }
+        if let Some(channel_node) = channel_node(&self.node_graph_spatial, node_id).cloned() {
+            self.sync_channels(ctx, node_id, channel_node);
+            return;
+        }
 
-                if mix_type != mix_type_menu {
-                    self.node_graph_spatial.node_graph.set_mix_type(node_id, mix_type_menu)
-                    .expect("Crash when setting node type");
-                }
-            }
+        match node_type {
             NodeType::Image(path) => {
                 let property_widget = ctx.get_widget(self.menu_property_list[0]);
                 let path_menu = property_widget.get::<String16>("text");
 
                 if path_menu.to_string() != *path {
-                    self.node_graph_spatial.node_graph.set_image_node_path(node_id, path_menu.to_string()).unwrap();
+                    let path_menu = path_menu.to_string();
+                    self.set_node_type(node_id, NodeType::Image(path_menu));
+                    self.queue_process();
+                }
+            }
+            NodeType::Value(value) => {
+                let value_menu = ctx
+                    .get_widget(self.menu_property_list[0])
+                    .get::<String16>("text")
+                    .to_string();
+
+                // Half typed numbers are left alone until they parse.
+                if let Ok(value_menu) = value_menu.trim().parse::<f32>() {
+                    if value_menu != value {
+                        self.set_node_type(node_id, NodeType::Value(value_menu));
+                        self.queue_process();
+                    }
                 }
             }
-            NodeType::OutputGray => (),
-            _ => todo!()
+            NodeType::Resize(resize_policy, resize_filter) => {
+                let resize_policy_menu =
+                    Self::changed_selection(ctx, self.menu_property_list[0], &resize_policy);
+                let resize_filter_menu =
+                    Self::changed_selection(ctx, self.menu_property_list[1], &resize_filter);
+
+                if resize_policy_menu.is_some() || resize_filter_menu.is_some() {
+                    self.set_node_type(
+                        node_id,
+                        NodeType::Resize(
+                            resize_policy_menu.unwrap_or(resize_policy),
+                            resize_filter_menu.unwrap_or(resize_filter),
+                        ),
+                    );
+                    self.queue_process();
+                }
+            }
+            NodeType::HeightToNormal => {
+                let height_to_normal = height_to_normal(&self.node_graph_spatial, node_id);
+
+                // Half typed numbers are left alone until they parse.
+                if let Some(strength_menu) =
+                    Self::parsed_text::<f32>(ctx, self.menu_property_list[0])
+                {
+                    if strength_menu != height_to_normal.strength {
+                        self.push_history(Edit::SetProperty(node_id));
+                        set_height_to_normal(
+                            &mut self.node_graph_spatial,
+                            node_id,
+                            HeightToNormal {
+                                strength: strength_menu,
+                            },
+                        );
+                        self.queue_process();
+                    }
+                }
+            }
+            NodeType::OutputGray | NodeType::OutputRgba => {
+                let output_settings = output_settings(&self.node_graph_spatial, node_id);
+
+                let file_name_menu = ctx
+                    .get_widget(self.menu_property_list[0])
+                    .get::<String16>("text")
+                    .to_string();
+                let format_menu = Self::changed_selection(
+                    ctx,
+                    self.menu_property_list[1],
+                    &output_settings.format,
+                );
+                let bit_depth_menu = Self::changed_selection(
+                    ctx,
+                    self.menu_property_list[2],
+                    &output_settings.bit_depth,
+                );
+                let channels_menu = Self::changed_selection(
+                    ctx,
+                    self.menu_property_list[3],
+                    &output_settings.channels,
+                );
+                let preview_map_menu = Self::changed_selection(
+                    ctx,
+                    self.menu_property_list[4],
+                    &output_settings.preview_map,
+                );
+
+                let new_output_settings = OutputSettings {
+                    file_name: file_name_menu,
+                    format: format_menu.unwrap_or(output_settings.format),
+                    bit_depth: bit_depth_menu.unwrap_or(output_settings.bit_depth),
+                    channels: channels_menu.unwrap_or(output_settings.channels),
+                    preview_map: preview_map_menu.unwrap_or(output_settings.preview_map),
+                };
+
+                if new_output_settings != output_settings {
+                    self.push_history(Edit::SetProperty(node_id));
+                    set_output_settings(
+                        &mut self.node_graph_spatial,
+                        node_id,
+                        new_output_settings,
+                    );
+                }
+            }
+            // The remaining node types have no properties.
+            _ => (),
+        }
+
+        self.refresh_node_title(ctx, menu_property_node);
+    }
+
+    /// Applies the generator settings in the property menu, generating a new texture when they
+    /// changed.
+    fn sync_generator(
+        &mut self,
+        ctx: &mut Context,
+        node_entity: Entity,
+        node_id: NodeId,
+        generator: Generator,
+    ) {
+        let generator_type_menu =
+            Self::changed_selection(ctx, self.menu_property_list[0], &generator.generator_type);
+        // Half typed numbers are left alone until they parse.
+        let seed_menu = Self::parsed_text::<u32>(ctx, self.menu_property_list[1]);
+        let scale_menu =
+            Self::parsed_text::<u32>(ctx, self.menu_property_list[2]).map(clamp_scale);
+        let sides_menu = match self.menu_property_list.get(3) {
+            Some(sides_box) => {
+                Self::parsed_text::<u32>(ctx, *sides_box).filter(|sides| *sides >= 3)
+            }
+            None => None,
+        };
+
+        let new_generator = Generator {
+            generator_type: generator_type_menu.unwrap_or(generator.generator_type),
+            seed: seed_menu.unwrap_or(generator.seed),
+            scale: scale_menu.unwrap_or(generator.scale),
+            sides: sides_menu.unwrap_or(generator.sides),
+        };
+        if new_generator == generator {
+            return;
+        }
+
+        self.push_history(Edit::SetProperty(node_id));
+        set_generator(&mut self.node_graph_spatial, node_id, new_generator.clone());
+        if let Err(e) = bake(&mut self.node_graph_spatial, node_id, &new_generator) {
+            Self::show_error(ctx, format!("Could not generate texture: {}", e));
+        }
+        self.queue_process();
+        self.refresh_node_title(ctx, node_entity);
+
+        // Only polygons have a number of sides.
+        if new_generator.generator_type != generator.generator_type {
+            self.open_menu_property(ctx, node_entity);
+        }
+    }
+
+    /// Applies the filter settings in the property menu.
+    fn sync_filter(
+        &mut self,
+        ctx: &mut Context,
+        node_entity: Entity,
+        node_id: NodeId,
+        filter: Filter,
+    ) {
+        let new_filter = match Self::changed_selection(ctx, self.menu_property_list[0], &filter) {
+            Some(new_filter) => new_filter,
+            None => match filter {
+                Filter::Curves(_) => Filter::Curves(
+                    ctx.get_widget(self.menu_property_list[1])
+                        .clone::<Curve>("curve"),
+                ),
+                _ => {
+                    // Half typed numbers are left alone until they parse.
+                    let mut values = Vec::new();
+                    for (i, (_, value)) in filter.parameters().into_iter().enumerate() {
+                        let value_menu = self
+                            .menu_property_list
+                            .get(i + 1)
+                            .and_then(|value_box| Self::parsed_text::<f32>(ctx, *value_box));
+                        values.push(value_menu.unwrap_or(value));
+                    }
+                    filter.with_parameters(&values)
+                }
+            },
+        };
+        if new_filter == filter {
+            return;
+        }
+
+        let filter_changed = new_filter.index() != filter.index();
+        self.push_history(Edit::SetProperty(node_id));
+        set_filter(&mut self.node_graph_spatial, node_id, new_filter);
+        self.queue_process();
+        self.refresh_node_title(ctx, node_entity);
+
+        // Each filter has its own properties.
+        if filter_changed {
+            self.open_menu_property(ctx, node_entity);
+        }
+    }
+
+    /// Applies the blend mode and opacity in the property menu. Switching to or from lerping
+    /// changes the number of inputs, so the node is rebuilt.
+    fn sync_mix(
+        &mut self,
+        ctx: &mut Context,
+        node_entity: Entity,
+        node_id: NodeId,
+        mix_node: MixNode,
+    ) {
+        let blend_mode_menu =
+            Self::changed_selection(ctx, self.menu_property_list[0], &mix_node.blend_mode);
+        // Half typed numbers are left alone until they parse.
+        let opacity_menu = Self::parsed_text::<f32>(ctx, self.menu_property_list[1])
+            .map(|opacity| opacity.max(0.).min(1.));
+
+        let new_mix_node = MixNode {
+            blend_mode: blend_mode_menu.unwrap_or(mix_node.blend_mode),
+            opacity: opacity_menu.unwrap_or(mix_node.opacity),
+        };
+        if new_mix_node == mix_node {
+            return;
+        }
+
+        let before = self.node_graph_spatial.clone();
+        let removed_edges =
+            set_mix_node(&mut self.node_graph_spatial, node_id, new_mix_node.clone());
+        self.push_history_before(Self::node_type_edit(node_id, &removed_edges), before);
+
+        if new_mix_node.blend_mode.input_count() == mix_node.blend_mode.input_count() {
+            self.queue_process();
+            self.refresh_node_title(ctx, node_entity);
+            return;
+        }
+
+        self.reset_workspace(ctx);
+        let node_entity = Self::children_type(ctx, WidgetType::Node)
+            .into_iter()
+            .find(|entity| NodeId(*ctx.get_widget(*entity).get::<u32>("node_id")) == node_id);
+        if let Some(node_entity) = node_entity {
+            self.open_menu_property(ctx, node_entity);
+        }
+    }
+
+    /// Applies the swizzle and fallback values in the property menu of a channel node.
+    fn sync_channels(&mut self, ctx: &mut Context, node_id: NodeId, channel_node: ChannelNode) {
+        // Half typed swizzles and numbers are left alone until they parse.
+        let swizzle_menu = ChannelNode::parse_swizzle(
+            &ctx.get_widget(self.menu_property_list[0])
+                .get::<String16>("text")
+                .to_string(),
+        );
+        let mut fallbacks = channel_node.fallbacks;
+        for (i, fallback) in fallbacks.iter_mut().enumerate() {
+            if let Some(fallback_menu) = self
+                .menu_property_list
+                .get(i + 1)
+                .and_then(|fallback_box| Self::parsed_text::<f32>(ctx, *fallback_box))
+            {
+                *fallback = fallback_menu;
+            }
+        }
+
+        let new_channel_node = ChannelNode {
+            swizzle: swizzle_menu.unwrap_or(channel_node.swizzle),
+            fallbacks,
+            ..channel_node.clone()
+        };
+        if new_channel_node != channel_node {
+            self.push_history(Edit::SetProperty(node_id));
+            set_channel_node(&mut self.node_graph_spatial, node_id, new_channel_node);
+            self.queue_process();
+        }
+    }
+
+    /// Parses the text in a `TextBox`, ignoring surrounding whitespace.
+    fn parsed_text<T: FromStr>(ctx: &mut Context, text_box: Entity) -> Option<T> {
+        ctx.get_widget(text_box)
+            .get::<String16>("text")
+            .to_string()
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// Returns the title shown on a node. Generator, filter, Mix and channel nodes are named after
+    /// what they do rather than the node they are in the `NodeGraph`.
+    fn title_of(&self, node_id: NodeId) -> String {
+        if let Some(channel_node) = channel_node(&self.node_graph_spatial, node_id) {
+            return channel_node.kind.name().to_string();
+        }
+        if let Some(filter) = filter(&self.node_graph_spatial, node_id) {
+            return filter.name().to_string();
+        }
+        if let Some(mix_node) = mix_node(&self.node_graph_spatial, node_id) {
+            return format!("Mix({:?})", mix_node.blend_mode);
+        }
+
+        match generator(&self.node_graph_spatial, node_id) {
+            Some(generator) => generator.generator_type.name().to_string(),
+            None => self
+                .node_graph_spatial
+                .node_graph
+                .node_with_id(node_id)
+                .map_or(String::new(), |node| node_title(&node.node_type)),
+        }
+    }
+
+    /// Updates the title of a node widget to show the current `NodeType` in the graph.
+    fn refresh_node_title(&self, ctx: &mut Context, node_entity: Entity) {
+        let node_id = NodeId(*ctx.get_widget(node_entity).get::<u32>("node_id"));
+        let title = self.title_of(node_id);
+
+        let mut node_widget = ctx.get_widget(node_entity);
+        if node_widget.get::<String16>("title").to_string() != title {
+            node_widget.set::<String16>("title", String16::from(title));
+        }
+    }
+
+    /// Returns the value selected in the given `ComboBox` if it differs from `current`.
+    fn changed_selection<T: Indexable>(
+        ctx: &mut Context,
+        combo_box: Entity,
+        current: &T,
+    ) -> Option<T> {
+        let selected_index = *ctx.get_widget(combo_box).get::<i32>("selected_index");
+
+        if selected_index < 0 || selected_index as usize == current.index() {
+            None
+        } else {
+            T::from_index(selected_index as usize)
+        }
+    }
+
+    /// Changes the `NodeType` of a node and records it in the history.
+    fn set_node_type(&mut self, node_id: NodeId, node_type: NodeType) {
+        let before = self.node_graph_spatial.clone();
+        let removed_edges = self.node_graph_spatial.set_node_type(node_id, node_type);
+        self.push_history_before(Self::node_type_edit(node_id, &removed_edges), before);
+    }
+
+    /// Returns the edit a change of `NodeType` is recorded as. A change disconnecting inputs the
+    /// node no longer has gets an entry of its own, instead of being folded into the previous
+    /// property edits of the node.
+    fn node_type_edit(node_id: NodeId, removed_edges: &[CoreEdge]) -> Edit {
+        if removed_edges.is_empty() {
+            Edit::SetProperty(node_id)
+        } else {
+            Edit::Disconnect
         }
     }
 
@@ -141,30 +654,42 @@ impl NodeContainerState {
         if let Some(action) = *ctx.widget().get::<OptionAction>("action") {
             match action {
                 Action::Press(mouse) => {
-                    let option_clicked_entity = self.get_clicked_child(ctx, mouse);
+                    let mouse_position_screen = Point::new(mouse.x, mouse.y);
+                    let mouse_position = self.view.to_graph(mouse_position_screen);
+                    let option_clicked_entity = self.get_clicked_child(ctx, mouse_position);
 
                     match mouse.button {
+                        MouseButton::Left if is_key_down(ctx, Key::Space) => {
+                            self.panning = true;
+                        }
+                        MouseButton::Middle => {
+                            self.panning = true;
+                        }
                         MouseButton::Left => {
                             if let Some(clicked_entity) = option_clicked_entity {
+                                let widget_type = *ctx
+                                    .get_widget(clicked_entity)
+                                    .get::<WidgetType>("widget_type");
                                 self.dragged_entity = Some(DragDropEntity {
-                                    widget_type: *ctx
-                                        .get_widget(clicked_entity)
-                                        .get::<WidgetType>("widget_type"),
+                                    widget_type,
                                     entity: clicked_entity,
                                 });
 
-                                let dragged_entity_pos = {
-                                    let widget = ctx.get_widget(clicked_entity);
-                                    let margin = widget.get::<Thickness>("margin");
+                                let dragged_entity_pos = Self::graph_position(ctx, clicked_entity);
 
-                                    Point {
-                                        x: margin.left,
-                                        y: margin.top,
-                                    }
-                                };
+                                self.drag_offset = mouse_position - dragged_entity_pos;
 
-                                self.drag_offset =
-                                    Point::new(mouse.x, mouse.y) - dragged_entity_pos;
+                                if widget_type == WidgetType::Node {
+                                    self.press_node(ctx, clicked_entity);
+                                }
+                            } else if let Some(edge_entity) =
+                                self.get_edge_at(ctx, mouse_position)
+                            {
+                                let edge = DragDropEntity::new(WidgetType::Edge, edge_entity);
+                                let mode = Self::select_mode(ctx);
+                                self.select(ctx, vec![edge], mode);
+                            } else {
+                                self.start_box_select(ctx, mouse_position);
                             }
                         }
                         MouseButton::Right => {
@@ -180,19 +705,24 @@ impl NodeContainerState {
                         }
                         _ => {}
                     };
-                    self.mouse_position = Point {
-                        x: mouse.x,
-                        y: mouse.y,
-                    };
+                    self.mouse_position = mouse_position;
+                    self.mouse_position_screen = mouse_position_screen;
                 }
                 Action::Release(mouse) => {
+                    let mouse_position_screen = Point::new(mouse.x, mouse.y);
+                    let mouse_position = self.view.to_graph(mouse_position_screen);
                     let widget_type = WidgetType::Slot;
+                    self.panning = false;
+
+                    if let Some(node) = self.click_to_select.take() {
+                        if !self.dragging {
+                            self.select(ctx, vec![node], SelectMode::Replace);
+                        }
+                    }
 
                     for slot_entity in Self::children_type(ctx, widget_type) {
-                        if ctx
-                            .get_widget(slot_entity)
-                            .get::<Rectangle>("bounds")
-                            .contains((mouse.x, mouse.y))
+                        if Self::graph_bounds(ctx, slot_entity)
+                            .contains((mouse_position.x, mouse_position.y))
                         {
                             self.dropped_on_entity = Some(DragDropEntity {
                                 widget_type,
@@ -201,18 +731,66 @@ impl NodeContainerState {
                         }
                     }
 
-                    self.mouse_position = Point {
-                        x: mouse.x,
-                        y: mouse.y,
+                    self.mouse_position = mouse_position;
+                    self.mouse_position_screen = mouse_position_screen;
+                    self.finish_box_select(ctx);
+                }
+                Action::Move(p) => {
+                    if self.panning {
+                        self.view.pan(p - self.mouse_position_screen);
+                        self.apply_view(ctx);
+                    }
+
+                    self.mouse_position = self.view.to_graph(p);
+                    self.mouse_position_screen = p;
+                    self.refresh_box_select(ctx);
+                    self.refresh_hovered_edge(ctx);
+                }
+                Action::Scroll(delta) => {
+                    let factor = if delta.y > 0. {
+                        ZOOM_STEP
+                    } else {
+                        1. / ZOOM_STEP
                     };
+
+                    self.view.zoom_around(self.mouse_position_screen, factor);
+                    self.mouse_position = self.view.to_graph(self.mouse_position_screen);
+                    self.apply_view(ctx);
+                }
+                Action::FrameAll => self.frame_all(ctx),
+                Action::FrameSelected => self.frame_selected(ctx),
+                Action::Undo => self.undo(ctx),
+                Action::Redo => self.redo(ctx),
+                Action::Copy => {
+                    self.copy_selection(ctx);
                 }
-                Action::Move(p) => self.mouse_position = p,
-                Action::Delete => {
-                    if let Some(selected_entity) = self.selected_entity {
-                        self.delete_node(ctx, selected_entity.entity);
-                        self.selected_entity = None;
+                Action::Cut => self.cut_selection(ctx),
+                Action::Paste => self.paste(ctx),
+                Action::Duplicate => self.duplicate_selection(ctx),
+                Action::Delete => self.delete_selection(ctx),
+                Action::CollapseSelection => self.collapse_selection(ctx),
+                Action::EnterSubgraph => {
+                    if let [node_entity] = self.selected_nodes()[..] {
+                        self.enter_subgraph(ctx, node_entity);
                     }
                 }
+                Action::LeaveSubgraph => self.leave_subgraph(ctx),
+                Action::ToggleGrid => {
+                    self.snap_to_grid = !self.snap_to_grid;
+                    self.refresh_grid(ctx);
+                }
+                Action::Align(alignment) => {
+                    let node_ids = self.selected_node_ids(ctx);
+                    self.arrange(ctx, |graph| align(graph, &node_ids, alignment));
+                }
+                Action::Distribute(axis) => {
+                    let node_ids = self.selected_node_ids(ctx);
+                    self.arrange(ctx, |graph| distribute(graph, &node_ids, axis));
+                }
+                Action::AutoLayout => {
+                    self.arrange(ctx, auto_layout);
+                    self.frame_all(ctx);
+                }
             }
         }
     }
@@ -233,36 +811,183 @@ impl NodeContainerState {
         self.menu_property_list.clear();
         ctx.get_widget(self.menu_property).get_mut::<Rectangle>("bounds").set_height(100.);
 
+        let node_id = NodeId(*ctx.get_widget(node_entity).get::<u32>("node_id"));
+        let generator = generator(&self.node_graph_spatial, node_id).cloned();
+        let filter = filter(&self.node_graph_spatial, node_id).cloned();
+        let mix_node = mix_node(&self.node_graph_spatial, node_id);
+        let channel_node = channel_node(&self.node_graph_spatial, node_id).cloned();
         let node_type = self.node_type_of_entity(ctx, node_entity);
 
+        let self_entity = ctx.widget().entity();
         let bc = &mut ctx.build_context();
-        let properties: Vec<Entity> = match *node_type {
-            NodeType::Mix(mix_type) => {
-                let mix_types = vec!["Add".to_string(), "Subtract".to_string(), "Multiply".to_string(), "Divide".to_string()];
+        let properties: Vec<Entity> = match (&generator, &filter, &mix_node, &channel_node) {
+            (Some(generator), _, _, _) => Self::generator_properties(bc, generator),
+            (_, Some(filter), _, _) => Self::filter_properties(bc, filter),
+            (_, _, Some(mix_node), _) => Self::mix_properties(bc, mix_node),
+            (_, _, _, Some(channel_node)) => Self::channel_properties(bc, channel_node),
+            _ => match *node_type {
+                NodeType::Image(ref path) => {
+                    let path = if path.is_empty() {
+                        "data/image_2.png".to_string()
+                    } else {
+                        path.to_owned()
+                    };
 
-                let mix_type_cb = MenuProperty::combo_box(mix_types, mix_type.index() as i32).build(bc);
+                    let path_box = MenuProperty::text_box(path).build(bc);
 
-                vec![mix_type_cb]
-            }
-            NodeType::Image(ref path) => {
-                let path = if path.is_empty() {
-                    "data/image_2.png".to_string()
-                } else {
-                    path.to_owned()
-                };
+                    vec![path_box]
+                }
+                NodeType::Value(value) => {
+                    let value_box = MenuProperty::text_box(value.to_string()).build(bc);
 
-                let path_box = MenuProperty::text_box(path).build(bc);
+                    vec![value_box]
+                }
+                NodeType::Resize(ref resize_policy, ref resize_filter) => {
+                    let resize_policies = vec![
+                        "Default".to_string(),
+                        "Most pixels".to_string(),
+                        "Least pixels".to_string(),
+                        "Largest axes".to_string(),
+                        "Smallest axes".to_string(),
+                    ];
+                    let resize_filters = vec![
+                        "Default".to_string(),
+                        "Nearest".to_string(),
+                        "Triangle".to_string(),
+                        "Catmull-Rom".to_string(),
+                        "Gaussian".to_string(),
+                        "Lanczos3".to_string(),
+                    ];
+
+                    let resize_policy_index =
+                        MenuProperty::combo_box_index(resize_policy, &resize_policies);
+                    let resize_filter_index =
+                        MenuProperty::combo_box_index(resize_filter, &resize_filters);
+
+                    let resize_policy_cb =
+                        MenuProperty::combo_box(resize_policies, resize_policy_index).build(bc);
+                    let resize_filter_cb =
+                        MenuProperty::combo_box(resize_filters, resize_filter_index).build(bc);
+
+                    vec![resize_policy_cb, resize_filter_cb]
+                }
+                NodeType::HeightToNormal => {
+                    let strength = height_to_normal(&self.node_graph_spatial, node_id).strength;
+                    let strength_box = MenuProperty::text_box(strength.to_string()).build(bc);
 
-                vec![path_box]
-            }
-            NodeType::OutputGray => Vec::new(),
-            _ => todo!()
+                    vec![strength_box]
+                }
+                NodeType::OutputGray | NodeType::OutputRgba => {
+                    let output_settings = output_settings(&self.node_graph_spatial, node_id);
+
+                    let formats = vec![
+                        "PNG".to_string(),
+                        "TGA".to_string(),
+                        "EXR".to_string(),
+                        "TIFF".to_string(),
+                    ];
+                    let bit_depths = vec![
+                        "8 bit".to_string(),
+                        "16 bit".to_string(),
+                        "32 bit float".to_string(),
+                    ];
+                    let channel_layouts =
+                        vec!["Gray".to_string(), "RGB".to_string(), "RGBA".to_string()];
+                    let preview_maps = vec![
+                        "Not previewed".to_string(),
+                        "Preview as albedo".to_string(),
+                        "Preview as height".to_string(),
+                        "Preview as normal".to_string(),
+                        "Preview as roughness".to_string(),
+                        "Preview as metallic".to_string(),
+                    ];
+
+                    let format_index = output_settings.format.index() as i32;
+                    let bit_depth_index = output_settings.bit_depth.index() as i32;
+                    let channels_index = output_settings.channels.index() as i32;
+                    let preview_map_index = output_settings.preview_map.index() as i32;
+
+                    let file_name_box = MenuProperty::text_box(output_settings.file_name).build(bc);
+                    let format_cb = MenuProperty::combo_box(formats, format_index).build(bc);
+                    let bit_depth_cb =
+                        MenuProperty::combo_box(bit_depths, bit_depth_index).build(bc);
+                    let channels_cb =
+                        MenuProperty::combo_box(channel_layouts, channels_index).build(bc);
+                    let preview_map_cb =
+                        MenuProperty::combo_box(preview_maps, preview_map_index).build(bc);
+
+                    vec![file_name_box, format_cb, bit_depth_cb, channels_cb, preview_map_cb]
+                }
+                NodeType::Graph(_) => {
+                    let path = linked_path(&self.node_graph_spatial, node_id).unwrap_or_default();
+                    let path_box = MenuProperty::text_box(path).build(bc);
+
+                    vec![path_box]
+                }
+                // The remaining node types have no properties.
+                _ => Vec::new(),
+            },
         };
 
+        let mut buttons = Vec::new();
+        if let (NodeType::Image(_), None) = (&*node_type, &generator) {
+            buttons.push(Self::menu_button(
+                bc,
+                self_entity,
+                "Browse...",
+                MenuCommand::BrowseImage,
+            ));
+        }
+        if let (NodeType::Graph(_), None, None, None) =
+            (&*node_type, &filter, &mix_node, &channel_node)
+        {
+            buttons.push(Self::menu_button(
+                bc,
+                self_entity,
+                "Link file",
+                MenuCommand::LinkSubgraph,
+            ));
+            buttons.push(Self::menu_button(
+                bc,
+                self_entity,
+                "Enter subgraph",
+                MenuCommand::EnterSubgraph,
+            ));
+        }
+        buttons.push(Self::menu_button(
+            bc,
+            self_entity,
+            "Disconnect all",
+            MenuCommand::DisconnectAll,
+        ));
+
+        let labels: Vec<&str> = match (&generator, &filter, &mix_node, &channel_node) {
+            (Some(_), _, _, _) => vec!["Pattern", "Seed", "Scale", "Sides"],
+            (_, Some(filter), _, _) => std::iter::once("Filter")
+                .chain(filter.parameters().into_iter().map(|(name, _)| name))
+                .collect(),
+            (_, _, Some(_), _) => vec!["Blend mode", "Opacity"],
+            (_, _, _, Some(_)) => vec![
+                "Swizzle",
+                "Red fallback",
+                "Green fallback",
+                "Blue fallback",
+                "Alpha fallback",
+            ],
+            _ if matches!(node_type, NodeType::HeightToNormal) => vec!["Strength"],
+            _ => Vec::new(),
+        };
         let property_stack = Stack::create().build(bc);
-        for property in &properties {
+        for (i, property) in properties.iter().enumerate() {
+            if let Some(label) = labels.get(i) {
+                let label = MenuProperty::label(label).build(bc);
+                bc.append_child(property_stack, label);
+            }
             bc.append_child(property_stack, *property);
         }
+        for button in &buttons {
+            bc.append_child(property_stack, *button);
+        }
 
         self.menu_property_list = properties;
 
@@ -277,6 +1002,77 @@ impl NodeContainerState {
 
     }
 
+    /// Builds the type, seed and scale properties of a generator node, and the number of sides
+    /// for polygons.
+    fn generator_properties(bc: &mut BuildContext, generator: &Generator) -> Vec<Entity> {
+        let generator_types = GeneratorType::ALL
+            .iter()
+            .map(|generator_type| generator_type.name().to_string())
+            .collect();
+
+        let generator_type_cb =
+            MenuProperty::combo_box(generator_types, generator.generator_type.index() as i32)
+                .build(bc);
+        let seed_box = MenuProperty::text_box(generator.seed.to_string()).build(bc);
+        let scale_box = MenuProperty::text_box(generator.scale.to_string()).build(bc);
+
+        let mut properties = vec![generator_type_cb, seed_box, scale_box];
+        if generator.generator_type == GeneratorType::Polygon {
+            properties.push(MenuProperty::text_box(generator.sides.to_string()).build(bc));
+        }
+
+        properties
+    }
+
+    /// Builds the filter type property of a filter node, followed by a text box for each of its
+    /// numbers or a curve editor for curves.
+    fn filter_properties(bc: &mut BuildContext, filter: &Filter) -> Vec<Entity> {
+        let filters = Filter::defaults()
+            .iter()
+            .map(|filter| filter.name().to_string())
+            .collect();
+
+        let mut properties =
+            vec![MenuProperty::combo_box(filters, filter.index() as i32).build(bc)];
+        match filter {
+            Filter::Curves(curve) => {
+                properties.push(CurveEditor::create().curve(curve.clone()).build(bc));
+            }
+            _ => {
+                for (_, value) in filter.parameters() {
+                    properties.push(MenuProperty::text_box(value.to_string()).build(bc));
+                }
+            }
+        }
+
+        properties
+    }
+
+    /// Builds the blend mode and opacity properties of a Mix node.
+    fn mix_properties(bc: &mut BuildContext, mix_node: &MixNode) -> Vec<Entity> {
+        let blend_modes = BlendMode::ALL
+            .iter()
+            .map(|blend_mode| blend_mode.name().to_string())
+            .collect();
+
+        let blend_mode_cb =
+            MenuProperty::combo_box(blend_modes, mix_node.blend_mode.index() as i32).build(bc);
+        let opacity_box = MenuProperty::text_box(mix_node.opacity.to_string()).build(bc);
+
+        vec![blend_mode_cb, opacity_box]
+    }
+
+    /// Builds the swizzle property of a channel node, followed by a text box for the fallback
+    /// value of each input.
+    fn channel_properties(bc: &mut BuildContext, channel_node: &ChannelNode) -> Vec<Entity> {
+        let mut properties = vec![MenuProperty::text_box(channel_node.swizzle_text()).build(bc)];
+        for fallback in &channel_node.fallbacks {
+            properties.push(MenuProperty::text_box(fallback.to_string()).build(bc));
+        }
+
+        properties
+    }
+
     fn handle_dragged_entity(&mut self, ctx: &mut Context) {
         let dragged_entity = match self.dragged_entity {
             Some(drag_drop_entity) => drag_drop_entity,
@@ -284,8 +1080,7 @@ impl NodeContainerState {
         };
 
         let drag_offset_world = {
-            let dragged_widget = ctx.get_widget(dragged_entity.entity);
-            let widget_pos = Self::thickness_to_point(*dragged_widget.get::<Thickness>("margin"));
+            let widget_pos = Self::graph_position(ctx, dragged_entity.entity);
 
             widget_pos + self.drag_offset
         };
@@ -309,6 +1104,7 @@ impl NodeContainerState {
             }
             WidgetType::Edge => {
                 self.refresh_dragged_edges(ctx);
+                self.refresh_slot_highlights(ctx);
             }
         };
     }
@@ -323,6 +1119,7 @@ impl NodeContainerState {
             return;
         }
         self.reset_dragging(ctx);
+        self.clear_slot_highlights(ctx);
 
         let dropped_on_entity = match self.dropped_on_entity {
             Some(drag_drop_entity) => drag_drop_entity,
@@ -341,6 +1138,19 @@ impl NodeContainerState {
                 let dropped_on_side = *dropped_on_widget.get::<WidgetSide>("side");
                 let dropped_on_slot = *dropped_on_widget.get::<u32>("slot_id");
 
+                // Only edges that were actually dragged have a loose end to check.
+                let dragging_edges = self
+                    .dragged_entity
+                    .map_or(false, |entity| entity.widget_type == WidgetType::Edge);
+                if dragging_edges {
+                    if let Err(e) = self.check_drop(ctx, dropped_on_entity.entity) {
+                        self.cancel_dragged_edges(ctx);
+                        Self::show_error(ctx, format!("Could not connect: {}", e));
+                        self.dropped_on_entity = None;
+                        return;
+                    }
+                }
+
                 let goal_position = {
                     let node_margin = *ctx
                         .child(&*dropped_on_node_id.to_string())
@@ -352,6 +1162,10 @@ impl NodeContainerState {
                     Self::position_edge(dropped_on_side, dropped_on_slot, node_pos)
                 };
 
+                // Only recorded once an edge has connected, failed connections change nothing.
+                let before = self.node_graph_spatial.clone();
+                let mut connected = false;
+
                 for edge_entity in self.get_dragged_edges(ctx) {
                     let mut edge_widget = ctx.get_widget(edge_entity);
 
@@ -379,16 +1193,41 @@ impl NodeContainerState {
                     };
 
                     ctx.push_event(ChangedEvent(edge_entity));
-                    let _ = self.node_graph_spatial.node_graph.connect_arbitrary(
+                    if let Err(e) = self.node_graph_spatial.node_graph.connect_arbitrary(
                         NodeId(dropped_on_node_id),
                         dropped_on_side.into(),
                         SlotId(dropped_on_slot),
                         NodeId(other_node_id),
                         other_side,
                         SlotId(other_slot_id),
-                    );
+                    ) {
+                        Self::show_error(ctx, format!("Could not connect: {:?}", e));
+                        continue;
+                    }
+                    connected = true;
+
+                    let dropped_on = (NodeId(dropped_on_node_id), SlotId(dropped_on_slot));
+                    let other = (NodeId(other_node_id), SlotId(other_slot_id));
+                    let (output, input) = match dropped_on_side {
+                        WidgetSide::Input => (other, dropped_on),
+                        WidgetSide::Output => (dropped_on, other),
+                    };
+                    for slot in bundled_slots(&self.node_graph_spatial, output, input) {
+                        let _ = self.node_graph_spatial.node_graph.connect_arbitrary(
+                            output.0,
+                            Side::Output,
+                            slot,
+                            input.0,
+                            Side::Input,
+                            slot,
+                        );
+                    }
+                }
+                if connected {
+                    self.push_history_before(Edit::Connect, before);
                 }
                 self.update_slot_edges_from_graph(ctx, dropped_on_entity.entity);
+                self.queue_process();
             }
             WidgetType::Node => {
                 panic!("Somehow dropped something on a node, should not be possible")
@@ -401,6 +1240,111 @@ impl NodeContainerState {
         self.dropped_on_entity = None;
     }
 
+    /// Returns the fixed end of a dragged edge, the end that is not following the mouse.
+    fn fixed_edge_end(&self, ctx: &mut Context, edge_entity: Entity) -> (NodeId, SlotId) {
+        let edge_widget = ctx.get_widget(edge_entity);
+
+        match self.dragged_edges.1 {
+            WidgetSide::Input => (
+                NodeId(*edge_widget.get::<u32>("output_node")),
+                SlotId(*edge_widget.get::<u32>("output_slot")),
+            ),
+            WidgetSide::Output => (
+                NodeId(*edge_widget.get::<u32>("input_node")),
+                SlotId(*edge_widget.get::<u32>("input_slot")),
+            ),
+        }
+    }
+
+    /// Checks whether the dragged edges can be connected to the given slot.
+    fn check_drop(&self, ctx: &mut Context, slot_entity: Entity) -> Result<(), ConnectionError> {
+        let (node_id, slot_id, side) = {
+            let slot_widget = ctx.get_widget(slot_entity);
+            (
+                NodeId(*slot_widget.get::<u32>("node_id")),
+                SlotId(*slot_widget.get::<u32>("slot_id")),
+                *slot_widget.get::<WidgetSide>("side"),
+            )
+        };
+
+        if side != self.dragged_edges.1 {
+            return Err(ConnectionError::SameSide);
+        }
+
+        for edge_entity in self.dragged_edges.0.clone() {
+            let fixed_end = self.fixed_edge_end(ctx, edge_entity);
+
+            let (output, input) = match side {
+                WidgetSide::Input => (fixed_end, (node_id, slot_id)),
+                WidgetSide::Output => ((node_id, slot_id), fixed_end),
+            };
+
+            check_connection(&self.node_graph_spatial, output, input)?;
+        }
+
+        Ok(())
+    }
+
+    /// Shows which slots the dragged edges can be dropped on.
+    fn refresh_slot_highlights(&mut self, ctx: &mut Context) {
+        for slot_entity in Self::children_type(ctx, WidgetType::Slot) {
+            let hovered = Self::graph_bounds(ctx, slot_entity)
+                .contains((self.mouse_position.x, self.mouse_position.y));
+
+            let highlight = match (self.check_drop(ctx, slot_entity).is_ok(), hovered) {
+                (true, true) => SlotHighlight::Hovered,
+                (true, false) => SlotHighlight::Compatible,
+                (false, true) => SlotHighlight::Rejected,
+                (false, false) => SlotHighlight::None,
+            };
+
+            ctx.get_widget(slot_entity)
+                .set::<SlotHighlight>("highlight", highlight);
+        }
+    }
+
+    fn clear_slot_highlights(&mut self, ctx: &mut Context) {
+        for slot_entity in Self::children_type(ctx, WidgetType::Slot) {
+            ctx.get_widget(slot_entity)
+                .set::<SlotHighlight>("highlight", SlotHighlight::None);
+        }
+    }
+
+    /// Puts the dragged edges back the way they are in the graph, used when a drop is refused.
+    fn cancel_dragged_edges(&mut self, ctx: &mut Context) {
+        for edge_entity in self.dragged_edges.0.clone() {
+            let (output_node, input_node, output_slot, input_slot) = {
+                let edge_widget = ctx.get_widget(edge_entity);
+                (
+                    NodeId(*edge_widget.get::<u32>("output_node")),
+                    NodeId(*edge_widget.get::<u32>("input_node")),
+                    SlotId(*edge_widget.get::<u32>("output_slot")),
+                    SlotId(*edge_widget.get::<u32>("input_slot")),
+                )
+            };
+            ctx.remove_child(edge_entity);
+
+            let graph_edge = self
+                .node_graph_spatial
+                .node_graph
+                .edges
+                .iter()
+                .find(|edge| {
+                    edge.output_id == output_node
+                        && edge.input_id == input_node
+                        && edge.output_slot == output_slot
+                        && edge.input_slot == input_slot
+                })
+                .copied();
+
+            if let Some(graph_edge) = graph_edge {
+                self.create_edge(ctx, &graph_edge);
+            }
+        }
+
+        self.dragged_edges.0 = Vec::new();
+    }
+
     fn reset_dragging(&mut self, ctx: &mut Context) {
         self.dragging = false;
         if let Some(dragged_entity) = self.dragged_entity {
@@ -414,50 +1358,471 @@ impl NodeContainerState {
         Point::new(thickness.left, thickness.top)
     }
 
-    fn select_entity(&mut self, ctx: &mut Context, option_drag_drop_entity: OptionDragDropEntity) {
-        if let Some(drag_drop_entity) = self.selected_entity {
-            ctx.get_widget(drag_drop_entity.entity)
-                .set::<bool>("selected", false);
+    fn select_mode(ctx: &mut Context) -> SelectMode {
+        if is_shift_down(ctx) {
+            SelectMode::Add
+        } else if is_ctrl_down(ctx) {
+            SelectMode::Toggle
+        } else {
+            SelectMode::Replace
         }
+    }
+
+    /// Selects the given entities, only nodes and edges can be selected.
+    fn select(&mut self, ctx: &mut Context, entities: Vec<DragDropEntity>, mode: SelectMode) {
+        let mut selected = match mode {
+            SelectMode::Replace => Vec::new(),
+            SelectMode::Add | SelectMode::Toggle => self.selected_entities.clone(),
+        };
 
-        self.selected_entity = if let Some(drag_drop_entity) = option_drag_drop_entity {
-            match drag_drop_entity.widget_type {
-                WidgetType::Node => {
-                    ctx.get_widget(drag_drop_entity.entity)
-                        .set::<bool>("selected", true);
-                    option_drag_drop_entity
+        for drag_drop_entity in entities {
+            if drag_drop_entity.widget_type == WidgetType::Slot {
+                continue;
+            }
+
+            match selected
+                .iter()
+                .position(|selected_entity| selected_entity.entity == drag_drop_entity.entity)
+            {
+                Some(index) if mode == SelectMode::Toggle => {
+                    selected.remove(index);
                 }
-                _ => None,
+                Some(_) => {}
+                None => selected.push(drag_drop_entity),
             }
+        }
+
+        self.set_selection(ctx, selected);
+    }
+
+    fn set_selection(&mut self, ctx: &mut Context, selected: Vec<DragDropEntity>) {
+        // Edges are removed and recreated when they are dragged, so previously selected entities
+        // may be gone.
+        for drag_drop_entity in &self.selected_entities {
+            if Self::entity_type(ctx, drag_drop_entity.entity, drag_drop_entity.widget_type) {
+                ctx.get_widget(drag_drop_entity.entity)
+                    .set::<bool>("selected", false);
+            }
+        }
+
+        for drag_drop_entity in &selected {
+            ctx.get_widget(drag_drop_entity.entity)
+                .set::<bool>("selected", true);
+        }
+
+        if let Some(node_entity) = selected
+            .iter()
+            .rev()
+            .find(|drag_drop_entity| drag_drop_entity.widget_type == WidgetType::Node)
+        {
+            self.viewed_node = Some(NodeId(
+                *ctx.get_widget(node_entity.entity).get::<u32>("node_id"),
+            ));
+        }
+
+        self.selected_entities = selected;
+    }
+
+    fn is_selected(&self, entity: Entity) -> bool {
+        self.selected_entities
+            .iter()
+            .any(|drag_drop_entity| drag_drop_entity.entity == entity)
+    }
+
+    fn selected_nodes(&self) -> Vec<Entity> {
+        self.selected_entities
+            .iter()
+            .filter(|drag_drop_entity| drag_drop_entity.widget_type == WidgetType::Node)
+            .map(|drag_drop_entity| drag_drop_entity.entity)
+            .collect()
+    }
+
+    fn selected_edges(&self, ctx: &mut Context) -> Vec<Entity> {
+        self.selected_entities
+            .iter()
+            .filter(|drag_drop_entity| {
+                drag_drop_entity.widget_type == WidgetType::Edge
+                    && Self::entity_type(ctx, drag_drop_entity.entity, WidgetType::Edge)
+            })
+            .map(|drag_drop_entity| drag_drop_entity.entity)
+            .collect()
+    }
+
+    /// Updates the selection when a node is pressed, and prepares the other selected nodes for
+    /// being dragged along with it.
+    fn press_node(&mut self, ctx: &mut Context, node_entity: Entity) {
+        let node = DragDropEntity::new(WidgetType::Node, node_entity);
+        let mode = Self::select_mode(ctx);
+
+        if mode == SelectMode::Replace && self.is_selected(node_entity) {
+            // Keep the selection so it can be dragged, it's narrowed down to this node on
+            // release if it turns out to be a click.
+            self.click_to_select = Some(node);
         } else {
-            None
+            self.select(ctx, vec![node], mode);
+        }
+
+        self.drag_group.clear();
+        if !self.is_selected(node_entity) {
+            return;
+        }
+
+        let node_position = Self::graph_position(ctx, node_entity);
+        for selected_node in self.selected_nodes() {
+            if selected_node != node_entity {
+                let offset = Self::graph_position(ctx, selected_node) - node_position;
+                self.drag_group.push((selected_node, offset));
+            }
+        }
+    }
+
+    fn start_box_select(&mut self, ctx: &mut Context, position: Point) {
+        let mode = Self::select_mode(ctx);
+        if mode == SelectMode::Replace {
+            self.set_selection(ctx, Vec::new());
+        }
+
+        let self_entity = ctx.widget().entity();
+        let bc = &mut ctx.build_context();
+
+        let selection_box = Container::create()
+            .background(Color::rgba(255, 255, 255, 40))
+            .border_width(1.)
+            .border_brush(Brush::SolidColor(Color::rgb(255, 255, 255)))
+            .build(bc);
+        bc.append_child(self_entity, selection_box);
+
+        self.box_select = Some(BoxSelect {
+            start: position,
+            mode,
+            entity: selection_box,
+        });
+        self.refresh_box_select(ctx);
+    }
+
+    /// Stretches the selection box between where it was started and the mouse.
+    fn refresh_box_select(&mut self, ctx: &mut Context) {
+        let box_select = match &self.box_select {
+            Some(box_select) => box_select,
+            None => return,
+        };
+
+        let graph_rect = rectangle_from_points(box_select.start, self.mouse_position);
+        let screen_position = self
+            .view
+            .to_screen(Point::new(graph_rect.x(), graph_rect.y()));
+
+        let mut selection_box_widget = ctx.get_widget(box_select.entity);
+        selection_box_widget.set::<Thickness>(
+            "margin",
+            Thickness {
+                left: screen_position.x,
+                top: screen_position.y,
+                right: 0.,
+                bottom: 0.,
+            },
+        );
+
+        let constraint = selection_box_widget.get_mut::<Constraint>("constraint");
+        constraint.set_width(graph_rect.width() * self.view.zoom);
+        constraint.set_height(graph_rect.height() * self.view.zoom);
+    }
+
+    /// Selects all nodes touching the selection box and removes the box.
+    fn finish_box_select(&mut self, ctx: &mut Context) {
+        let box_select = match self.box_select.take() {
+            Some(box_select) => box_select,
+            None => return,
+        };
+        ctx.remove_child(box_select.entity);
+
+        let graph_rect = rectangle_from_points(box_select.start, self.mouse_position);
+        let nodes: Vec<DragDropEntity> = Self::children_type(ctx, WidgetType::Node)
+            .into_iter()
+            .filter(|node_entity| {
+                rectangles_intersect(&graph_rect, &Self::graph_bounds(ctx, *node_entity))
+            })
+            .map(|node_entity| DragDropEntity::new(WidgetType::Node, node_entity))
+            .collect();
+
+        let mode = match box_select.mode {
+            // The selection was already cleared when the box was started.
+            SelectMode::Replace => SelectMode::Add,
+            mode => mode,
+        };
+        self.select(ctx, nodes, mode);
+    }
+
+    fn selected_node_ids(&self, ctx: &mut Context) -> Vec<NodeId> {
+        self.selected_nodes()
+            .into_iter()
+            .map(|node_entity| NodeId(*ctx.get_widget(node_entity).get::<u32>("node_id")))
+            .collect()
+    }
+
+    /// Copies the selected nodes to the clipboard, returns false if nothing was selected.
+    fn copy_selection(&mut self, ctx: &mut Context) -> bool {
+        let node_ids = self.selected_node_ids(ctx);
+        if node_ids.is_empty() {
+            return false;
+        }
+
+        let clip = copy_nodes(&self.node_graph_spatial, &node_ids);
+        // The system clipboard is not available everywhere, the copy is kept around so pasting
+        // within this instance still works.
+        to_system_clipboard(&clip);
+        self.clip = Some(clip);
+
+        true
+    }
+
+    fn cut_selection(&mut self, ctx: &mut Context) {
+        if self.copy_selection(ctx) {
+            self.delete_selection(ctx);
+        }
+    }
+
+    fn paste(&mut self, ctx: &mut Context) {
+        let clip = match from_system_clipboard().or_else(|| self.clip.clone()) {
+            Some(clip) => clip,
+            None => return,
+        };
+
+        let position = (self.mouse_position.x, self.mouse_position.y);
+        self.paste_clip(ctx, &clip, position);
+    }
+
+    fn duplicate_selection(&mut self, ctx: &mut Context) {
+        let node_ids = self.selected_node_ids(ctx);
+        if node_ids.is_empty() {
+            return;
+        }
+
+        let clip = copy_nodes(&self.node_graph_spatial, &node_ids);
+        let top_left = top_left(&clip);
+        let position = (top_left.0 + DUPLICATE_OFFSET, top_left.1 + DUPLICATE_OFFSET);
+
+        self.paste_clip(ctx, &clip, position);
+    }
+
+    /// Adds the nodes in `clip` to the graph with their top left corner at `position`, and
+    /// selects them.
+    fn paste_clip(&mut self, ctx: &mut Context, clip: &NodeGraphSpatial, position: (f64, f64)) {
+        if clip.node_graph.node_ids().is_empty() {
+            return;
+        }
+
+        self.push_history(Edit::Paste);
+        let node_ids = paste_nodes(&mut self.node_graph_spatial, clip, position);
+
+        // A clip from another instance does not refer to any generated textures.
+        if let Some(e) = bake_nodes(&mut self.node_graph_spatial, &node_ids).first() {
+            Self::show_error(ctx, format!("Could not generate texture: {}", e));
+        }
+
+        for node_id in &node_ids {
+            self.populate_node(ctx, *node_id);
+        }
+
+        // Pasted nodes can only be connected to each other, so every edge going into one of
+        // them is new.
+        let edges: Vec<CoreEdge> = self
+            .node_graph_spatial
+            .node_graph
+            .edges
+            .iter()
+            .filter(|edge| node_ids.contains(&edge.input_id))
+            .copied()
+            .collect();
+        for edge in edges {
+            self.create_edge(ctx, &edge);
+        }
+
+        let nodes: Vec<DragDropEntity> = node_ids
+            .iter()
+            .map(|node_id| {
+                let node_entity = ctx.child(&*node_id.0.to_string()).entity();
+                DragDropEntity::new(WidgetType::Node, node_entity)
+            })
+            .collect();
+        self.select(ctx, nodes, SelectMode::Replace);
+
+        self.queue_process();
+    }
+
+    fn delete_selection(&mut self, ctx: &mut Context) {
+        let node_entities = self.selected_nodes();
+        let edge_entities = self.selected_edges(ctx);
+        if node_entities.is_empty() && edge_entities.is_empty() {
+            return;
+        }
+
+        if let Some(menu_property_node) = self.menu_property_node {
+            if node_entities.contains(&menu_property_node) {
+                self.close_menu_property(ctx);
+            }
+        }
+
+        if node_entities.is_empty() {
+            self.push_history(Edit::Disconnect);
+        } else {
+            self.push_history(Edit::DeleteNode);
+        }
+
+        for edge_entity in edge_entities {
+            self.delete_edge(ctx, edge_entity);
+        }
+        for node_entity in node_entities {
+            self.delete_node(ctx, node_entity);
+        }
+        self.selected_entities.clear();
+    }
+
+    /// Removes an edge from the graph and the GUI.
+    fn delete_edge(&mut self, ctx: &mut Context, edge_entity: Entity) {
+        let (output_node, input_node, output_slot, input_slot) = {
+            let edge_widget = ctx.get_widget(edge_entity);
+            (
+                NodeId(*edge_widget.get::<u32>("output_node")),
+                NodeId(*edge_widget.get::<u32>("input_node")),
+                SlotId(*edge_widget.get::<u32>("output_slot")),
+                SlotId(*edge_widget.get::<u32>("input_slot")),
+            )
+        };
+
+        self.remove_edge((output_node, output_slot), (input_node, input_slot));
+        ctx.remove_child(edge_entity);
+
+        self.queue_process();
+    }
+
+    /// Removes an edge from the graph, along with the edges of the other channels if it goes
+    /// into or out of an RGBA slot of a channel node.
+    fn remove_edge(&mut self, output: (NodeId, SlotId), input: (NodeId, SlotId)) {
+        let slots = bundled_slots(&self.node_graph_spatial, output, input);
+        let node_graph = &mut self.node_graph_spatial.node_graph;
+
+        node_graph.remove_edge(output.0, input.0, output.1, input.1);
+        for slot in slots {
+            node_graph.remove_edge(output.0, input.0, slot, slot);
+        }
+    }
+
+    fn menu_button(
+        bc: &mut BuildContext,
+        self_entity: Entity,
+        text: &str,
+        menu_command: MenuCommand,
+    ) -> Entity {
+        Button::create()
+            .element("button")
+            .on_click(move |states, _| {
+                states
+                    .get_mut::<NodeContainerState>(self_entity)
+                    .menu_command = Some(menu_command);
+                true
+            })
+            .text(text)
+            .build(bc)
+    }
+
+    fn handle_menu_command(&mut self, ctx: &mut Context) {
+        let (menu_command, node_entity) =
+            match (self.menu_command.take(), self.menu_property_node) {
+                (Some(menu_command), Some(node_entity)) => (menu_command, node_entity),
+                _ => return,
+            };
+
+        match menu_command {
+            MenuCommand::BrowseImage => ctx
+                .widget()
+                .set::<OptionFileBrowserMode>("browse_request", Some(FileBrowserMode::Image)),
+            MenuCommand::DisconnectAll => self.disconnect_all(ctx, node_entity),
+            MenuCommand::EnterSubgraph => self.enter_subgraph(ctx, node_entity),
+            MenuCommand::LinkSubgraph => self.link_subgraph(ctx, node_entity),
+        }
+    }
+
+    /// Removes every edge going into or out of a node.
+    fn disconnect_all(&mut self, ctx: &mut Context, node_entity: Entity) {
+        if !Self::entity_type(ctx, node_entity, WidgetType::Node) {
+            return;
+        }
+        let node_id = *ctx.get_widget(node_entity).get::<u32>("node_id");
+
+        let edge_entities: Vec<Entity> = Self::children_type(ctx, WidgetType::Edge)
+            .into_iter()
+            .filter(|edge_entity| {
+                let edge_widget = ctx.get_widget(*edge_entity);
+                *edge_widget.get::<u32>("output_node") == node_id
+                    || *edge_widget.get::<u32>("input_node") == node_id
+            })
+            .collect();
+        if edge_entities.is_empty() {
+            return;
+        }
+
+        self.push_history(Edit::Disconnect);
+        for edge_entity in edge_entities {
+            self.delete_edge(ctx, edge_entity);
         }
     }
 
     fn handle_add_node(&mut self, ctx: &mut Context) {
         if let Some(node_type) = ctx.widget().get::<OptionNodeType>("add_node").clone() {
+            let before = self.node_graph_spatial.clone();
             let node_id = self
                 .node_graph_spatial
                 .node_graph
                 .add_node(CoreNode::new(node_type))
                 .unwrap();
+            self.push_history_before(Edit::AddNode(node_id), before);
+
+            if let Some(generator_type) = *ctx.widget().get::<OptionGeneratorType>("add_generator")
+            {
+                let generator = Generator::new(generator_type);
+                set_generator(&mut self.node_graph_spatial, node_id, generator.clone());
+                if let Err(e) = bake(&mut self.node_graph_spatial, node_id, &generator) {
+                    Self::show_error(ctx, format!("Could not generate texture: {}", e));
+                }
+            }
+            if let Some(filter) = ctx.widget().get::<OptionFilter>("add_filter").clone() {
+                set_filter(&mut self.node_graph_spatial, node_id, filter);
+            }
+            if let Some(kind) = *ctx.widget().get::<OptionChannelNodeKind>("add_channels") {
+                set_channel_node(&mut self.node_graph_spatial, node_id, ChannelNode::new(kind));
+            }
+
+            // The node goes where the palette was opened.
+            let position = self
+                .view
+                .to_graph(*ctx.widget().get::<Point>("add_node_position"));
+            self.node_graph_spatial.locations.push(Location {
+                node_id,
+                point: (position.x, position.y),
+            });
 
             self.populate_node(ctx, node_id);
+            self.queue_process();
 
-            self.dragged_entity = Some(DragDropEntity {
+            let node = DragDropEntity {
                 entity: Self::get_most_recent_entity_type(ctx, WidgetType::Node),
                 widget_type: WidgetType::Node,
-            });
+            };
+            self.select(ctx, vec![node], SelectMode::Replace);
         }
 
-        ctx.widget().set::<OptionNodeType>("add_node", None)
+        ctx.widget().set::<OptionNodeType>("add_node", None);
+        ctx.widget().set::<OptionGeneratorType>("add_generator", None);
+        ctx.widget().set::<OptionFilter>("add_filter", None);
+        ctx.widget().set::<OptionChannelNodeKind>("add_channels", None);
     }
 
     fn reset_mouse_action(&mut self, ctx: &mut Context) {
         if let Some(action) = ctx.widget().get::<OptionAction>("action") {
             if let Action::Release(_) = action {
-                self.select_entity(ctx, self.dragged_entity);
-                self.dragged_entity = None
+                self.dragged_entity = None;
+                self.drag_group.clear();
             }
         }
 
@@ -475,13 +1840,41 @@ impl NodeContainerState {
             return;
         };
 
-        self.update_node_to_graph(ctx, dragged_entity);
+        let mut node_entities = vec![dragged_entity];
+        node_entities.extend(self.drag_group.iter().map(|(node_entity, _)| *node_entity));
+
+        if !node_entities
+            .iter()
+            .any(|node_entity| self.node_moved(ctx, *node_entity))
+        {
+            return;
+        }
+
+        let node_id = NodeId(*ctx.get_widget(dragged_entity).get::<u32>("node_id"));
+        self.push_history(Edit::MoveNode(node_id));
+
+        for node_entity in node_entities {
+            self.update_node_to_graph(ctx, node_entity);
+        }
+    }
+
+    /// Whether the node widget is somewhere else than its location in the graph.
+    fn node_moved(&self, ctx: &mut Context, node_entity: Entity) -> bool {
+        let (margin, node_id) = {
+            let widget = ctx.get_widget(node_entity);
+            (
+                *widget.get::<Thickness>("my_margin"),
+                NodeId(*widget.get::<u32>("node_id")),
+            )
+        };
+
+        self.try_get_location(ctx, node_id) != Some((margin.left, margin.top))
     }
 
     fn update_node_to_graph(&mut self, ctx: &mut Context<'_>, entity: Entity) {
         let widget = ctx.get_widget(entity);
 
-        let margin = widget.get::<Thickness>("margin");
+        let margin = widget.get::<Thickness>("my_margin");
 
         let node_id = NodeId(*widget.get::<u32>("node_id"));
 
@@ -531,30 +1924,42 @@ impl NodeContainerState {
     fn remove_dragged_edges(&mut self, ctx: &mut Context) {
         let dragged_edge_entities: Vec<Entity> = self.get_dragged_edges(ctx);
 
-        for dragged_edge_entity in dragged_edge_entities {
-            let dragged_edge_widget = ctx.get_widget(dragged_edge_entity);
+        let dragged_edges: Vec<_> = dragged_edge_entities
+            .iter()
+            .map(|dragged_edge_entity| {
+                let dragged_edge_widget = ctx.get_widget(*dragged_edge_entity);
 
-            let (output_node, input_node, output_slot, input_slot): (
-                NodeId,
-                NodeId,
-                SlotId,
-                SlotId,
-            ) = {
                 (
-                    NodeId(*dragged_edge_widget.get::<u32>("output_node")),
-                    NodeId(*dragged_edge_widget.get::<u32>("input_node")),
-                    SlotId(*dragged_edge_widget.get::<u32>("output_slot")),
-                    SlotId(*dragged_edge_widget.get::<u32>("input_slot")),
+                    *dragged_edge_entity,
+                    (
+                        NodeId(*dragged_edge_widget.get::<u32>("output_node")),
+                        NodeId(*dragged_edge_widget.get::<u32>("input_node")),
+                        SlotId(*dragged_edge_widget.get::<u32>("output_slot")),
+                        SlotId(*dragged_edge_widget.get::<u32>("input_slot")),
+                    ),
                 )
-            };
+            })
+            .collect();
+
+        // Loose edges that were never connected are not in the graph, so removing only those is
+        // not an edit.
+        if dragged_edges.iter().any(|(_, (output_node, input_node, output_slot, input_slot))| {
+            self.node_graph_spatial.node_graph.edges.iter().any(|edge| {
+                edge.output_id == *output_node
+                    && edge.input_id == *input_node
+                    && edge.output_slot == *output_slot
+                    && edge.input_slot == *input_slot
+            })
+        }) {
+            self.push_history(Edit::Disconnect);
+        }
 
-            self.node_graph_spatial.node_graph.remove_edge(
-                output_node,
-                input_node,
-                output_slot,
-                input_slot,
-            );
+        for (dragged_edge_entity, (output_node, input_node, output_slot, input_slot)) in
+            dragged_edges
+        {
+            self.remove_edge((output_node, output_slot), (input_node, input_slot));
             ctx.remove_child(dragged_edge_entity);
+            self.queue_process();
         }
 
         self.dragged_edges.0 = Vec::new();
@@ -573,18 +1978,29 @@ impl NodeContainerState {
         if !Self::entity_type(ctx, node_entity, WidgetType::Node) {
             return;
         }
-        let mut dragged_widget = ctx.get_widget(node_entity);
-        let current_margin = *dragged_widget.get::<Thickness>("my_margin");
+        let position = self.mouse_position - self.drag_offset;
+        self.move_node(ctx, node_entity, self.snapped(position));
+
+        for (group_entity, offset) in self.drag_group.clone() {
+            self.move_node(ctx, group_entity, self.snapped(position + offset));
+        }
+    }
 
-        dragged_widget.set::<Thickness>(
+    /// Moves a node widget to `position` in graph space, along with its slots and edges.
+    fn move_node(&mut self, ctx: &mut Context, node_entity: Entity, position: Point) {
+        let mut node_widget = ctx.get_widget(node_entity);
+        let current_margin = *node_widget.get::<Thickness>("my_margin");
+
+        node_widget.set::<Thickness>(
             "my_margin",
             Thickness {
-                left: self.mouse_position.x - self.drag_offset.x,
+                left: position.x,
                 right: current_margin.right,
-                top: self.mouse_position.y - self.drag_offset.y,
+                top: position.y,
                 bottom: current_margin.bottom,
             },
         );
+        self.apply_view_to_node(ctx, node_entity);
 
         self.refresh_node_edges(ctx, node_entity);
         self.refresh_node_slots(ctx, node_entity);
@@ -742,6 +2158,7 @@ impl NodeContainerState {
             y: node_margin.top,
         };
         let slot_position = Self::position_edge(side, slot_id, node_pos);
+        let slot_type = self.slot_type_of(node_id, side, slot_id);
 
         let self_entity = ctx.widget().entity();
         let bc = &mut ctx.build_context();
@@ -754,6 +2171,8 @@ impl NodeContainerState {
                 .input_node(node_id)
                 .output_slot(other_slot_id.unwrap_or_default())
                 .input_slot(slot_id)
+                .view(self.view)
+                .slot_type(slot_type)
                 .build(bc),
             WidgetSide::Output => Edge::create()
                 .id("edge")
@@ -763,6 +2182,8 @@ impl NodeContainerState {
                 .input_node(other_node_id.unwrap_or_default())
                 .output_slot(slot_id)
                 .input_slot(other_slot_id.unwrap_or_default())
+                .view(self.view)
+                .slot_type(slot_type)
                 .build(bc),
         };
         bc.append_child(self_entity, item);
@@ -827,24 +2248,11 @@ impl NodeContainerState {
         if !Self::entity_type(ctx, node_entity, WidgetType::Node) {
             return;
         }
-        let node_widget = ctx.get_widget(node_entity);
-        let node_margin = *node_widget.get::<Thickness>("margin");
-
-        let node_id = *node_widget.get::<u32>("node_id");
+        let node_id = *ctx.get_widget(node_entity).get::<u32>("node_id");
         let slot_entities: Vec<Entity> = self.node_slots(ctx, NodeId(node_id));
 
         for slot_entity in slot_entities {
-            let (slot_id, side) = {
-                let slot_widget = ctx.get_widget(slot_entity);
-                (
-                    *slot_widget.get::<u32>("slot_id"),
-                    *slot_widget.get::<WidgetSide>("side"),
-                )
-            };
-
-            let mut slot_widget = ctx.get_widget(slot_entity);
-
-            slot_widget.set("margin", Self::position_slot(side, slot_id, node_margin));
+            self.apply_view_to_slot(ctx, slot_entity);
         }
     }
 
@@ -934,15 +2342,15 @@ impl NodeContainerState {
         let node_id = *ctx.get_widget(entity).get::<u32>("node_id");
 
         // Delete node in graph
-        self.node_graph_spatial
-            .node_graph
-            .remove_node(NodeId(node_id));
+        self.node_graph_spatial.remove_nodes(&[NodeId(node_id)]);
 
         // Delete connected edges in GUI
         Self::disconnect_node(ctx, entity);
 
         // Delete node in GUI
         ctx.remove_child(entity);
+
+        self.queue_process();
     }
 
     fn disconnect_node(ctx: &mut Context, entity: Entity) {
@@ -978,6 +2386,153 @@ impl NodeContainerState {
         ctx.remove_child(entity);
     }
 
+    /// Returns the top left corner of a `Node` or `Slot` widget in graph space.
+    fn graph_position(ctx: &mut Context, entity: Entity) -> Point {
+        if Self::entity_type(ctx, entity, WidgetType::Slot) {
+            let (node_id, side, slot_id) = {
+                let slot_widget = ctx.get_widget(entity);
+                (
+                    *slot_widget.get::<u32>("node_id"),
+                    *slot_widget.get::<WidgetSide>("side"),
+                    *slot_widget.get::<u32>("slot_id"),
+                )
+            };
+            let node_margin = *ctx
+                .child(&*node_id.to_string())
+                .get::<Thickness>("my_margin");
+
+            Self::thickness_to_point(Self::position_slot(side, slot_id, node_margin))
+        } else if let Some(my_margin) = ctx.get_widget(entity).try_get::<Thickness>("my_margin") {
+            Self::thickness_to_point(*my_margin)
+        } else {
+            Point::new(0., 0.)
+        }
+    }
+
+    /// Returns the bounds of a `Node` or `Slot` widget in graph space.
+    fn graph_bounds(ctx: &mut Context, entity: Entity) -> Rectangle {
+        let position = Self::graph_position(ctx, entity);
+        let (width, height) = if Self::entity_type(ctx, entity, WidgetType::Slot) {
+            (SLOT_SIZE, SLOT_SIZE)
+        } else {
+            (NODE_WIDTH, NODE_HEIGHT)
+        };
+
+        Rectangle::new(position.x, position.y, width, height)
+    }
+
+    /// Places every node, slot and edge on screen according to the current `View`.
+    fn apply_view(&mut self, ctx: &mut Context) {
+        for node_entity in Self::children_type(ctx, WidgetType::Node) {
+            self.apply_view_to_node(ctx, node_entity);
+        }
+
+        for slot_entity in Self::children_type(ctx, WidgetType::Slot) {
+            self.apply_view_to_slot(ctx, slot_entity);
+        }
+
+        for edge_entity in Self::children_type(ctx, WidgetType::Edge) {
+            ctx.get_widget(edge_entity).set::<View>("view", self.view);
+        }
+
+        self.refresh_grid(ctx);
+    }
+
+    /// Shows the grid while snapping is on, and sizes it to cover the whole container.
+    fn refresh_grid(&mut self, ctx: &mut Context) {
+        let bounds = *ctx.widget().get::<Rectangle>("bounds");
+        let visibility = if self.snap_to_grid {
+            Visibility::Visible
+        } else {
+            Visibility::Collapsed
+        };
+
+        let mut grid_widget = ctx.get_widget(self.grid);
+        grid_widget.set::<View>("view", self.view);
+        grid_widget.set::<Visibility>("visibility", visibility);
+
+        let constraint = grid_widget.get_mut::<Constraint>("constraint");
+        constraint.set_width(bounds.width());
+        constraint.set_height(bounds.height());
+    }
+
+    /// Returns where a node dragged to `position` ends up.
+    fn snapped(&self, position: Point) -> Point {
+        if self.snap_to_grid {
+            snap(position)
+        } else {
+            position
+        }
+    }
+
+    /// Moves nodes around with one of the layout functions.
+    fn arrange(&mut self, ctx: &mut Context, layout: impl FnOnce(&mut NodeGraphSpatial)) {
+        let before = self.node_graph_spatial.clone();
+        layout(&mut self.node_graph_spatial);
+
+        self.push_history_before(Edit::Arrange, before);
+        self.reset_workspace(ctx);
+    }
+
+    fn apply_view_to_node(&self, ctx: &mut Context, node_entity: Entity) {
+        let mut node_widget = ctx.get_widget(node_entity);
+        let my_margin = *node_widget.get::<Thickness>("my_margin");
+
+        node_widget.set::<Thickness>("margin", self.view.thickness_to_screen(my_margin));
+        node_widget.set::<f64>("zoom", self.view.zoom);
+
+        let constraint = node_widget.get_mut::<Constraint>("constraint");
+        constraint.set_width(NODE_WIDTH * self.view.zoom);
+        constraint.set_height(NODE_HEIGHT * self.view.zoom);
+    }
+
+    fn apply_view_to_slot(&self, ctx: &mut Context, slot_entity: Entity) {
+        let screen_position = self
+            .view
+            .to_screen(Self::graph_position(ctx, slot_entity));
+
+        let mut slot_widget = ctx.get_widget(slot_entity);
+        slot_widget.set::<Thickness>(
+            "margin",
+            Thickness {
+                left: screen_position.x,
+                top: screen_position.y,
+                right: 0.,
+                bottom: 0.,
+            },
+        );
+
+        let constraint = slot_widget.get_mut::<Constraint>("constraint");
+        constraint.set_width(SLOT_SIZE * self.view.zoom);
+        constraint.set_height(SLOT_SIZE * self.view.zoom);
+    }
+
+    fn frame_all(&mut self, ctx: &mut Context) {
+        let node_entities = Self::children_type(ctx, WidgetType::Node);
+        self.frame_nodes(ctx, &node_entities);
+    }
+
+    fn frame_selected(&mut self, ctx: &mut Context) {
+        let node_entities = self.selected_nodes();
+        self.frame_nodes(ctx, &node_entities);
+    }
+
+    /// Zooms and pans the view so all given nodes are visible.
+    fn frame_nodes(&mut self, ctx: &mut Context, node_entities: &[Entity]) {
+        let node_bounds: Vec<Rectangle> = node_entities
+            .iter()
+            .map(|node_entity| Self::graph_bounds(ctx, *node_entity))
+            .collect();
+
+        if let Some(graph_rect) = union_rectangles(&node_bounds) {
+            let bounds = *ctx.widget().get::<Rectangle>("bounds");
+            let viewport = Rectangle::new(0., 0., bounds.width(), bounds.height());
+
+            self.view.frame(graph_rect, viewport);
+            self.apply_view(ctx);
+        }
+    }
+
     fn is_clickable(ctx: &mut Context, entity: Entity) -> bool {
         if let Some(widget_type) = ctx.get_widget(entity).try_get("widget_type") {
             match widget_type {
@@ -997,8 +2552,15 @@ impl NodeContainerState {
                     self.load_graph(ctx, path);
                 }
                 ActionMain::SaveGraph(path) => {
-                    self.save_graph(path);
+                    self.save_graph(ctx, path);
                 }
+                ActionMain::ExportOutputs(dir) => {
+                    self.export_outputs(ctx, dir);
+                }
+                ActionMain::SetImagePath(path) => self.set_image_path(ctx, path),
+                ActionMain::Recover => self.recover(ctx),
+                ActionMain::FrameAll => self.frame_all(ctx),
+                ActionMain::FrameSelected => self.frame_selected(ctx),
                 _ => {}
             };
 
@@ -1008,6 +2570,7 @@ impl NodeContainerState {
 
     fn populate_workspace(&mut self, ctx: &mut Context<'_>) {
         ctx.clear_children();
+        self.init_grid(ctx);
         self.init_menu_property(ctx);
 
         self.populate_nodes(ctx);
@@ -1039,16 +2602,18 @@ impl NodeContainerState {
 
         let location_point = match self.try_get_location(ctx, node_id) {
             Some(location_point) => location_point,
-            None => (0., 0.),
-        };
+            None => {
+                let location = Location {
+                    node_id,
+                    point: (0., 0.),
+                };
+                self.node_graph_spatial.locations.push(location);
 
-        let location = Location {
-            node_id,
-            point: location_point,
+                (0., 0.)
+            }
         };
-        self.node_graph_spatial.locations.push(location);
 
-        let node_title = format!("{:?}", node_type);
+        let node_title = self.title_of(node_id);
 
         let margin = Thickness {
             left: location_point.0,
@@ -1057,13 +2622,20 @@ impl NodeContainerState {
             bottom: 0.,
         };
 
+        let channel_kind = channel_node(&self.node_graph_spatial, node_id)
+            .map(|channel_node| channel_node.kind);
+
         let slot_count_input = match node_type {
             NodeType::InputGray | NodeType::InputRgba => 0,
-            _ => input_capacity,
+            _ => channel_kind.map_or(input_capacity, |kind| kind.slot_count(WidgetSide::Input)),
         };
         let slot_count_output = match node_type {
             NodeType::OutputGray | NodeType::OutputRgba => 0,
-            _ => outputput_capacity,
+            // Generators only use the first channel of their texture.
+            _ if generator(&self.node_graph_spatial, node_id).is_some() => 1,
+            _ => channel_kind.map_or(outputput_capacity, |kind| {
+                kind.slot_count(WidgetSide::Output)
+            }),
         };
 
         let self_entity = ctx.widget().entity();
@@ -1081,6 +2653,7 @@ impl NodeContainerState {
         bc.append_child(self_entity, item);
 
         let created_node_entity = Self::get_most_recent_entity_type(ctx, WidgetType::Node);
+        self.apply_view_to_node(ctx, created_node_entity);
         self.populate_node_slots(ctx, created_node_entity);
     }
 
@@ -1102,12 +2675,14 @@ impl NodeContainerState {
 
             let item = Slot::create()
                 .node_id(node_id)
-                .margin(slot_margin)
+                .margin(self.view.thickness_to_screen(slot_margin))
                 .side(WidgetSide::Input)
                 .slot_id(i as u32)
+                .slot_type(self.slot_type_of(node_id, WidgetSide::Input, i as u32))
                 .build(build_context);
 
             build_context.append_child(self_entity, item);
+            self.apply_view_to_slot(ctx, item);
         }
 
         for i in 0..*ctx
@@ -1120,12 +2695,14 @@ impl NodeContainerState {
 
             let item = Slot::create()
                 .node_id(node_id)
-                .margin(slot_margin)
+                .margin(self.view.thickness_to_screen(slot_margin))
                 .side(WidgetSide::Output)
                 .slot_id(i as u32)
+                .slot_type(self.slot_type_of(node_id, WidgetSide::Output, i as u32))
                 .build(build_context);
 
             build_context.append_child(self_entity, item);
+            self.apply_view_to_slot(ctx, item);
         }
     }
 
@@ -1142,6 +2719,22 @@ impl NodeContainerState {
     }
 
     fn create_edge(&mut self, ctx: &mut Context, edge: &CoreEdge) {
+        let output = (edge.output_id, edge.output_slot);
+        let input = (edge.input_id, edge.input_slot);
+        if is_bundled(&self.node_graph_spatial, output, input) {
+            return;
+        }
+
+        // Nodes that work on any type take the type of what is connected to them.
+        let output_type =
+            self.slot_type_of(edge.output_id.0, WidgetSide::Output, edge.output_slot.0);
+        let slot_type = match output_type {
+            SlotType::Any => {
+                self.slot_type_of(edge.input_id.0, WidgetSide::Input, edge.input_slot.0)
+            }
+            slot_type => slot_type,
+        };
+
         let self_entity = ctx.widget().entity();
         let bc = &mut ctx.build_context();
 
@@ -1183,6 +2776,8 @@ impl NodeContainerState {
             .input_node(edge.input_id.0)
             .output_slot(output_slot)
             .input_slot(input_slot)
+            .view(self.view)
+            .slot_type(slot_type)
             .build(bc);
 
         bc.append_child(self_entity, item);
@@ -1200,16 +2795,444 @@ impl NodeContainerState {
         }
     }
 
+    /// Marks the graph as changed so it gets processed at the end of this update.
+    fn queue_process(&mut self) {
+        self.process_queued = true;
+    }
+
+    /// Sends the graph off for processing if it has changed, and shows any finished results as
+    /// thumbnails on the nodes.
+    fn handle_processing(&mut self, ctx: &mut Context) {
+        let processor = match &self.processor {
+            Some(processor) => processor,
+            None => return,
+        };
+
+        if self.process_queued {
+            processor.process(self.node_graph_spatial.clone());
+            self.process_queued = false;
+        }
+
+        if let Some(process_result) = processor.try_receive() {
+            for (node_id, image) in process_result.thumbnails {
+                if let Some(mut node_widget) = ctx.try_child(&*node_id.0.to_string()) {
+                    node_widget.set::<ImageData>("thumbnail", image);
+                }
+            }
+
+            self.node_channels = process_result
+                .channels
+                .into_iter()
+                .map(|(node_id, channels)| (node_id, Arc::new(channels)))
+                .collect();
+
+            let process_error = process_result
+                .errors
+                .first()
+                .map(|e| format!("Could not process a node: {}", e));
+            if process_error != self.process_error {
+                if let Some(message) = &process_error {
+                    Self::show_error(ctx, message.clone());
+                }
+                self.process_error = process_error;
+            }
+        }
+    }
+
+    /// Shows the output of the most recently selected node in the texture viewer. The viewer
+    /// keeps showing it when the selection is cleared, until the node is deleted.
+    fn refresh_viewer(&mut self, ctx: &mut Context) {
+        let viewed_node = self.viewed_node.filter(|node_id| {
+            self.node_graph_spatial
+                .node_graph
+                .node_with_id(*node_id)
+                .is_some()
+        });
+
+        let viewer_source = match viewed_node {
+            Some(viewed_node) => ViewerSource {
+                title: self.title_of(viewed_node),
+                channels: self
+                    .node_channels
+                    .iter()
+                    .find(|(node_id, _)| *node_id == viewed_node)
+                    .map(|(_, channels)| Arc::clone(channels)),
+            },
+            None => {
+                self.viewed_node = None;
+                ViewerSource::default()
+            }
+        };
+
+        if !viewer_source.is_same(ctx.widget().get::<ViewerSource>("viewer_source")) {
+            ctx.widget()
+                .set::<ViewerSource>("viewer_source", viewer_source);
+        }
+    }
+
+    /// Gathers the outputs that are picked as maps of the material in the 3D preview.
+    fn refresh_preview(&mut self, ctx: &mut Context) {
+        let mut material = Material::default();
+
+        for (output_id, node_settings) in &self.node_graph_spatial.node_settings {
+            let output_settings = match node_settings {
+                NodeSettings::Output(output_settings) => output_settings,
+                _ => continue,
+            };
+            let channels = self
+                .node_channels
+                .iter()
+                .find(|(node_id, _)| node_id == output_id)
+                .map(|(_, channels)| Arc::clone(channels));
+
+            match output_settings.preview_map {
+                MaterialMap::None => (),
+                MaterialMap::Albedo => material.albedo = channels,
+                MaterialMap::Height => material.height = channels,
+                MaterialMap::Normal => material.normal = channels,
+                MaterialMap::Roughness => material.roughness = channels,
+                MaterialMap::Metallic => material.metallic = channels,
+            }
+        }
+
+        if !material.is_same(ctx.widget().get::<Material>("preview_material")) {
+            ctx.widget()
+                .set::<Material>("preview_material", material);
+        }
+    }
+
+    /// Shows an error message to the user.
+    fn show_error(ctx: &mut Context<'_>, message: String) {
+        ctx.widget()
+            .set::<String16>("error_message", String16::from(message));
+    }
+
     fn load_graph(&mut self, ctx: &mut Context<'_>, path: String) {
-        let file = File::open(path).unwrap();
-        self.node_graph_spatial = serde_json::from_reader(file).unwrap();
+        let node_graph_spatial = match NodeGraphSpatial::load(&path) {
+            Ok(node_graph_spatial) => node_graph_spatial,
+            Err(e) => {
+                Self::show_error(ctx, format!("Could not load graph: {}", e));
+                return;
+            }
+        };
+        self.replace_graph(ctx, node_graph_spatial);
+        self.set_graph_path(ctx, path);
+    }
+
+    /// Loads the changes that were autosaved in an earlier session as a new, unsaved graph.
+    fn recover(&mut self, ctx: &mut Context<'_>) {
+        let node_graph_spatial = match recovery::load_previous() {
+            Some(Ok(node_graph_spatial)) => node_graph_spatial,
+            Some(Err(e)) => {
+                Self::show_error(ctx, format!("Could not recover unsaved changes: {}", e));
+                return;
+            }
+            None => return,
+        };
+        self.replace_graph(ctx, node_graph_spatial);
+        self.show_graph_path(ctx, None);
+        self.mark_modified();
+
+        if let Err(e) = recovery::discard_previous() {
+            Self::show_error(ctx, format!("Could not remove the recovery file: {}", e));
+        }
+    }
+
+    fn replace_graph(&mut self, ctx: &mut Context<'_>, node_graph_spatial: NodeGraphSpatial) {
+        // Loading is undone in the outermost graph, with its own history.
+        if !self.subgraph_stack.is_empty() {
+            self.node_graph_spatial = self.root_graph();
+            self.history = self.subgraph_stack.remove(0).history;
+            self.subgraph_stack.clear();
+        }
+
+        self.push_history(Edit::LoadGraph);
+        self.node_graph_spatial = node_graph_spatial;
+        layout_missing(&mut self.node_graph_spatial);
+
+        if let Some(e) = sync_linked(&mut self.node_graph_spatial).first() {
+            Self::show_error(ctx, format!("Could not update linked subgraph: {}", e));
+        }
+        if let Some(e) = bake_all(&mut self.node_graph_spatial).first() {
+            Self::show_error(ctx, format!("Could not generate texture: {}", e));
+        }
+
+        self.reset_workspace(ctx);
+    }
+
+    /// Writes unsaved changes to the recovery file every once in a while, so they survive a
+    /// crash or closing the window.
+    fn autosave(&mut self, ctx: &mut Context<'_>) {
+        let autosaver = match &self.autosaver {
+            Some(autosaver) => autosaver,
+            None => return,
+        };
+
+        if let Some(e) = autosaver.errors().first() {
+            Self::show_error(ctx, format!("Could not autosave: {}", e));
+        }
+        if self.autosave_pending {
+            autosaver.changed(self.root_graph());
+            self.autosave_pending = false;
+        }
+    }
 
+    /// Rebuilds all widgets from `node_graph_spatial`, dropping anything that refers to the old
+    /// widgets.
+    fn reset_workspace(&mut self, ctx: &mut Context<'_>) {
         self.dragged_edges.0 = Vec::new();
+        self.dragged_entity = None;
+        self.dropped_on_entity = None;
+        self.selected_entities.clear();
+        self.click_to_select = None;
+        self.drag_group.clear();
+        self.box_select = None;
+        self.menu_property_node = None;
+        self.menu_property_list.clear();
+        self.node_channels.clear();
+        self.viewed_node = None;
+
         self.populate_workspace(ctx);
+        self.queue_process();
+    }
+
+    fn push_history(&mut self, edit: Edit) {
+        self.push_history_before(edit, self.node_graph_spatial.clone());
+    }
+
+    /// Records an edit that has already been made to the graph, `before` is the graph as it was
+    /// before the edit.
+    fn push_history_before(&mut self, edit: Edit, before: NodeGraphSpatial) {
+        self.history.push(edit, before, self.graph_path.clone());
+        self.mark_modified();
+    }
+
+    fn mark_modified(&mut self) {
+        self.modified = true;
+        self.autosave_pending = true;
+    }
+
+    /// Whether the graph is as it was last saved or loaded. Each subgraph level has its own
+    /// history, so all of them have to be at their saved state.
+    fn is_saved(&self) -> bool {
+        self.history.is_saved()
+            && self
+                .subgraph_stack
+                .iter()
+                .all(|level| level.history.is_saved())
+    }
+
+    /// Updates whether the graph has unsaved changes after moving through the history, which can
+    /// lead back to the saved graph.
+    fn refresh_modified(&mut self, ctx: &mut Context<'_>) {
+        if self.is_saved() {
+            self.modified = false;
+            self.autosave_pending = false;
+            self.discard_recovery(ctx);
+        } else {
+            self.mark_modified();
+        }
+    }
+
+    fn undo(&mut self, ctx: &mut Context<'_>) {
+        let current = self.node_graph_spatial.clone();
+        if let Some((node_graph_spatial, graph_path)) =
+            self.history.undo(current, self.graph_path.clone())
+        {
+            self.node_graph_spatial = node_graph_spatial;
+            self.show_graph_path(ctx, graph_path);
+            self.refresh_modified(ctx);
+            self.reset_workspace(ctx);
+        }
+    }
+
+    fn redo(&mut self, ctx: &mut Context<'_>) {
+        let current = self.node_graph_spatial.clone();
+        if let Some((node_graph_spatial, graph_path)) =
+            self.history.redo(current, self.graph_path.clone())
+        {
+            self.node_graph_spatial = node_graph_spatial;
+            self.show_graph_path(ctx, graph_path);
+            self.refresh_modified(ctx);
+            self.reset_workspace(ctx);
+        }
     }
 
-    fn save_graph(&mut self, path: String) {
-        let file = File::create(path).unwrap();
-        serde_json::to_writer_pretty(&file, &self.node_graph_spatial).unwrap();
+    /// Makes `graph_path` the file the graph is saved to, and shows it in the title bar.
+    fn show_graph_path(&mut self, ctx: &mut Context<'_>, graph_path: Option<PathBuf>) {
+        let text = graph_path
+            .as_ref()
+            .map_or(String::new(), |graph_path| graph_path.to_string_lossy().to_string());
+        ctx.widget()
+            .set::<String16>("graph_path", String16::from(text));
+        self.graph_path = graph_path;
+    }
+
+    /// Remembers the file the graph was loaded from or saved to, and shows it in the title bar.
+    fn set_graph_path(&mut self, ctx: &mut Context<'_>, path: String) {
+        self.show_graph_path(ctx, Some(PathBuf::from(path)));
+        self.history.mark_saved();
+        for level in &mut self.subgraph_stack {
+            level.history.mark_saved();
+        }
+        self.refresh_modified(ctx);
+    }
+
+    /// Removes the recovery file and drops changes waiting to be written to it, once the graph
+    /// has no unsaved changes.
+    fn discard_recovery(&self, ctx: &mut Context<'_>) {
+        if let Some(autosaver) = &self.autosaver {
+            autosaver.discard();
+        }
+        if let Err(e) = recovery::discard() {
+            Self::show_error(ctx, format!("Could not remove the recovery file: {}", e));
+        }
+    }
+
+    fn save_graph(&mut self, ctx: &mut Context<'_>, path: String) {
+        match self.root_graph().save(&path) {
+            Ok(()) => self.set_graph_path(ctx, path),
+            Err(e) => Self::show_error(ctx, format!("Could not save graph: {}", e)),
+        }
+    }
+
+    /// Puts a path in the path box of the Image node whose properties are shown.
+    fn set_image_path(&mut self, ctx: &mut Context<'_>, path: String) {
+        let node_entity = match self.menu_property_node {
+            Some(node_entity) => node_entity,
+            None => return,
+        };
+
+        if let NodeType::Image(_) = self.node_type_of_entity(ctx, node_entity) {
+            ctx.get_widget(self.menu_property_list[0])
+                .set::<String16>("text", String16::from(path));
+        }
+    }
+
+    /// Writes every output node to `dir`, output file names can refer to the name of the
+    /// graph file.
+    fn export_outputs(&mut self, ctx: &mut Context<'_>, dir: String) {
+        let graph_name = self
+            .graph_path
+            .as_ref()
+            .and_then(|graph_path| graph_path.file_stem())
+            .map_or("untitled".into(), |file_stem| file_stem.to_string_lossy());
+
+        if let Err(e) = export_outputs(&self.root_graph(), Path::new(&dir), &graph_name) {
+            Self::show_error(ctx, format!("Could not export outputs: {}", e));
+        }
+    }
+
+    /// Returns the outermost graph with the changes made in any entered subgraphs.
+    fn root_graph(&self) -> NodeGraphSpatial {
+        let mut node_graph_spatial = self.node_graph_spatial.clone();
+
+        for level in self.subgraph_stack.iter().rev() {
+            let mut parent = level.parent.clone();
+            leave(&mut parent, level.node_id, node_graph_spatial);
+            node_graph_spatial = parent;
+        }
+
+        node_graph_spatial
+    }
+
+    /// Replaces the selected nodes with a Graph node containing them.
+    fn collapse_selection(&mut self, ctx: &mut Context<'_>) {
+        let node_ids = self.selected_node_ids(ctx);
+        if node_ids.is_empty() {
+            return;
+        }
+
+        let before = self.node_graph_spatial.clone();
+        if collapse(&mut self.node_graph_spatial, &node_ids).is_some() {
+            self.push_history_before(Edit::Collapse, before);
+        } else {
+            self.node_graph_spatial = before;
+        }
+
+        self.reset_workspace(ctx);
+    }
+
+    /// Shows the contents of a Graph node in the workspace instead of the current graph. Each
+    /// subgraph has its own undo history.
+    fn enter_subgraph(&mut self, ctx: &mut Context<'_>, node_entity: Entity) {
+        let node_id = NodeId(*ctx.get_widget(node_entity).get::<u32>("node_id"));
+        let inner = match enter(&self.node_graph_spatial, node_id) {
+            Some(inner) => inner,
+            None => return,
+        };
+
+        let parent = std::mem::replace(&mut self.node_graph_spatial, inner);
+        layout_missing(&mut self.node_graph_spatial);
+        let history = std::mem::take(&mut self.history);
+        self.subgraph_stack.push(SubgraphLevel {
+            node_id,
+            parent,
+            history,
+        });
+
+        self.reset_workspace(ctx);
+    }
+
+    /// Goes back to the graph containing the current subgraph. If the subgraph was changed, it is
+    /// put back into its Graph node and written to its file if it is linked to one.
+    fn leave_subgraph(&mut self, ctx: &mut Context<'_>) {
+        let level = match self.subgraph_stack.pop() {
+            Some(level) => level,
+            None => return,
+        };
+
+        let changed = match enter(&level.parent, level.node_id) {
+            Some(mut entered) => {
+                layout_missing(&mut entered);
+                serde_json::to_value(&entered).ok()
+                    != serde_json::to_value(&self.node_graph_spatial).ok()
+            }
+            None => true,
+        };
+
+        if changed {
+            if let Some(path) = linked_path(&level.parent, level.node_id) {
+                if let Err(e) = self.node_graph_spatial.save(path) {
+                    Self::show_error(ctx, format!("Could not save linked subgraph: {}", e));
+                }
+            }
+        }
+
+        // The changes are already in the graph file if they were saved from inside the subgraph.
+        let saved = self.history.is_saved() && level.history.is_saved();
+        let inner = std::mem::replace(&mut self.node_graph_spatial, level.parent);
+        self.history = level.history;
+        if changed {
+            self.push_history(Edit::EditSubgraph(level.node_id));
+            leave(&mut self.node_graph_spatial, level.node_id, inner);
+            if saved {
+                self.history.mark_saved();
+                self.refresh_modified(ctx);
+            }
+        }
+
+        self.reset_workspace(ctx);
+    }
+
+    /// Loads the graph file in the path box of a Graph node into it.
+    fn link_subgraph(&mut self, ctx: &mut Context<'_>, node_entity: Entity) {
+        let node_id = NodeId(*ctx.get_widget(node_entity).get::<u32>("node_id"));
+        let path = match self.menu_property_list.first() {
+            Some(path_box) => ctx.get_widget(*path_box).get::<String16>("text").to_string(),
+            None => return,
+        };
+
+        let before = self.node_graph_spatial.clone();
+        match link(&mut self.node_graph_spatial, node_id, &path) {
+            Ok(()) => {
+                self.push_history_before(Edit::SetProperty(node_id), before);
+                if let Some(e) = bake_nodes(&mut self.node_graph_spatial, &[node_id]).first() {
+                    Self::show_error(ctx, format!("Could not generate texture: {}", e));
+                }
+                self.reset_workspace(ctx);
+            }
+            Err(e) => Self::show_error(ctx, format!("Could not link subgraph: {}", e)),
+        }
     }
 }
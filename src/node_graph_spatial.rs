@@ -0,0 +1,270 @@
+use crate::{
+    blend::MixNode,
+    channels::ChannelNode,
+    export::OutputSettings,
+    filter::{Filter, HeightToNormal},
+    generator::{unbaked, Generator},
+};
+use kanter_core::{
+    node::{Node as CoreNode, NodeType, Side},
+    node_graph::{Edge as CoreEdge, NodeGraph, NodeId},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The version of the graph file format written by `NodeGraphSpatial::save`. Whenever the
+/// serialized shape of the graph changes in a way `#[serde(default)]` can't cover, this is bumped
+/// and a function upgrading files from the previous version is added to `MIGRATIONS`.
+pub const FORMAT_VERSION: u64 = 1;
+
+/// Upgrades a graph file from the version equal to its index to the next version. Files written
+/// before the format had a version are version 0.
+const MIGRATIONS: [fn(&mut Value); FORMAT_VERSION as usize] = [migrate_0_to_1];
+
+#[derive(Debug)]
+pub enum GraphFileError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, serde_json::Error),
+    Serialize(PathBuf, serde_json::Error),
+    NewerVersion(PathBuf, u64),
+}
+
+impl fmt::Display for GraphFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            // The error already ends with the line and column, when it has them.
+            Self::Parse(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Serialize(path, error) => write!(
+                f,
+                "{}: could not serialize the graph: {}",
+                path.display(),
+                error
+            ),
+            Self::NewerVersion(path, version) => write!(
+                f,
+                "{}: the file has format version {}, but the newest supported version is {}",
+                path.display(),
+                version,
+                FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GraphFileError {}
+
+/// A `NodeGraph` together with the position of each node in the workspace and the settings of
+/// the nodes `kanter_core` doesn't know about. This is what gets written to and read from graph
+/// files.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct NodeGraphSpatial {
+    pub locations: Vec<Location>,
+    pub node_graph: NodeGraph,
+    // Clips on the system clipboard are not versioned, so this has to be optional there.
+    #[serde(default)]
+    pub node_settings: HashMap<NodeId, NodeSettings>,
+}
+
+/// The settings of a node that live outside of the `NodeGraph`. Nodes that need none, like
+/// Value nodes, have no entry.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum NodeSettings {
+    Output(OutputSettings),
+    Subgraph(Subgraph),
+    Generator(Generator),
+    Filter(Filter),
+    Mix(MixNode),
+    Channels(ChannelNode),
+    HeightToNormal(HeightToNormal),
+}
+
+/// The parts of a subgraph that live outside of the `NodeGraph` stored in its Graph node.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Subgraph {
+    /// The graph file the subgraph is kept in sync with, empty if it only exists in this graph.
+    pub path: String,
+    pub locations: Vec<Location>,
+    #[serde(default)]
+    pub node_settings: HashMap<NodeId, NodeSettings>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Location {
+    pub node_id: NodeId,
+    pub point: (f64, f64),
+}
+
+/// Why the contents of a graph file could not be read, `GraphFileError` adds the path.
+enum LoadError {
+    Parse(serde_json::Error),
+    NewerVersion(u64),
+}
+
+/// The top level of a graph file.
+#[derive(Serialize)]
+struct GraphFile<'a> {
+    version: u64,
+    #[serde(flatten)]
+    node_graph_spatial: &'a NodeGraphSpatial,
+}
+
+impl NodeGraphSpatial {
+    /// Loads a graph file, upgrading it to the current format version if it is older.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, GraphFileError> {
+        let path = path.as_ref();
+
+        let json = fs::read_to_string(path).map_err(|e| GraphFileError::Io(path.to_owned(), e))?;
+        Self::from_json(&json).map_err(|e| match e {
+            LoadError::Parse(e) => GraphFileError::Parse(path.to_owned(), e),
+            LoadError::NewerVersion(version) => {
+                GraphFileError::NewerVersion(path.to_owned(), version)
+            }
+        })
+    }
+
+    /// Reads the contents of a graph file. Files of the current version are read straight from
+    /// the text, so errors in them come with a line and column.
+    fn from_json(json: &str) -> Result<Self, LoadError> {
+        #[derive(Deserialize)]
+        struct Version {
+            #[serde(default)]
+            version: u64,
+        }
+
+        let version = serde_json::from_str::<Version>(json)
+            .map_err(LoadError::Parse)?
+            .version;
+        if version > FORMAT_VERSION {
+            return Err(LoadError::NewerVersion(version));
+        } else if version == FORMAT_VERSION {
+            return serde_json::from_str(json).map_err(LoadError::Parse);
+        }
+
+        let mut value: Value = serde_json::from_str(json).map_err(LoadError::Parse)?;
+        for migration in &MIGRATIONS[version as usize..] {
+            migration(&mut value);
+        }
+
+        serde_json::from_value(value).map_err(LoadError::Parse)
+    }
+
+    /// Removes the given nodes from the graph, along with their locations and settings.
+    pub fn remove_nodes(&mut self, node_ids: &[NodeId]) {
+        for node_id in node_ids {
+            self.node_graph.remove_node(*node_id);
+            self.node_settings.remove(node_id);
+        }
+        self.locations
+            .retain(|location| !node_ids.contains(&location.node_id));
+    }
+
+    /// Changes the `NodeType` of a node, through the `NodeGraph` setter for it where there is one.
+    /// Edges into input slots the new type doesn't have are removed and returned.
+    pub fn set_node_type(&mut self, node_id: NodeId, node_type: NodeType) -> Vec<CoreEdge> {
+        let input_count = CoreNode::new(node_type.clone()).capacity(Side::Input);
+        let removed: Vec<CoreEdge> = self
+            .node_graph
+            .edges
+            .iter()
+            .filter(|edge| edge.input_id == node_id && edge.input_slot.0 as usize >= input_count)
+            .copied()
+            .collect();
+        for edge in &removed {
+            self.node_graph.remove_edge(
+                edge.output_id,
+                edge.input_id,
+                edge.output_slot,
+                edge.input_slot,
+            );
+        }
+
+        let current_type = match self.node_graph.node_with_id(node_id) {
+            Some(node) => node.node_type.clone(),
+            None => return removed,
+        };
+        // The setters only fail for missing nodes or nodes of another type, which was just ruled
+        // out.
+        match (current_type, node_type) {
+            (NodeType::Mix(_), NodeType::Mix(mix_type)) => {
+                let _ = self.node_graph.set_mix_type(node_id, mix_type);
+            }
+            (NodeType::Image(_), NodeType::Image(path)) => {
+                let _ = self.node_graph.set_image_node_path(node_id, path);
+            }
+            (_, node_type) => {
+                if let Some(node) = self.node_graph.node_with_id_mut(node_id) {
+                    node.node_type = node_type;
+                }
+            }
+        }
+
+        removed
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GraphFileError> {
+        let path = path.as_ref();
+
+        let graph_file = GraphFile {
+            version: FORMAT_VERSION,
+            node_graph_spatial: &unbaked(self),
+        };
+
+        let file = File::create(path).map_err(|e| GraphFileError::Io(path.to_owned(), e))?;
+        serde_json::to_writer_pretty(&file, &graph_file).map_err(|e| {
+            if e.is_io() {
+                GraphFileError::Io(path.to_owned(), e.into())
+            } else {
+                GraphFileError::Serialize(path.to_owned(), e)
+            }
+        })
+    }
+}
+
+/// Version 0 is the unversioned format, which only lacks the version field.
+fn migrate_0_to_1(value: &mut Value) {
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), Value::from(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_0_to_1_adds_the_version() {
+        let mut value = serde_json::json!({ "locations": [], "node_graph": {} });
+        migrate_0_to_1(&mut value);
+
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["locations"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn type_errors_in_current_files_have_a_line() {
+        let json = "{\n  \"version\": 1,\n  \"locations\": 5\n}";
+
+        match NodeGraphSpatial::from_json(json) {
+            Err(LoadError::Parse(e)) => assert_eq!(e.line(), 3),
+            _ => panic!("the locations should fail to parse"),
+        }
+    }
+
+    #[test]
+    fn newer_files_are_refused() {
+        let json = format!("{{ \"version\": {} }}", FORMAT_VERSION + 1);
+
+        match NodeGraphSpatial::from_json(&json) {
+            Err(LoadError::NewerVersion(version)) => assert_eq!(version, FORMAT_VERSION + 1),
+            _ => panic!("the version should be refused"),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    channels::{channel_node_type, ChannelNodeKind},
+    filter::Filter,
+    generator::GeneratorType,
+    subgraph::passthrough,
+};
+use kanter_core::{
+    node::{MixType, NodeType},
+    node_graph::NodeGraph,
+};
+
+/// A kind of node that can be added from the node palette.
+#[derive(Clone, Debug)]
+pub struct NodeEntry {
+    pub name: &'static str,
+    pub category: &'static str,
+    pub node_type: NodeType,
+    /// Generator nodes are image nodes in the `NodeGraph`, reading the texture they generate.
+    pub generator: Option<GeneratorType>,
+    /// Filter nodes are Graph nodes in the `NodeGraph` passing their input through.
+    pub filter: Option<Filter>,
+    /// Channel nodes are Graph nodes in the `NodeGraph` passing their inputs through.
+    pub channels: Option<ChannelNodeKind>,
+}
+
+impl NodeEntry {
+    fn new(name: &'static str, category: &'static str, node_type: NodeType) -> Self {
+        Self {
+            name,
+            category,
+            node_type,
+            generator: None,
+            filter: None,
+            channels: None,
+        }
+    }
+
+    fn generator(generator_type: GeneratorType) -> Self {
+        Self {
+            name: generator_type.name(),
+            category: "Generator",
+            node_type: NodeType::Image(String::new()),
+            generator: Some(generator_type),
+            filter: None,
+            channels: None,
+        }
+    }
+
+    fn filter(filter: Filter) -> Self {
+        Self {
+            name: filter.name(),
+            category: "Filter",
+            node_type: passthrough(1, 1),
+            generator: None,
+            filter: Some(filter),
+            channels: None,
+        }
+    }
+
+    fn channels(kind: ChannelNodeKind) -> Self {
+        Self {
+            name: kind.name(),
+            category: "Channel",
+            node_type: channel_node_type(),
+            generator: None,
+            filter: None,
+            channels: Some(kind),
+        }
+    }
+
+    fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+
+        self.name.to_lowercase().contains(&filter) || self.category.to_lowercase().contains(&filter)
+    }
+}
+
+/// Returns every node that can be added, in the order they are listed in the palette.
+pub fn node_entries() -> Vec<NodeEntry> {
+    let mut node_entries = vec![
+        NodeEntry::new("Image", "Input", NodeType::Image(String::new())),
+        NodeEntry::new("Value", "Input", NodeType::Value(0.)),
+        NodeEntry::new("Input gray", "Input", NodeType::InputGray),
+        NodeEntry::new("Input RGBA", "Input", NodeType::InputRgba),
+        NodeEntry::new("Mix", "Operation", NodeType::Mix(MixType::default())),
+        NodeEntry::new("Resize", "Operation", NodeType::Resize(None, None)),
+        NodeEntry::new("Height to normal", "Operation", NodeType::HeightToNormal),
+        NodeEntry::new("Output gray", "Output", NodeType::OutputGray),
+        NodeEntry::new("Output RGBA", "Output", NodeType::OutputRgba),
+        NodeEntry::new("Graph", "Graph", NodeType::Graph(NodeGraph::default())),
+    ];
+    node_entries.extend(GeneratorType::ALL.iter().map(|generator_type| {
+        NodeEntry::generator(*generator_type)
+    }));
+    node_entries.extend(Filter::defaults().into_iter().map(NodeEntry::filter));
+    node_entries.extend(ChannelNodeKind::ALL.iter().map(|kind| NodeEntry::channels(*kind)));
+
+    node_entries
+}
+
+/// Returns the title shown on a node. Graph nodes would otherwise print their whole contents.
+pub fn node_title(node_type: &NodeType) -> String {
+    match node_type {
+        NodeType::Graph(_) => "Graph".to_string(),
+        _ => format!("{:?}", node_type),
+    }
+}
+
+/// Returns the nodes whose name or category contains `filter`, ignoring case.
+pub fn search(filter: &str) -> Vec<NodeEntry> {
+    node_entries()
+        .into_iter()
+        .filter(|node_entry| node_entry.matches(filter.trim()))
+        .collect()
+}
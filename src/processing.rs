@@ -0,0 +1,752 @@
+use crate::{
+    blend::MixNode,
+    channels::{ChannelNode, CHANNEL_COUNT},
+    connection::is_downstream,
+    filter::{height_to_normal, Filter, HeightToNormal},
+    node_graph_spatial::{NodeGraphSpatial, NodeSettings},
+    subgraph::{enter, inline},
+};
+use image::{ImageBuffer, Luma, Rgba};
+use kanter_core::{
+    dag::TextureProcessor,
+    node::NodeType,
+    node_graph::{NodeGraph, NodeId, SlotId},
+};
+use orbtk::prelude::*;
+use std::{
+    fmt, fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    thread,
+};
+
+pub const THUMBNAIL_SIZE: u32 = 64;
+
+/// Numbers the directories the results of deferred nodes are written to, so processing on
+/// several threads at once doesn't mix them up.
+static FILTER_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// An image stored as one `0xAARRGGBB` value per pixel, ready to be drawn by OrbTk.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImageData {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u32>,
+}
+into_property_source!(ImageData);
+
+impl ImageData {
+    /// Returns a nearest-neighbour scaled copy of the image that fits within `size` x `size`.
+    pub fn downscaled(&self, size: u32) -> Self {
+        if self.width == 0 || self.height == 0 {
+            return Self::default();
+        }
+
+        let scale = (size as f64 / self.width.max(self.height) as f64).min(1.);
+        let width = ((self.width as f64 * scale) as u32).max(1);
+        let height = ((self.height as f64 * scale) as u32).max(1);
+
+        let mut data = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            let source_y = (y as f64 / scale) as u32;
+            for x in 0..width {
+                let source_x = (x as f64 / scale) as u32;
+                data.push(self.data[(source_y * self.width + source_x) as usize]);
+            }
+        }
+
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ProcessResult {
+    pub thumbnails: Vec<(NodeId, ImageData)>,
+    /// The full resolution output of every node, for the texture viewer.
+    pub channels: Vec<(NodeId, NodeChannels)>,
+    /// The deferred nodes that could not compute their result, they output nothing.
+    pub errors: Vec<ProcessError>,
+}
+
+/// Why a deferred node could not compute its result.
+#[derive(Debug)]
+pub enum ProcessError {
+    NoCacheDir,
+    Io(PathBuf, io::Error),
+    Image(PathBuf, image::ImageError),
+    /// The result of the node with the given id does not fill its image.
+    Size(u32),
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoCacheDir => write!(
+                f,
+                "there is no cache directory to compute filter, channel and mix nodes in"
+            ),
+            Self::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Image(path, error) => write!(f, "{}: {}", path.display(), error),
+            Self::Size(node_id) => {
+                write!(f, "node {} computed a result of the wrong size", node_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+/// Runs graphs through `kanter_core` on a background thread.
+pub struct Processor {
+    graph_sender: Sender<NodeGraphSpatial>,
+    result_receiver: Receiver<ProcessResult>,
+}
+
+impl Processor {
+    pub fn new() -> Self {
+        let (graph_sender, graph_receiver) = mpsc::channel::<NodeGraphSpatial>();
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            while let Ok(mut node_graph_spatial) = graph_receiver.recv() {
+                // Only the most recent graph is interesting, skip anything that queued up while
+                // the previous one was processing.
+                while let Ok(newer_node_graph_spatial) = graph_receiver.try_recv() {
+                    node_graph_spatial = newer_node_graph_spatial;
+                }
+
+                // A broken node, like an image node with an invalid path, should not take the
+                // whole processing thread down with it.
+                let result =
+                    panic::catch_unwind(AssertUnwindSafe(|| process(&node_graph_spatial)))
+                        .unwrap_or_default();
+
+                if result_sender.send(result).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            graph_sender,
+            result_receiver,
+        }
+    }
+
+    /// Queues the given graph for processing.
+    pub(crate) fn process(&self, node_graph_spatial: NodeGraphSpatial) {
+        let _ = self.graph_sender.send(node_graph_spatial);
+    }
+
+    /// Returns the most recent finished result, if any arrived since the last call.
+    pub fn try_receive(&self) -> Option<ProcessResult> {
+        self.result_receiver.try_iter().last()
+    }
+}
+
+/// Processes the given `NodeGraph` on the current thread.
+pub fn process_node_graph(node_graph: NodeGraph) -> TextureProcessor {
+    let mut tex_pro = TextureProcessor::new();
+    tex_pro.node_graph = node_graph;
+    tex_pro.process();
+    tex_pro
+}
+
+/// A node `kanter_core` can't process, which is computed between passes instead.
+#[derive(Copy, Clone)]
+enum Deferred<'a> {
+    Filter(NodeId, &'a Filter),
+    Mix(NodeId, &'a MixNode),
+    Channels(NodeId, &'a ChannelNode),
+    HeightToNormal(NodeId, HeightToNormal),
+}
+
+impl Deferred<'_> {
+    fn node_id(&self) -> NodeId {
+        match self {
+            Self::Filter(node_id, _)
+            | Self::Mix(node_id, _)
+            | Self::Channels(node_id, _)
+            | Self::HeightToNormal(node_id, _) => *node_id,
+        }
+    }
+}
+
+/// The result of `process_graph`.
+pub(crate) struct Processed {
+    pub tex_pro: TextureProcessor,
+    /// The deferred nodes that could not compute their result, they output nothing.
+    pub errors: Vec<ProcessError>,
+    /// The Graph nodes that were replaced by their contents.
+    inlined: Vec<Inlined>,
+    /// The results of the deferred nodes as they were computed, before being written to images.
+    results: Vec<(NodeId, NodeChannels)>,
+}
+
+/// A Graph node that was replaced by its contents, with where each of its output slots comes
+/// from.
+type Inlined = (NodeId, Vec<Option<(NodeId, SlotId)>>);
+
+impl Processed {
+    /// Collects the output slots of a processed node like `node_channels`, following Graph nodes
+    /// that were inlined to the nodes that give their outputs.
+    pub fn node_channels(&self, node_id: NodeId) -> Option<NodeChannels> {
+        if let Some(result) = deferred_result(&self.results, node_id) {
+            return Some(result.clone());
+        }
+        if let Some(channels) = self.output_channels(node_id) {
+            return Some(channels);
+        }
+
+        let outputs = match self.inlined_outputs(node_id) {
+            Some(outputs) => outputs,
+            None => return node_channels(&self.tex_pro, node_id),
+        };
+
+        let slots: Vec<(u32, u32, Vec<f32>)> = outputs
+            .iter()
+            .filter_map(|source| self.slot_values((*source)?))
+            .collect();
+        let (width, height, _) = slots.first()?;
+        let (width, height) = (*width, *height);
+        let channels = slots
+            .into_iter()
+            .filter(|(slot_width, slot_height, _)| (*slot_width, *slot_height) == (width, height))
+            .map(|(_, _, values)| values)
+            .collect();
+
+        Some(NodeChannels {
+            width,
+            height,
+            channels,
+        })
+    }
+
+    /// Collects the channels of an output node whose inputs all come straight from deferred
+    /// nodes, from their results as they were computed.
+    fn output_channels(&self, node_id: NodeId) -> Option<NodeChannels> {
+        let node_graph = &self.tex_pro.node_graph;
+        match node_graph.node_with_id(node_id)?.node_type {
+            NodeType::OutputGray | NodeType::OutputRgba => (),
+            _ => return None,
+        }
+
+        let mut edges: Vec<_> = node_graph
+            .edges
+            .iter()
+            .filter(|edge| edge.input_id == node_id)
+            .collect();
+        edges.sort_by_key(|edge| edge.input_slot.0);
+        let slots: Vec<(u32, u32, Vec<f32>)> = edges
+            .iter()
+            .map(|edge| result_slot(&self.results, (edge.output_id, edge.output_slot)))
+            .collect::<Option<_>>()?;
+
+        let (width, height, _) = slots.first()?;
+        let (width, height) = (*width, *height);
+        if slots
+            .iter()
+            .any(|(slot_width, slot_height, _)| (*slot_width, *slot_height) != (width, height))
+        {
+            return None;
+        }
+
+        Some(NodeChannels {
+            width,
+            height,
+            channels: slots.into_iter().map(|(_, _, values)| values).collect(),
+        })
+    }
+
+    fn inlined_outputs(&self, node_id: NodeId) -> Option<&[Option<(NodeId, SlotId)>]> {
+        self.inlined
+            .iter()
+            .find(|(inlined_id, _)| *inlined_id == node_id)
+            .map(|(_, outputs)| outputs.as_slice())
+    }
+
+    fn slot_values(&self, source: (NodeId, SlotId)) -> Option<(u32, u32, Vec<f32>)> {
+        let (node_id, slot_id) = source;
+
+        if let Some(outputs) = self.inlined_outputs(node_id) {
+            return self.slot_values((*outputs.get(slot_id.0 as usize)?)?);
+        }
+        if let Some(values) = result_slot(&self.results, source) {
+            return Some(values);
+        }
+
+        self.tex_pro
+            .node_datas(node_id)
+            .into_iter()
+            .find(|node_data| node_data.slot_id == slot_id)
+            .map(|node_data| {
+                (
+                    node_data.size.width,
+                    node_data.size.height,
+                    node_data.value.to_vec(),
+                )
+            })
+    }
+}
+
+fn deferred_result(results: &[(NodeId, NodeChannels)], node_id: NodeId) -> Option<&NodeChannels> {
+    results
+        .iter()
+        .find(|(result_id, _)| *result_id == node_id)
+        .map(|(_, result)| result)
+}
+
+/// Returns the size and values of an output slot of a deferred node that computed its result.
+fn result_slot(
+    results: &[(NodeId, NodeChannels)],
+    source: (NodeId, SlotId),
+) -> Option<(u32, u32, Vec<f32>)> {
+    let result = deferred_result(results, source.0)?;
+    let values = result.channels.get(source.1 .0 as usize)?;
+
+    Some((result.width, result.height, values.clone()))
+}
+
+fn is_deferred(node_settings: &NodeSettings) -> bool {
+    matches!(
+        node_settings,
+        NodeSettings::Filter(_) | NodeSettings::Mix(_) | NodeSettings::Channels(_)
+    )
+}
+
+/// Returns the height to normal nodes in the graph, they are all deferred whether they have
+/// settings stored or not.
+fn height_to_normal_nodes(node_graph_spatial: &NodeGraphSpatial) -> Vec<NodeId> {
+    node_graph_spatial
+        .node_graph
+        .node_ids()
+        .into_iter()
+        .filter(|node_id| {
+            node_graph_spatial
+                .node_graph
+                .node_with_id(*node_id)
+                .map_or(false, |node| {
+                    matches!(node.node_type, NodeType::HeightToNormal)
+                })
+        })
+        .collect()
+}
+
+/// Whether there are deferred nodes in the graph or any of its subgraphs.
+fn has_deferred(node_graph_spatial: &NodeGraphSpatial) -> bool {
+    !height_to_normal_nodes(node_graph_spatial).is_empty()
+        || node_graph_spatial
+            .node_settings
+            .iter()
+            .any(|(node_id, node_settings)| match node_settings {
+                NodeSettings::Subgraph(_) => {
+                    enter(node_graph_spatial, *node_id).map_or(false, |inner| has_deferred(&inner))
+                }
+                node_settings => is_deferred(node_settings),
+            })
+}
+
+/// Deferred nodes are only computed at the top level, so the Graph nodes containing any are
+/// replaced by their contents.
+fn inline_deferred(node_graph_spatial: &NodeGraphSpatial) -> (NodeGraphSpatial, Vec<Inlined>) {
+    let mut node_graph_spatial = node_graph_spatial.clone();
+    let mut inlined = Vec::new();
+
+    loop {
+        let next = node_graph_spatial
+            .node_settings
+            .iter()
+            .filter(|(_, node_settings)| matches!(node_settings, NodeSettings::Subgraph(_)))
+            .map(|(node_id, _)| *node_id)
+            .find(|node_id| {
+                enter(&node_graph_spatial, *node_id).map_or(false, |inner| has_deferred(&inner))
+            });
+
+        match next.and_then(|node_id| {
+            inline(&mut node_graph_spatial, node_id).map(|outputs| (node_id, outputs))
+        }) {
+            Some(inlined_node) => inlined.push(inlined_node),
+            None => return (node_graph_spatial, inlined),
+        }
+    }
+}
+
+/// Processes the given graph on the current thread, including its filter, channel and height
+/// to normal nodes, and the Mix nodes `kanter_core` can't do on its own. The graph is processed
+/// in passes. After each pass the deferred nodes whose inputs are ready compute their result, and
+/// are replaced by image nodes reading it for the next pass, so each layer of deferred nodes costs
+/// a pass over the whole graph.
+///
+/// `kanter_core` can't read floating point images, so the nodes it processes after a deferred
+/// node get the result clamped to 0..1 as a 16 bit image. Deferred nodes, output nodes and the
+/// returned channels get the result as it was computed.
+///
+/// Deferred nodes that fail output nothing, the reasons are returned with the result.
+pub(crate) fn process_graph(node_graph_spatial: &NodeGraphSpatial) -> Processed {
+    let (node_graph_spatial, inlined) = inline_deferred(node_graph_spatial);
+    let mut node_graph = node_graph_spatial.node_graph.clone();
+    let mut pending: Vec<Deferred> = node_graph_spatial
+        .node_settings
+        .iter()
+        .filter_map(|(node_id, node_settings)| match node_settings {
+            NodeSettings::Filter(filter) => Some(Deferred::Filter(*node_id, filter)),
+            NodeSettings::Mix(mix_node) => Some(Deferred::Mix(*node_id, mix_node)),
+            NodeSettings::Channels(channel_node) => {
+                Some(Deferred::Channels(*node_id, channel_node))
+            }
+            _ => None,
+        })
+        .chain(
+            height_to_normal_nodes(&node_graph_spatial)
+                .into_iter()
+                .map(|node_id| {
+                    let height_to_normal = height_to_normal(&node_graph_spatial, node_id);
+                    Deferred::HeightToNormal(node_id, height_to_normal)
+                }),
+        )
+        .collect();
+    let mut errors = Vec::new();
+    let mut results = Vec::new();
+
+    let dir = match dirs::cache_dir() {
+        Some(dir) => dir.join("kanter").join("filtered").join(format!(
+            "{}_{}",
+            process::id(),
+            FILTER_RUN.fetch_add(1, Ordering::Relaxed)
+        )),
+        None => {
+            if !pending.is_empty() {
+                errors.push(ProcessError::NoCacheDir);
+            }
+            for deferred in pending {
+                disconnect_inputs(&mut node_graph, deferred.node_id());
+            }
+            return Processed {
+                tex_pro: process_node_graph(node_graph),
+                errors,
+                inlined,
+                results,
+            };
+        }
+    };
+
+    loop {
+        let tex_pro = process_node_graph(node_graph.clone());
+
+        // A node is ready when no other deferred node is waiting somewhere upstream of it.
+        let (ready, waiting): (Vec<Deferred>, Vec<Deferred>) =
+            pending.iter().copied().partition(|deferred| {
+                !pending.iter().any(|other| {
+                    other.node_id() != deferred.node_id()
+                        && is_downstream(&node_graph, other.node_id(), deferred.node_id())
+                })
+            });
+
+        if ready.is_empty() {
+            let _ = fs::remove_dir_all(&dir);
+            return Processed {
+                tex_pro,
+                errors,
+                inlined,
+                results,
+            };
+        }
+
+        for deferred in ready {
+            // Nodes that are not connected are left alone, they have nothing to output anyway.
+            match deferred_to_file(&tex_pro, &node_graph, &results, deferred, &dir) {
+                Ok(Some((path, result))) => {
+                    disconnect_inputs(&mut node_graph, deferred.node_id());
+                    if let Some(node) = node_graph.node_with_id_mut(deferred.node_id()) {
+                        node.node_type = NodeType::Image(path.to_string_lossy().to_string());
+                    }
+                    results.push((deferred.node_id(), result));
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    disconnect_inputs(&mut node_graph, deferred.node_id());
+                    errors.push(e);
+                }
+            }
+        }
+        pending = waiting;
+    }
+}
+
+/// Removes the edges going into a node, so a deferred node that is not replaced by its result
+/// outputs nothing instead of passing its input on.
+fn disconnect_inputs(node_graph: &mut NodeGraph, node_id: NodeId) {
+    node_graph.edges.retain(|edge| edge.input_id != node_id);
+}
+
+/// Returns the size and values of whatever is connected to an input slot of a node. Deferred
+/// nodes that already computed their result give it as it was computed, not as it was read back
+/// from its image.
+fn input_channel(
+    tex_pro: &TextureProcessor,
+    node_graph: &NodeGraph,
+    results: &[(NodeId, NodeChannels)],
+    node_id: NodeId,
+    slot_id: SlotId,
+) -> Option<(u32, u32, Vec<f32>)> {
+    let edge = node_graph
+        .edges
+        .iter()
+        .find(|edge| edge.input_id == node_id && edge.input_slot == slot_id)?;
+    if let Some(values) = result_slot(results, (edge.output_id, edge.output_slot)) {
+        return Some(values);
+    }
+    let node_data = tex_pro
+        .node_datas(edge.output_id)
+        .into_iter()
+        .find(|node_data| node_data.slot_id == edge.output_slot)?;
+
+    Some((
+        node_data.size.width,
+        node_data.size.height,
+        node_data.value.to_vec(),
+    ))
+}
+
+/// Scales a channel to another size, reading between pixels by blending the closest ones.
+fn resized(channel: (u32, u32, Vec<f32>), width: u32, height: u32) -> Vec<f32> {
+    let (source_width, source_height, values) = channel;
+    if (source_width, source_height) == (width, height) {
+        return values;
+    }
+    if source_width == 0 || source_height == 0 {
+        return vec![0.; (width * height) as usize];
+    }
+
+    let get = |x: u32, y: u32| {
+        values[(y.min(source_height - 1) * source_width + x.min(source_width - 1)) as usize]
+    };
+    let source_position = |position: u32, size: u32, source_size: u32| {
+        ((position as f32 + 0.5) * source_size as f32 / size as f32 - 0.5).max(0.)
+    };
+
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let source_x = source_position(x, width, source_width);
+            let source_y = source_position(y, height, source_height);
+            let (left, top) = (source_x.floor() as u32, source_y.floor() as u32);
+            let (tx, ty) = (source_x.fract(), source_y.fract());
+
+            let upper = get(left, top) * (1. - tx) + get(left + 1, top) * tx;
+            let lower = get(left, top + 1) * (1. - tx) + get(left + 1, top + 1) * tx;
+            upper * (1. - ty) + lower * ty
+        })
+        .collect()
+}
+
+/// Computes the result of a deferred node from its inputs and writes it to an image in `dir`.
+/// Channel and height to normal nodes give an RGBA image, the others a gray one. Returns `None`
+/// if the node has no input to compute a result from.
+fn deferred_to_file(
+    tex_pro: &TextureProcessor,
+    node_graph: &NodeGraph,
+    results: &[(NodeId, NodeChannels)],
+    deferred: Deferred,
+    dir: &Path,
+) -> Result<Option<(PathBuf, NodeChannels)>, ProcessError> {
+    let node_id = deferred.node_id();
+    let input = |slot: u32| input_channel(tex_pro, node_graph, results, node_id, SlotId(slot));
+
+    let (width, height, channels) = match deferred {
+        Deferred::Filter(_, filter) => {
+            let (width, height, base) = match input(0) {
+                Some(input) => input,
+                None => return Ok(None),
+            };
+            let filtered = filter.apply(width, height, &base);
+
+            (width, height, vec![filtered])
+        }
+        Deferred::HeightToNormal(_, height_to_normal) => {
+            let (width, height, heights) = match input(0) {
+                Some(input) => input,
+                None => return Ok(None),
+            };
+
+            let normals = height_to_normal.apply(width, height, &heights);
+
+            (width, height, normals)
+        }
+        Deferred::Mix(_, mix_node) => {
+            let (width, height, base) = match input(0) {
+                Some(input) => input,
+                None => return Ok(None),
+            };
+            // Inputs of another size than the base are scaled to fit it.
+            let same_size = |slot: u32| input(slot).map(|input| resized(input, width, height));
+            let blend = same_size(1);
+            let mask = same_size(2);
+
+            (
+                width,
+                height,
+                vec![mix_node.apply(&base, blend.as_deref(), mask.as_deref())],
+            )
+        }
+        Deferred::Channels(_, channel_node) => {
+            let inputs: Vec<_> = (0..CHANNEL_COUNT as u32).map(input).collect();
+            // The first connected input decides the size, inputs of another size are left out.
+            let (width, height) = match inputs.iter().flatten().next() {
+                Some((width, height, _)) => (*width, *height),
+                None => return Ok(None),
+            };
+            let inputs: Vec<Option<Vec<f32>>> = inputs
+                .into_iter()
+                .map(|input| {
+                    input
+                        .filter(|(other_width, other_height, _)| {
+                            (*other_width, *other_height) == (width, height)
+                        })
+                        .map(|(_, _, values)| values)
+                })
+                .collect();
+
+            (
+                width,
+                height,
+                channel_node.apply((width * height) as usize, &inputs),
+            )
+        }
+    };
+
+    let to_u16 = |value: f32| (value.max(0.).min(1.) * u16::MAX as f32).round() as u16;
+    let path = dir.join(format!("{}.png", node_id.0));
+    fs::create_dir_all(dir).map_err(|e| ProcessError::Io(dir.to_owned(), e))?;
+
+    let saved = if channels.len() == CHANNEL_COUNT {
+        let pixels = (0..(width * height) as usize)
+            .flat_map(|i| channels.iter().map(move |channel| channel[i]))
+            .map(to_u16)
+            .collect();
+        let image: ImageBuffer<Rgba<u16>, Vec<u16>> =
+            ImageBuffer::from_raw(width, height, pixels).ok_or(ProcessError::Size(node_id.0))?;
+        image.save(&path)
+    } else {
+        let pixels = channels[0].iter().copied().map(to_u16).collect();
+        let image: ImageBuffer<Luma<u16>, Vec<u16>> =
+            ImageBuffer::from_raw(width, height, pixels).ok_or(ProcessError::Size(node_id.0))?;
+        image.save(&path)
+    };
+    saved.map_err(|e| ProcessError::Image(path.clone(), e))?;
+
+    Ok(Some((
+        path,
+        NodeChannels {
+            width,
+            height,
+            channels,
+        },
+    )))
+}
+
+fn process(node_graph_spatial: &NodeGraphSpatial) -> ProcessResult {
+    let node_ids = node_graph_spatial.node_graph.node_ids();
+    let processed = process_graph(node_graph_spatial);
+
+    let channels: Vec<(NodeId, NodeChannels)> = node_ids
+        .into_iter()
+        .filter_map(|node_id| {
+            processed
+                .node_channels(node_id)
+                .map(|channels| (node_id, channels))
+        })
+        .collect();
+    let thumbnails = channels
+        .iter()
+        .map(|(node_id, channels)| (*node_id, channels.image().downscaled(THUMBNAIL_SIZE)))
+        .collect();
+
+    ProcessResult {
+        thumbnails,
+        channels,
+        errors: processed.errors,
+    }
+}
+
+/// The raw output slots of a processed node, one buffer of values per channel.
+#[derive(Clone, Debug, Default)]
+pub struct NodeChannels {
+    pub width: u32,
+    pub height: u32,
+    pub channels: Vec<Vec<f32>>,
+}
+
+impl NodeChannels {
+    /// Returns the red, green, blue and alpha value of a pixel. A single channel is treated as
+    /// grayscale, three or more channels as RGB(A).
+    pub fn rgba(&self, i: usize) -> (f32, f32, f32, f32) {
+        let channels = &self.channels;
+
+        if channels.len() >= 3 {
+            (
+                channels[0][i],
+                channels[1][i],
+                channels[2][i],
+                channels.get(3).map_or(1., |channel| channel[i]),
+            )
+        } else {
+            (channels[0][i], channels[0][i], channels[0][i], 1.)
+        }
+    }
+
+    /// Builds an image from the channels, see `rgba` for how they are combined.
+    pub fn image(&self) -> ImageData {
+        self.image_with(|i| self.rgba(i))
+    }
+
+    /// Builds an image from the given red, green, blue and alpha value of each pixel.
+    pub fn image_with(&self, rgba: impl Fn(usize) -> (f32, f32, f32, f32)) -> ImageData {
+        let to_byte = |value: f32| (value.max(0.).min(1.) * 255.).round() as u32;
+        let data = (0..(self.width * self.height) as usize)
+            .map(|i| {
+                let (r, g, b, a) = rgba(i);
+
+                to_byte(a) << 24 | to_byte(r) << 16 | to_byte(g) << 8 | to_byte(b)
+            })
+            .collect();
+
+        ImageData {
+            width: self.width,
+            height: self.height,
+            data,
+        }
+    }
+}
+
+/// Collects the output slots of a processed node that have the same size as the first one.
+pub fn node_channels(tex_pro: &TextureProcessor, node_id: NodeId) -> Option<NodeChannels> {
+    let mut node_datas = tex_pro.node_datas(node_id);
+    if node_datas.is_empty() {
+        return None;
+    }
+    node_datas.sort_by_key(|node_data| node_data.slot_id.0);
+
+    let width = node_datas[0].size.width;
+    let height = node_datas[0].size.height;
+    let channels = node_datas
+        .iter()
+        .filter(|node_data| node_data.size.width == width && node_data.size.height == height)
+        .map(|node_data| node_data.value.to_vec())
+        .collect();
+
+    Some(NodeChannels {
+        width,
+        height,
+        channels,
+    })
+}
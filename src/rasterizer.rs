@@ -0,0 +1,459 @@
+use crate::processing::{ImageData, NodeChannels};
+use std::{
+    f32::consts::PI,
+    ops::{Add, Mul, Neg, Sub},
+    sync::Arc,
+};
+
+const FIELD_OF_VIEW: f32 = PI / 4.;
+const SPHERE_RINGS: usize = 32;
+const SPHERE_SEGMENTS: usize = 64;
+const PLANE_SIZE: f32 = 1.2;
+/// How steep the normals made from a height map are.
+const HEIGHT_STRENGTH: f32 = 4.;
+const LIGHT_INTENSITY: f32 = PI;
+const AMBIENT: f32 = 0.08;
+const BACKGROUND: u32 = 0xff_2a_2a_2a;
+const DEFAULT_ALBEDO: (f32, f32, f32) = (0.8, 0.8, 0.8);
+const DEFAULT_ROUGHNESS: f32 = 0.5;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Vec3 {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+impl Vec3 {
+    fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    fn cross(self, other: Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    fn normalized(self) -> Self {
+        let length = self.dot(self).sqrt();
+
+        if length > 0. {
+            self * (1. / length)
+        } else {
+            self
+        }
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Self;
+
+    fn mul(self, scale: f32) -> Self {
+        Self::new(self.x * scale, self.y * scale, self.z * scale)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self * -1.
+    }
+}
+
+/// The shapes the material can be previewed on.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    Sphere,
+    Cube,
+    Plane,
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Self::Sphere
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Vertex {
+    position: Vec3,
+    normal: Vec3,
+    /// Points along increasing u. The cross product of the normal and the tangent points along
+    /// decreasing v, which is up in the image.
+    tangent: Vec3,
+    uv: (f32, f32),
+}
+
+/// Returns the triangles making up the shape, centered on the origin and about two units across.
+fn mesh(shape: Shape) -> Vec<[Vertex; 3]> {
+    match shape {
+        Shape::Sphere => sphere(),
+        Shape::Cube => {
+            let faces = [
+                (Vec3::new(0., 0., 1.), Vec3::new(1., 0., 0.)),
+                (Vec3::new(0., 0., -1.), Vec3::new(-1., 0., 0.)),
+                (Vec3::new(1., 0., 0.), Vec3::new(0., 0., -1.)),
+                (Vec3::new(-1., 0., 0.), Vec3::new(0., 0., 1.)),
+                (Vec3::new(0., 1., 0.), Vec3::new(1., 0., 0.)),
+                (Vec3::new(0., -1., 0.), Vec3::new(1., 0., 0.)),
+            ];
+
+            faces
+                .iter()
+                .flat_map(|(normal, tangent)| quad(*normal * 0.8, *normal, *tangent, 0.8))
+                .collect()
+        }
+        Shape::Plane => quad(
+            Vec3::default(),
+            Vec3::new(0., 1., 0.),
+            Vec3::new(1., 0., 0.),
+            PLANE_SIZE,
+        )
+        .to_vec(),
+    }
+}
+
+/// Two triangles covering a square around `center`, with `half_size` from the center to the
+/// edges.
+fn quad(center: Vec3, normal: Vec3, tangent: Vec3, half_size: f32) -> [[Vertex; 3]; 2] {
+    let bitangent = normal.cross(tangent);
+    let vertex = |u: f32, v: f32| Vertex {
+        position: center + tangent * ((u * 2. - 1.) * half_size)
+            - bitangent * ((v * 2. - 1.) * half_size),
+        normal,
+        tangent,
+        uv: (u, v),
+    };
+
+    [
+        [vertex(0., 0.), vertex(0., 1.), vertex(1., 1.)],
+        [vertex(0., 0.), vertex(1., 1.), vertex(1., 0.)],
+    ]
+}
+
+fn sphere() -> Vec<[Vertex; 3]> {
+    let vertex = |ring: usize, segment: usize| {
+        let theta = ring as f32 / SPHERE_RINGS as f32 * PI;
+        let phi = segment as f32 / SPHERE_SEGMENTS as f32 * PI * 2.;
+        let normal = Vec3::new(
+            theta.sin() * phi.sin(),
+            theta.cos(),
+            theta.sin() * phi.cos(),
+        );
+
+        Vertex {
+            position: normal,
+            normal,
+            tangent: Vec3::new(phi.cos(), 0., -phi.sin()),
+            uv: (
+                segment as f32 / SPHERE_SEGMENTS as f32,
+                ring as f32 / SPHERE_RINGS as f32,
+            ),
+        }
+    };
+
+    let mut triangles = Vec::with_capacity(SPHERE_RINGS * SPHERE_SEGMENTS * 2);
+    for ring in 0..SPHERE_RINGS {
+        for segment in 0..SPHERE_SEGMENTS {
+            let top_left = vertex(ring, segment);
+            let top_right = vertex(ring, segment + 1);
+            let bottom_left = vertex(ring + 1, segment);
+            let bottom_right = vertex(ring + 1, segment + 1);
+
+            triangles.push([top_left, bottom_left, bottom_right]);
+            triangles.push([top_left, bottom_right, top_right]);
+        }
+    }
+
+    triangles
+}
+
+/// A camera circling the origin and looking at it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrbitCamera {
+    /// The angle around the vertical axis, in radians.
+    pub yaw: f32,
+    /// The angle above the horizon, in radians.
+    pub pitch: f32,
+    pub distance: f32,
+}
+
+impl Default for OrbitCamera {
+    fn default() -> Self {
+        Self {
+            yaw: PI / 6.,
+            pitch: PI / 8.,
+            distance: 4.,
+        }
+    }
+}
+
+impl OrbitCamera {
+    fn eye(&self) -> Vec3 {
+        Vec3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        ) * self.distance
+    }
+}
+
+/// The maps making up a material, any of them can be missing.
+#[derive(Clone, Debug, Default)]
+pub struct Material {
+    pub albedo: Option<Arc<NodeChannels>>,
+    pub height: Option<Arc<NodeChannels>>,
+    pub normal: Option<Arc<NodeChannels>>,
+    pub roughness: Option<Arc<NodeChannels>>,
+    pub metallic: Option<Arc<NodeChannels>>,
+}
+
+/// Returns the pixel at `u`, `v` with bilinear filtering, repeating the texture outside of 0-1.
+fn sample(channels: &NodeChannels, u: f32, v: f32) -> (f32, f32, f32, f32) {
+    let width = channels.width as i64;
+    let height = channels.height as i64;
+    let x = u * width as f32 - 0.5;
+    let y = v * height as f32 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let texel = |x: i64, y: i64| {
+        channels.rgba((y.rem_euclid(height) * width + x.rem_euclid(width)) as usize)
+    };
+    let lerp = |a: (f32, f32, f32, f32), b: (f32, f32, f32, f32), t: f32| {
+        (
+            a.0 + (b.0 - a.0) * t,
+            a.1 + (b.1 - a.1) * t,
+            a.2 + (b.2 - a.2) * t,
+            a.3 + (b.3 - a.3) * t,
+        )
+    };
+
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let top = lerp(texel(x0, y0), texel(x0 + 1, y0), fx);
+    let bottom = lerp(texel(x0, y0 + 1), texel(x0 + 1, y0 + 1), fx);
+    lerp(top, bottom, fy)
+}
+
+impl Material {
+    /// Whether both hold the same maps. The maps are compared by identity since every
+    /// processing run creates new ones.
+    pub fn is_same(&self, other: &Self) -> bool {
+        let same = |a: &Option<Arc<NodeChannels>>, b: &Option<Arc<NodeChannels>>| match (a, b) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        };
+
+        same(&self.albedo, &other.albedo)
+            && same(&self.height, &other.height)
+            && same(&self.normal, &other.normal)
+            && same(&self.roughness, &other.roughness)
+            && same(&self.metallic, &other.metallic)
+    }
+
+    /// Returns the surface normal in tangent space, where z points away from the surface and y
+    /// points up in the image. A normal map wins over a height map.
+    fn tangent_normal(&self, u: f32, v: f32) -> Vec3 {
+        if let Some(normal) = &self.normal {
+            let (x, y, z, _) = sample(normal, u, v);
+            return Vec3::new(x * 2. - 1., y * 2. - 1., z * 2. - 1.).normalized();
+        }
+
+        if let Some(height) = &self.height {
+            let du = 1. / height.width as f32;
+            let dv = 1. / height.height as f32;
+            let dx = sample(height, u + du, v).0 - sample(height, u - du, v).0;
+            let dy = sample(height, u, v - dv).0 - sample(height, u, v + dv).0;
+            return Vec3::new(-dx * HEIGHT_STRENGTH, -dy * HEIGHT_STRENGTH, 1.).normalized();
+        }
+
+        Vec3::new(0., 0., 1.)
+    }
+
+    /// Lights the surface with a light coming from above and to the left of the camera, using a
+    /// normalized Blinn-Phong highlight whose sharpness follows the roughness.
+    fn shade(&self, vertex: &Vertex, to_eye: Vec3, to_light: Vec3) -> u32 {
+        let (u, v) = vertex.uv;
+        let to_linear = |value: f32| value.max(0.).powf(2.2);
+        let albedo = self.albedo.as_ref().map_or(DEFAULT_ALBEDO, |albedo| {
+            let (r, g, b, _) = sample(albedo, u, v);
+            (r, g, b)
+        });
+        let albedo = Vec3::new(
+            to_linear(albedo.0),
+            to_linear(albedo.1),
+            to_linear(albedo.2),
+        );
+        let roughness = self
+            .roughness
+            .as_ref()
+            .map_or(DEFAULT_ROUGHNESS, |roughness| sample(roughness, u, v).0)
+            .max(0.05)
+            .min(1.);
+        let metallic = self
+            .metallic
+            .as_ref()
+            .map_or(0., |metallic| sample(metallic, u, v).0)
+            .max(0.)
+            .min(1.);
+
+        let normal = vertex.normal.normalized();
+        let tangent = (vertex.tangent - normal * normal.dot(vertex.tangent)).normalized();
+        let bitangent = normal.cross(tangent);
+        let tangent_normal = self.tangent_normal(u, v);
+        let normal =
+            (tangent * tangent_normal.x + bitangent * tangent_normal.y + normal * tangent_normal.z)
+                .normalized();
+
+        let n_dot_l = normal.dot(to_light).max(0.);
+        let half = (to_eye + to_light).normalized();
+        let alpha = roughness * roughness;
+        let exponent = 2. / (alpha * alpha) - 2.;
+        let highlight = (exponent + 8.) / (8. * PI) * normal.dot(half).max(0.).powf(exponent);
+
+        let dielectric = Vec3::new(0.04, 0.04, 0.04);
+        let specular_color = dielectric + (albedo - dielectric) * metallic;
+        let diffuse_color = albedo * (1. - metallic);
+
+        let color = (diffuse_color * (1. / PI) + specular_color * highlight)
+            * (LIGHT_INTENSITY * n_dot_l)
+            + (diffuse_color + specular_color) * AMBIENT;
+
+        let to_byte = |value: f32| (value.max(0.).min(1.).powf(1. / 2.2) * 255.).round() as u32;
+        0xff << 24 | to_byte(color.x) << 16 | to_byte(color.y) << 8 | to_byte(color.z)
+    }
+}
+
+/// A vertex after projection, with the attributes divided by depth so they can be interpolated
+/// with perspective.
+struct Projected {
+    x: f32,
+    y: f32,
+    inverse_depth: f32,
+    vertex: Vertex,
+}
+
+/// Renders the shape with the material on the CPU, so the preview works without a GPU.
+pub fn render(
+    shape: Shape,
+    material: &Material,
+    camera: &OrbitCamera,
+    width: u32,
+    height: u32,
+) -> ImageData {
+    let mut data = vec![BACKGROUND; (width * height) as usize];
+    let mut depth_buffer = vec![f32::MAX; (width * height) as usize];
+
+    let eye = camera.eye();
+    let forward = (-eye).normalized();
+    let right = forward.cross(Vec3::new(0., 1., 0.)).normalized();
+    let up = right.cross(forward);
+    let to_light = (right * -0.4 + up * 0.6 - forward * 0.7).normalized();
+    let focal_length = 1. / (FIELD_OF_VIEW * 0.5).tan();
+    let aspect = width as f32 / height as f32;
+
+    let project = |vertex: Vertex| {
+        let relative = vertex.position - eye;
+        let depth = relative.dot(forward);
+
+        Projected {
+            x: (relative.dot(right) * focal_length / (depth * aspect) * 0.5 + 0.5) * width as f32,
+            y: (0.5 - relative.dot(up) * focal_length / depth * 0.5) * height as f32,
+            inverse_depth: 1. / depth,
+            vertex,
+        }
+    };
+    let edge = |a: &Projected, b: &Projected, x: f32, y: f32| {
+        (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+    };
+
+    for triangle in mesh(shape) {
+        // The camera never gets close enough to the shapes for triangles to cross the near plane.
+        let (a, b, c) = (
+            project(triangle[0]),
+            project(triangle[1]),
+            project(triangle[2]),
+        );
+        let area = edge(&a, &b, c.x, c.y);
+        if area.abs() < f32::EPSILON {
+            continue;
+        }
+
+        let min_x = a.x.min(b.x).min(c.x).floor().max(0.) as u32;
+        let max_x = a.x.max(b.x).max(c.x).ceil().min(width as f32 - 1.) as u32;
+        let min_y = a.y.min(b.y).min(c.y).floor().max(0.) as u32;
+        let max_y = a.y.max(b.y).max(c.y).ceil().min(height as f32 - 1.) as u32;
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let wa = edge(&b, &c, px, py) / area;
+                let wb = edge(&c, &a, px, py) / area;
+                let wc = edge(&a, &b, px, py) / area;
+                if wa < 0. || wb < 0. || wc < 0. {
+                    continue;
+                }
+
+                let inverse_depth =
+                    wa * a.inverse_depth + wb * b.inverse_depth + wc * c.inverse_depth;
+                let depth = 1. / inverse_depth;
+                let i = (y * width + x) as usize;
+                if depth >= depth_buffer[i] {
+                    continue;
+                }
+                depth_buffer[i] = depth;
+
+                // Weights that interpolate the attributes with perspective.
+                let (pa, pb, pc) = (
+                    wa * a.inverse_depth * depth,
+                    wb * b.inverse_depth * depth,
+                    wc * c.inverse_depth * depth,
+                );
+                let interpolate = |a: Vec3, b: Vec3, c: Vec3| a * pa + b * pb + c * pc;
+                let vertex = Vertex {
+                    position: interpolate(a.vertex.position, b.vertex.position, c.vertex.position),
+                    normal: interpolate(a.vertex.normal, b.vertex.normal, c.vertex.normal),
+                    tangent: interpolate(a.vertex.tangent, b.vertex.tangent, c.vertex.tangent),
+                    uv: (
+                        a.vertex.uv.0 * pa + b.vertex.uv.0 * pb + c.vertex.uv.0 * pc,
+                        a.vertex.uv.1 * pa + b.vertex.uv.1 * pb + c.vertex.uv.1 * pc,
+                    ),
+                };
+
+                let to_eye = (eye - vertex.position).normalized();
+                data[i] = material.shade(&vertex, to_eye, to_light);
+            }
+        }
+    }
+
+    ImageData {
+        width,
+        height,
+        data,
+    }
+}
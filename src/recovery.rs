@@ -0,0 +1,160 @@
+use crate::{
+    config::config_dir,
+    node_graph_spatial::{GraphFileError, NodeGraphSpatial},
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often unsaved changes are written to the recovery file at most.
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Where unsaved changes are autosaved. The file only exists while there are unsaved changes,
+/// so if it is there on startup Kanter was closed without saving.
+fn recovery_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("recovery.json"))
+}
+
+/// Where the unsaved changes of an earlier session are kept until the user has decided whether
+/// to recover them.
+fn previous_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("recovery_previous.json"))
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Moves unsaved changes from an earlier session aside, so autosaves in this session don't
+/// overwrite them. Returns whether there are any changes to recover.
+pub fn take_previous() -> bool {
+    let (recovery_path, previous_path) = match (recovery_path(), previous_path()) {
+        (Some(recovery_path), Some(previous_path)) => (recovery_path, previous_path),
+        _ => return false,
+    };
+
+    if recovery_path.is_file() {
+        let _ = fs::rename(recovery_path, &previous_path);
+    }
+
+    previous_path.is_file()
+}
+
+/// Returns `None` if there is no config directory to recover from.
+pub fn load_previous() -> Option<Result<NodeGraphSpatial, GraphFileError>> {
+    previous_path().map(NodeGraphSpatial::load)
+}
+
+pub fn discard_previous() -> io::Result<()> {
+    previous_path().map_or(Ok(()), |path| remove(&path))
+}
+
+/// Autosaves the graph. Does nothing if there is no config directory to save it in.
+pub fn write(node_graph_spatial: &NodeGraphSpatial) -> Result<(), GraphFileError> {
+    let path = match recovery_path() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| GraphFileError::Io(dir.to_owned(), e))?;
+    }
+    node_graph_spatial.save(path)
+}
+
+/// Removes the recovery file, there is nothing to recover once changes are saved or discarded.
+pub fn discard() -> io::Result<()> {
+    recovery_path().map_or(Ok(()), |path| remove(&path))
+}
+
+enum AutosaveMessage {
+    Changed(NodeGraphSpatial),
+    /// The changes were saved or discarded, so there is nothing left to write.
+    Discard,
+}
+
+/// Writes unsaved changes to the recovery file on a background thread, at most once per
+/// `AUTOSAVE_INTERVAL`. The thread keeps its own time, so changes made right before the window
+/// goes idle are still written.
+pub struct Autosaver {
+    message_sender: Sender<AutosaveMessage>,
+    error_receiver: Receiver<GraphFileError>,
+}
+
+impl Autosaver {
+    pub fn new() -> Self {
+        let (message_sender, message_receiver) = mpsc::channel();
+        let (error_sender, error_receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut pending: Option<NodeGraphSpatial> = None;
+            let mut last_write: Option<Instant> = None;
+
+            loop {
+                let message = if pending.is_some() {
+                    let wait = last_write.map_or(Duration::from_secs(0), |last_write| {
+                        AUTOSAVE_INTERVAL
+                            .checked_sub(last_write.elapsed())
+                            .unwrap_or_default()
+                    });
+                    match message_receiver.recv_timeout(wait) {
+                        Ok(message) => Some(message),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                } else {
+                    match message_receiver.recv() {
+                        Ok(message) => Some(message),
+                        Err(_) => break,
+                    }
+                };
+
+                match message {
+                    Some(AutosaveMessage::Changed(node_graph_spatial)) => {
+                        pending = Some(node_graph_spatial)
+                    }
+                    Some(AutosaveMessage::Discard) => pending = None,
+                    None => {
+                        if let Some(node_graph_spatial) = pending.take() {
+                            if let Err(e) = write(&node_graph_spatial) {
+                                if error_sender.send(e).is_err() {
+                                    break;
+                                }
+                            }
+                            last_write = Some(Instant::now());
+                        }
+                    }
+                }
+            }
+        });
+
+        Self {
+            message_sender,
+            error_receiver,
+        }
+    }
+
+    /// Queues the graph to be written once the interval since the last write has passed.
+    pub(crate) fn changed(&self, node_graph_spatial: NodeGraphSpatial) {
+        let _ = self
+            .message_sender
+            .send(AutosaveMessage::Changed(node_graph_spatial));
+    }
+
+    /// Drops changes that are still waiting to be written.
+    pub fn discard(&self) {
+        let _ = self.message_sender.send(AutosaveMessage::Discard);
+    }
+
+    /// Returns the errors from writing the recovery file since the last call.
+    pub fn errors(&self) -> Vec<GraphFileError> {
+        self.error_receiver.try_iter().collect()
+    }
+}
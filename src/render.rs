@@ -0,0 +1,77 @@
+use crate::{
+    export::{export_outputs, ExportError},
+    generator::{bake_all, GeneratorError},
+    node_graph_spatial::{GraphFileError, NodeGraphSpatial},
+};
+use std::{fmt, path::PathBuf};
+
+const USAGE: &str = "usage: kanter render <graph.json> --out-dir <dir>";
+
+#[derive(Debug)]
+pub enum RenderError {
+    Usage,
+    GraphFile(GraphFileError),
+    Export(ExportError),
+    Generator(GeneratorError),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage => write!(f, "{}", USAGE),
+            Self::GraphFile(error) => write!(f, "{}", error),
+            Self::Export(error) => write!(f, "{}", error),
+            Self::Generator(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+struct RenderArgs {
+    graph_path: PathBuf,
+    out_dir: PathBuf,
+}
+
+impl RenderArgs {
+    fn parse(args: &[String]) -> Result<Self, RenderError> {
+        let mut graph_path = None;
+        let mut out_dir = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--out-dir" => out_dir = args.next().map(PathBuf::from),
+                _ if graph_path.is_none() => graph_path = Some(PathBuf::from(arg)),
+                _ => return Err(RenderError::Usage),
+            }
+        }
+
+        match (graph_path, out_dir) {
+            (Some(graph_path), Some(out_dir)) => Ok(Self {
+                graph_path,
+                out_dir,
+            }),
+            _ => Err(RenderError::Usage),
+        }
+    }
+}
+
+/// Loads the graph file given in `args`, processes it and writes every output node to the
+/// output directory using the export settings of the node. Returns the paths of the written
+/// files.
+pub fn run(args: &[String]) -> Result<Vec<PathBuf>, RenderError> {
+    let args = RenderArgs::parse(args)?;
+
+    let mut node_graph_spatial =
+        NodeGraphSpatial::load(&args.graph_path).map_err(RenderError::GraphFile)?;
+    if let Some(error) = bake_all(&mut node_graph_spatial).into_iter().next() {
+        return Err(RenderError::Generator(error));
+    }
+    let graph_name = args
+        .graph_path
+        .file_stem()
+        .map_or("graph".into(), |file_stem| file_stem.to_string_lossy());
+
+    export_outputs(&node_graph_spatial, &args.out_dir, &graph_name).map_err(RenderError::Export)
+}
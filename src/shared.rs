@@ -1,5 +1,10 @@
-use kanter_core::node::{MixType, NodeType, Side};
-use orbtk::prelude::*;
+use crate::{
+    blend::BlendMode,
+    file_browser::FileBrowserMode,
+    layout::{Alignment, Axis},
+};
+use kanter_core::node::{NodeType, ResizeFilter, ResizePolicy, Side};
+use orbtk::{prelude::*, shell::Key};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum WidgetType {
@@ -60,7 +65,23 @@ pub enum Action {
     Press(Mouse),
     Release(Mouse),
     Move(Point),
+    Scroll(Point),
     Delete,
+    FrameAll,
+    FrameSelected,
+    Undo,
+    Redo,
+    Copy,
+    Cut,
+    Paste,
+    Duplicate,
+    CollapseSelection,
+    EnterSubgraph,
+    LeaveSubgraph,
+    ToggleGrid,
+    Align(Alignment),
+    Distribute(Axis),
+    AutoLayout,
 }
 pub type OptionAction = Option<Action>;
 
@@ -78,13 +99,59 @@ pub fn child_entities(ctx: &mut Context) -> Vec<Entity> {
     output
 }
 
+pub fn is_key_down(ctx: &mut Context, key: Key) -> bool {
+    ctx.window()
+        .get::<Global>("global")
+        .keyboard_state
+        .is_key_down(key)
+}
+
+pub fn is_ctrl_down(ctx: &mut Context) -> bool {
+    ctx.window()
+        .get::<Global>("global")
+        .keyboard_state
+        .is_ctrl_down()
+}
+
+pub fn is_shift_down(ctx: &mut Context) -> bool {
+    ctx.window()
+        .get::<Global>("global")
+        .keyboard_state
+        .is_shift_down()
+}
+
 #[derive(Debug, Clone)]
 pub enum ActionMain {
     LoadGraph(String),
     SaveGraph(String),
+    Save,
+    Browse(FileBrowserMode),
+    FileChosen(FileBrowserMode, String),
+    SetImagePath(String),
+    ToggleRecentFiles,
+    ShowShortcuts,
+    ToggleViewer,
+    TogglePreview,
+    Recover,
+    PromptAnswer(bool),
+    Quit,
+    ExportOutputs(String),
     MenuNode(Point),
+    FrameAll,
+    FrameSelected,
+    DismissError,
 }
 pub type OptionActionMain = Option<ActionMain>;
+
+impl ActionMain {
+    /// Whether the action throws away the graph that is open.
+    pub fn discards_changes(&self) -> bool {
+        matches!(
+            self,
+            Self::LoadGraph(_) | Self::Browse(FileBrowserMode::Open) | Self::Quit
+        )
+    }
+}
 pub type OptionNodeType = Option<NodeType>;
 
 pub trait Indexable {
@@ -92,23 +159,67 @@ pub trait Indexable {
     fn from_index(index: usize) -> Option<Self> where Self: std::marker::Sized ;
 }
 
-impl Indexable for MixType {
+impl Indexable for BlendMode {
+    fn index(&self) -> usize {
+        BlendMode::ALL
+            .iter()
+            .position(|blend_mode| blend_mode == self)
+            .unwrap_or_default()
+    }
+
+    fn from_index(index: usize) -> Option<Self> {
+        BlendMode::ALL.get(index).copied()
+    }
+}
+
+/// `None` is the first item, the variants that need more data than an index can hold come last
+/// and can't be created from an index.
+impl Indexable for Option<ResizePolicy> {
+    fn index(&self) -> usize {
+        match self {
+            None => 0,
+            Some(ResizePolicy::MostPixels) => 1,
+            Some(ResizePolicy::LeastPixels) => 2,
+            Some(ResizePolicy::LargestAxes) => 3,
+            Some(ResizePolicy::SmallestAxes) => 4,
+            Some(ResizePolicy::SpecificNode(_)) => 5,
+            Some(ResizePolicy::SpecificSize(_)) => 6,
+        }
+    }
+
+    fn from_index(index: usize) -> Option<Self> {
+        match index {
+            0 => Some(None),
+            1 => Some(Some(ResizePolicy::MostPixels)),
+            2 => Some(Some(ResizePolicy::LeastPixels)),
+            3 => Some(Some(ResizePolicy::LargestAxes)),
+            4 => Some(Some(ResizePolicy::SmallestAxes)),
+            _ => None,
+        }
+    }
+}
+
+impl Indexable for Option<ResizeFilter> {
     fn index(&self) -> usize {
         match self {
-            MixType::Add => 0,
-            MixType::Subtract => 1,
-            MixType::Multiply => 2,
-            MixType::Divide => 3,
+            None => 0,
+            Some(ResizeFilter::Nearest) => 1,
+            Some(ResizeFilter::Triangle) => 2,
+            Some(ResizeFilter::CatmullRom) => 3,
+            Some(ResizeFilter::Gaussian) => 4,
+            Some(ResizeFilter::Lanczos3) => 5,
         }
     }
 
     fn from_index(index: usize) -> Option<Self> {
         match index {
-            0 => Some(MixType::Add),
-            1 => Some(MixType::Subtract),
-            2 => Some(MixType::Multiply),
-            3 => Some(MixType::Divide),
-            _ => None
+            0 => Some(None),
+            1 => Some(Some(ResizeFilter::Nearest)),
+            2 => Some(Some(ResizeFilter::Triangle)),
+            3 => Some(Some(ResizeFilter::CatmullRom)),
+            4 => Some(Some(ResizeFilter::Gaussian)),
+            5 => Some(Some(ResizeFilter::Lanczos3)),
+            _ => None,
         }
     }
 }
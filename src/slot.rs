@@ -1,33 +1,77 @@
-use crate::shared::*;
+use crate::{connection::SlotType, shared::*};
 use orbtk::prelude::*;
 
+/// How a slot is drawn while an edge is being dragged.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SlotHighlight {
+    None,
+    /// The dragged edge can be dropped here.
+    Compatible,
+    /// The dragged edge is over this slot and can be dropped here.
+    Hovered,
+    /// The dragged edge is over this slot but can not be dropped here.
+    Rejected,
+}
+into_property_source!(SlotHighlight);
+
+impl Default for SlotHighlight {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
 widget!(
     Slot<SlotState> {
         widget_type: WidgetType,
         side: WidgetSide,
         node_workspace: Entity,
         node_id: u32,
-        slot_id: u32
+        slot_id: u32,
+        slot_type: SlotType,
+        highlight: SlotHighlight
     }
 );
 
 impl Template for Slot {
-    fn template(self, _id: Entity, ctx: &mut BuildContext) -> Self {
+    fn template(mut self, _id: Entity, ctx: &mut BuildContext) -> Self {
+        let circle = Container::create()
+            .background(Color::rgb(200, 200, 200))
+            .border_width(1.)
+            .border_radius(SLOT_SIZE_HALF)
+            .border_brush(Brush::SolidColor(Color::rgb(0, 0, 0)))
+            .build(ctx);
+        self.state_mut().circle = circle;
+
         self.name("Slot")
             .widget_type(WidgetType::Slot)
             .width(SLOT_SIZE)
             .height(SLOT_SIZE)
-            .child(
-                Container::create()
-                    .background(Color::rgb(200, 200, 200))
-                    .border_width(1.)
-                    .border_radius(SLOT_SIZE_HALF)
-                    .border_brush(Brush::SolidColor(Color::rgb(0, 0, 0)))
-                    .build(ctx),
-            )
+            .slot_type(SlotType::default())
+            .highlight(SlotHighlight::None)
+            .child(circle)
     }
 }
 
 #[derive(Default, AsAny)]
-pub struct SlotState {}
-impl State for SlotState {}
+pub struct SlotState {
+    circle: Entity,
+}
+
+impl State for SlotState {
+    fn update(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
+        let slot_type = *ctx.widget().get::<SlotType>("slot_type");
+        let highlight = *ctx.widget().get::<SlotHighlight>("highlight");
+
+        let (border_color, border_width) = match highlight {
+            SlotHighlight::None => (Color::rgb(0, 0, 0), 1.),
+            SlotHighlight::Compatible => (Color::rgb(255, 255, 255), 2.),
+            SlotHighlight::Hovered => (Color::rgb(255, 255, 255), 4.),
+            SlotHighlight::Rejected => (Color::rgb(255, 0, 0), 4.),
+        };
+
+        let mut circle = ctx.get_widget(self.circle);
+        circle.set::<Brush>("background", Brush::SolidColor(slot_type.color()));
+        circle.set::<Brush>("border_brush", Brush::SolidColor(border_color));
+        circle.set::<Thickness>("border_width", Thickness::from(border_width));
+    }
+}
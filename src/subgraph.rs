@@ -0,0 +1,514 @@
+use crate::{
+    clipboard::append_nodes,
+    connection::{node_slot_type, SlotType},
+    node_graph_spatial::{GraphFileError, Location, NodeGraphSpatial, NodeSettings, Subgraph},
+    shared::*,
+};
+use kanter_core::{
+    node::{Node as CoreNode, NodeType, Side},
+    node_graph::{Edge as CoreEdge, NodeGraph, NodeId, SlotId},
+};
+
+/// The distance between the input and output nodes created when collapsing nodes into a
+/// subgraph, and between them and the collapsed nodes.
+const BOUNDARY_SPACING: f64 = 150.;
+
+fn subgraph(parent: &NodeGraphSpatial, node_id: NodeId) -> Option<&Subgraph> {
+    match parent.node_settings.get(&node_id)? {
+        NodeSettings::Subgraph(subgraph) => Some(subgraph),
+        _ => None,
+    }
+}
+
+fn subgraph_mut(parent: &mut NodeGraphSpatial, node_id: NodeId) -> &mut Subgraph {
+    if subgraph(parent, node_id).is_none() {
+        parent
+            .node_settings
+            .insert(node_id, NodeSettings::Subgraph(Subgraph::default()));
+    }
+
+    match parent.node_settings.get_mut(&node_id) {
+        Some(NodeSettings::Subgraph(subgraph)) => subgraph,
+        _ => unreachable!("the subgraph settings were just inserted"),
+    }
+}
+
+/// Returns a Graph node with `input_count` inputs and `output_count` outputs, each input going
+/// straight to the output with the same index. Filter, lerping Mix and channel nodes have this
+/// `NodeType` in the `NodeGraph`.
+pub fn passthrough(input_count: usize, output_count: usize) -> NodeType {
+    let mut node_graph = NodeGraph::default();
+
+    let input_ids: Vec<NodeId> = (0..input_count)
+        .filter_map(|_| node_graph.add_node(CoreNode::new(NodeType::InputGray)).ok())
+        .collect();
+    let output_ids: Vec<NodeId> = (0..output_count)
+        .filter_map(|_| node_graph.add_node(CoreNode::new(NodeType::OutputGray)).ok())
+        .collect();
+    for (input_id, output_id) in input_ids.into_iter().zip(output_ids) {
+        let _ = node_graph.connect_arbitrary(
+            input_id,
+            Side::Output,
+            SlotId(0),
+            output_id,
+            Side::Input,
+            SlotId(0),
+        );
+    }
+
+    NodeType::Graph(node_graph)
+}
+
+/// Returns the graph file a Graph node is kept in sync with, if any.
+pub(crate) fn linked_path(parent: &NodeGraphSpatial, node_id: NodeId) -> Option<String> {
+    subgraph(parent, node_id)
+        .filter(|subgraph| !subgraph.path.is_empty())
+        .map(|subgraph| subgraph.path.clone())
+}
+
+/// Returns the contents of a Graph node as a graph of its own, so it can be edited. Filter,
+/// lerping Mix and channel nodes are Graph nodes in the `NodeGraph` too, but have nothing to
+/// edit inside.
+pub(crate) fn enter(parent: &NodeGraphSpatial, node_id: NodeId) -> Option<NodeGraphSpatial> {
+    match parent.node_settings.get(&node_id) {
+        Some(NodeSettings::Subgraph(_)) | None => (),
+        Some(_) => return None,
+    }
+
+    let node_graph = match &parent.node_graph.node_with_id(node_id)?.node_type {
+        NodeType::Graph(node_graph) => node_graph.clone(),
+        _ => return None,
+    };
+    let subgraph = subgraph(parent, node_id).cloned().unwrap_or_default();
+
+    Some(NodeGraphSpatial {
+        locations: subgraph.locations,
+        node_graph,
+        node_settings: subgraph.node_settings,
+    })
+}
+
+/// Puts an edited subgraph back into its Graph node.
+pub(crate) fn leave(parent: &mut NodeGraphSpatial, node_id: NodeId, inner: NodeGraphSpatial) {
+    if let Some(node) = parent.node_graph.node_with_id_mut(node_id) {
+        node.node_type = NodeType::Graph(inner.node_graph);
+    }
+
+    let subgraph = subgraph_mut(parent, node_id);
+    subgraph.locations = inner.locations;
+    subgraph.node_settings = inner.node_settings;
+}
+
+/// Loads a graph file into a Graph node and keeps the node in sync with that file from now on.
+pub(crate) fn link(
+    parent: &mut NodeGraphSpatial,
+    node_id: NodeId,
+    path: &str,
+) -> Result<(), GraphFileError> {
+    let inner = NodeGraphSpatial::load(path)?;
+    leave(parent, node_id, inner);
+    subgraph_mut(parent, node_id).path = path.to_string();
+
+    Ok(())
+}
+
+/// Reloads every Graph node that is linked to a file, so changes made to shared subgraphs show
+/// up in every graph using them. Returns the files that could not be loaded.
+pub(crate) fn sync_linked(graph: &mut NodeGraphSpatial) -> Vec<GraphFileError> {
+    let linked: Vec<(NodeId, String)> = graph
+        .node_graph
+        .node_ids()
+        .into_iter()
+        .filter_map(|node_id| linked_path(graph, node_id).map(|path| (node_id, path)))
+        .collect();
+
+    linked
+        .into_iter()
+        .filter_map(|(node_id, path)| link(graph, node_id, &path).err())
+        .collect()
+}
+
+/// Returns the input or output node type to use for data of the given type. Slots that take any
+/// type are treated as gray.
+fn boundary_node_type(slot_type: SlotType, side: WidgetSide) -> NodeType {
+    match (slot_type, side) {
+        (SlotType::Rgba, WidgetSide::Input) => NodeType::InputRgba,
+        (_, WidgetSide::Input) => NodeType::InputGray,
+        (SlotType::Rgba, WidgetSide::Output) => NodeType::OutputRgba,
+        (_, WidgetSide::Output) => NodeType::OutputGray,
+    }
+}
+
+fn output_slot_type(graph: &NodeGraphSpatial, node_id: NodeId, slot_id: SlotId) -> SlotType {
+    node_slot_type(graph, node_id, WidgetSide::Output, slot_id.0)
+}
+
+/// Replaces the given nodes with a Graph node containing them. Edges going into the nodes from
+/// the rest of the graph become input nodes in the subgraph, and edges going out of them become
+/// output nodes. Returns the id of the new Graph node.
+pub(crate) fn collapse(graph: &mut NodeGraphSpatial, node_ids: &[NodeId]) -> Option<NodeId> {
+    if node_ids.is_empty() {
+        return None;
+    }
+
+    let mut inner = NodeGraphSpatial::default();
+    let id_map = append_nodes(&mut inner, graph, node_ids, (0., 0.));
+    let inner_id = |node_id: NodeId| {
+        id_map
+            .iter()
+            .find(|(old_node_id, _)| *old_node_id == node_id)
+            .map(|(_, new_node_id)| *new_node_id)
+    };
+
+    let left = inner
+        .locations
+        .iter()
+        .map(|location| location.point.0)
+        .fold(f64::MAX, f64::min);
+    let right = inner
+        .locations
+        .iter()
+        .map(|location| location.point.0 + NODE_WIDTH)
+        .fold(f64::MIN, f64::max);
+    let top = inner
+        .locations
+        .iter()
+        .map(|location| location.point.1)
+        .fold(f64::MAX, f64::min);
+
+    let crossing = |inside: NodeId, outside: NodeId| {
+        node_ids.contains(&inside) && !node_ids.contains(&outside)
+    };
+    let incoming: Vec<CoreEdge> = graph
+        .node_graph
+        .edges
+        .iter()
+        .filter(|edge| crossing(edge.input_id, edge.output_id))
+        .copied()
+        .collect();
+    let outgoing: Vec<CoreEdge> = graph
+        .node_graph
+        .edges
+        .iter()
+        .filter(|edge| crossing(edge.output_id, edge.input_id))
+        .copied()
+        .collect();
+
+    // Each outside output feeding the nodes gets one input node, the same goes for each inside
+    // output used by the rest of the graph. Their order is the slot order on the Graph node.
+    let mut inputs: Vec<((NodeId, SlotId), NodeId)> = Vec::new();
+    for edge in &incoming {
+        let source = (edge.output_id, edge.output_slot);
+
+        let existing_input = inputs
+            .iter()
+            .find(|(input_source, _)| *input_source == source)
+            .map(|(_, input_node_id)| *input_node_id);
+
+        let input_node_id = match existing_input {
+            Some(input_node_id) => input_node_id,
+            None => {
+                let slot_type = output_slot_type(graph, edge.output_id, edge.output_slot);
+                let input_node_id = inner
+                    .node_graph
+                    .add_node(CoreNode::new(boundary_node_type(slot_type, WidgetSide::Input)))
+                    .ok()?;
+                inner.locations.push(Location {
+                    node_id: input_node_id,
+                    point: (
+                        left - BOUNDARY_SPACING,
+                        top + inputs.len() as f64 * BOUNDARY_SPACING,
+                    ),
+                });
+                inputs.push((source, input_node_id));
+                input_node_id
+            }
+        };
+
+        let _ = inner.node_graph.connect_arbitrary(
+            input_node_id,
+            Side::Output,
+            SlotId(0),
+            inner_id(edge.input_id)?,
+            Side::Input,
+            edge.input_slot,
+        );
+    }
+
+    let mut outputs: Vec<(NodeId, SlotId)> = Vec::new();
+    for edge in &outgoing {
+        let source = (edge.output_id, edge.output_slot);
+        if outputs.contains(&source) {
+            continue;
+        }
+
+        let slot_type = output_slot_type(graph, edge.output_id, edge.output_slot);
+        let output_node_id = inner
+            .node_graph
+            .add_node(CoreNode::new(boundary_node_type(slot_type, WidgetSide::Output)))
+            .ok()?;
+        inner.locations.push(Location {
+            node_id: output_node_id,
+            point: (right + BOUNDARY_SPACING, top + outputs.len() as f64 * BOUNDARY_SPACING),
+        });
+        outputs.push(source);
+
+        let _ = inner.node_graph.connect_arbitrary(
+            inner_id(edge.output_id)?,
+            Side::Output,
+            edge.output_slot,
+            output_node_id,
+            Side::Input,
+            SlotId(0),
+        );
+    }
+
+    let outgoing_slots: Vec<(CoreEdge, usize)> = outgoing
+        .iter()
+        .map(|edge| {
+            let source = (edge.output_id, edge.output_slot);
+            let slot = outputs.iter().position(|output| *output == source)?;
+            Some((*edge, slot))
+        })
+        .collect::<Option<_>>()?;
+
+    // Everything that can fail happens before the graph is touched, so a failed collapse leaves
+    // the graph as it was.
+    let graph_node_id = graph
+        .node_graph
+        .add_node(CoreNode::new(NodeType::Graph(inner.node_graph)))
+        .ok()?;
+    graph.remove_nodes(node_ids);
+    graph.locations.push(Location {
+        node_id: graph_node_id,
+        point: (left, top),
+    });
+    graph.node_settings.insert(
+        graph_node_id,
+        NodeSettings::Subgraph(Subgraph {
+            path: String::new(),
+            locations: inner.locations,
+            node_settings: inner.node_settings,
+        }),
+    );
+
+    for (slot, (source, _)) in inputs.iter().enumerate() {
+        let _ = graph.node_graph.connect_arbitrary(
+            source.0,
+            Side::Output,
+            source.1,
+            graph_node_id,
+            Side::Input,
+            SlotId(slot as u32),
+        );
+    }
+    for (edge, slot) in outgoing_slots {
+        let _ = graph.node_graph.connect_arbitrary(
+            graph_node_id,
+            Side::Output,
+            SlotId(slot as u32),
+            edge.input_id,
+            Side::Input,
+            edge.input_slot,
+        );
+    }
+
+    Some(graph_node_id)
+}
+
+/// Replaces a Graph node with the nodes inside it, connected straight to the nodes around it.
+/// The Graph node is left behind as an unconnected Value node, so its id is not given to another
+/// node. Returns where each output slot of the Graph node comes from now.
+pub(crate) fn inline(
+    graph: &mut NodeGraphSpatial,
+    node_id: NodeId,
+) -> Option<Vec<Option<(NodeId, SlotId)>>> {
+    let inner = enter(graph, node_id)?;
+    let id_map = append_nodes(graph, &inner, &inner.node_graph.node_ids(), (0., 0.));
+
+    // The input and output nodes inside are in the slot order of the Graph node.
+    let boundary_ids = |is_boundary: fn(&NodeType) -> bool| -> Vec<NodeId> {
+        id_map
+            .iter()
+            .filter(|(inner_id, _)| {
+                inner
+                    .node_graph
+                    .node_with_id(*inner_id)
+                    .map_or(false, |node| is_boundary(&node.node_type))
+            })
+            .map(|(_, new_node_id)| *new_node_id)
+            .collect()
+    };
+    let input_ids =
+        boundary_ids(|node_type| matches!(node_type, NodeType::InputGray | NodeType::InputRgba));
+    let output_ids =
+        boundary_ids(|node_type| matches!(node_type, NodeType::OutputGray | NodeType::OutputRgba));
+
+    let edges = &graph.node_graph.edges;
+    let source_of = |node_id: NodeId, slot_id: SlotId| {
+        edges
+            .iter()
+            .find(|edge| edge.input_id == node_id && edge.input_slot == slot_id)
+            .map(|edge| (edge.output_id, edge.output_slot))
+    };
+    // Data leaving an input node comes from whatever is connected to that slot of the Graph node.
+    let resolve = |source: (NodeId, SlotId)| {
+        let input_slot = input_ids.iter().position(|input_id| *input_id == source.0);
+        match input_slot {
+            Some(slot) => source_of(node_id, SlotId(slot as u32)),
+            None => Some(source),
+        }
+    };
+
+    let outputs: Vec<Option<(NodeId, SlotId)>> = output_ids
+        .iter()
+        .map(|output_id| source_of(*output_id, SlotId(0)).and_then(resolve))
+        .collect();
+
+    let mut new_edges: Vec<((NodeId, SlotId), (NodeId, SlotId))> = Vec::new();
+    for edge in edges {
+        let target = (edge.input_id, edge.input_slot);
+
+        if input_ids.contains(&edge.output_id) && !output_ids.contains(&edge.input_id) {
+            if let Some(source) = resolve((edge.output_id, edge.output_slot)) {
+                new_edges.push((source, target));
+            }
+        } else if edge.output_id == node_id {
+            if let Some(Some(source)) = outputs.get(edge.output_slot.0 as usize) {
+                new_edges.push((*source, target));
+            }
+        }
+    }
+
+    let boundary: Vec<NodeId> = input_ids.iter().chain(&output_ids).copied().collect();
+    graph.remove_nodes(&boundary);
+    graph.node_settings.remove(&node_id);
+    graph
+        .node_graph
+        .edges
+        .retain(|edge| edge.input_id != node_id && edge.output_id != node_id);
+    if let Some(node) = graph.node_graph.node_with_id_mut(node_id) {
+        node.node_type = NodeType::Value(0.);
+    }
+
+    for (source, target) in new_edges {
+        let _ = graph.node_graph.connect_arbitrary(
+            source.0,
+            Side::Output,
+            source.1,
+            target.0,
+            Side::Input,
+            target.1,
+        );
+    }
+
+    Some(outputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kanter_core::node::MixType;
+
+    fn add_node(graph: &mut NodeGraphSpatial, node_type: NodeType, point: (f64, f64)) -> NodeId {
+        let node_id = graph.node_graph.add_node(CoreNode::new(node_type)).unwrap();
+        graph.locations.push(Location { node_id, point });
+        node_id
+    }
+
+    fn connect(graph: &mut NodeGraphSpatial, output: (NodeId, u32), input: (NodeId, u32)) {
+        graph
+            .node_graph
+            .connect_arbitrary(
+                output.0,
+                Side::Output,
+                SlotId(output.1),
+                input.0,
+                Side::Input,
+                SlotId(input.1),
+            )
+            .unwrap();
+    }
+
+    fn has_edge(graph: &NodeGraph, output: (NodeId, u32), input: (NodeId, u32)) -> bool {
+        graph.edges.iter().any(|edge| {
+            (edge.output_id, edge.output_slot) == (output.0, SlotId(output.1))
+                && (edge.input_id, edge.input_slot) == (input.0, SlotId(input.1))
+        })
+    }
+
+    /// Two Value nodes mixed together into an output node.
+    fn mix_graph() -> (NodeGraphSpatial, [NodeId; 4]) {
+        let mut graph = NodeGraphSpatial::default();
+        let a = add_node(&mut graph, NodeType::Value(0.25), (0., 0.));
+        let b = add_node(&mut graph, NodeType::Value(0.5), (0., 100.));
+        let mix = add_node(&mut graph, NodeType::Mix(MixType::Add), (200., 0.));
+        let output = add_node(&mut graph, NodeType::OutputGray, (400., 0.));
+        connect(&mut graph, (a, 0), (mix, 0));
+        connect(&mut graph, (b, 0), (mix, 1));
+        connect(&mut graph, (mix, 0), (output, 0));
+
+        (graph, [a, b, mix, output])
+    }
+
+    #[test]
+    fn collapse_wires_the_graph_node_in_place_of_the_nodes() {
+        let (mut graph, [a, b, mix, output]) = mix_graph();
+
+        let graph_node_id = collapse(&mut graph, &[mix]).unwrap();
+
+        assert!(graph.node_graph.node_with_id(mix).is_none());
+        assert!(has_edge(&graph.node_graph, (a, 0), (graph_node_id, 0)));
+        assert!(has_edge(&graph.node_graph, (b, 0), (graph_node_id, 1)));
+        assert!(has_edge(&graph.node_graph, (graph_node_id, 0), (output, 0)));
+        assert_eq!(graph.node_graph.edges.len(), 3);
+    }
+
+    #[test]
+    fn collapse_adds_boundary_nodes_inside() {
+        let (mut graph, [_, _, mix, _]) = mix_graph();
+
+        let graph_node_id = collapse(&mut graph, &[mix]).unwrap();
+        let inner = enter(&graph, graph_node_id).unwrap();
+
+        let node_types: Vec<&NodeType> = inner
+            .node_graph
+            .node_ids()
+            .into_iter()
+            .filter_map(|node_id| inner.node_graph.node_with_id(node_id))
+            .map(|node| &node.node_type)
+            .collect();
+        let count = |is_type: fn(&NodeType) -> bool| {
+            node_types
+                .iter()
+                .filter(|node_type| is_type(node_type))
+                .count()
+        };
+
+        assert_eq!(count(|t| matches!(t, NodeType::InputGray)), 2);
+        assert_eq!(count(|t| matches!(t, NodeType::OutputGray)), 1);
+        assert_eq!(count(|t| matches!(t, NodeType::Mix(_))), 1);
+        assert_eq!(inner.node_graph.edges.len(), 3);
+        assert_eq!(inner.locations.len(), 4);
+    }
+
+    #[test]
+    fn collapse_shares_an_input_between_edges_from_one_slot() {
+        let (mut graph, [a, b, mix, _]) = mix_graph();
+        graph.node_graph.remove_edge(b, mix, SlotId(0), SlotId(1));
+        connect(&mut graph, (a, 0), (mix, 1));
+
+        let graph_node_id = collapse(&mut graph, &[mix]).unwrap();
+
+        assert!(has_edge(&graph.node_graph, (a, 0), (graph_node_id, 0)));
+        assert!(!has_edge(&graph.node_graph, (a, 0), (graph_node_id, 1)));
+    }
+
+    #[test]
+    fn collapse_nothing_leaves_the_graph_alone() {
+        let (mut graph, _) = mix_graph();
+
+        assert!(collapse(&mut graph, &[]).is_none());
+        assert_eq!(graph.node_graph.node_ids().len(), 4);
+    }
+}
@@ -0,0 +1,654 @@
+use crate::processing::{ImageData, NodeChannels};
+use orbtk::{prelude::*, shell::MouseButton};
+use std::{cell::Cell, sync::Arc};
+
+const PANEL_WIDTH: f64 = 320.;
+const CANVAS_SIZE: f64 = 300.;
+/// Keeps the panel clear of the title bar and toolbar of the main view.
+const PANEL_TOP: f64 = 60.;
+const ZOOM_STEP: f64 = 1.1;
+const ZOOM_MIN: f64 = 0.01;
+const ZOOM_MAX: f64 = 64.;
+const BACKGROUND_COLOR: Color = Color::rgb(34, 34, 34);
+
+/// The processed output of the node shown in the texture viewer.
+#[derive(Clone, Debug, Default)]
+pub struct ViewerSource {
+    pub title: String,
+    pub channels: Option<Arc<NodeChannels>>,
+}
+into_property_source!(ViewerSource);
+
+impl ViewerSource {
+    /// Whether both show the same result of the same node. The channels are compared by
+    /// identity since every processing run creates new ones.
+    pub fn is_same(&self, other: &Self) -> bool {
+        self.title == other.title
+            && match (&self.channels, &other.channels) {
+                (Some(channels), Some(other_channels)) => Arc::ptr_eq(channels, other_channels),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+/// Which channels of the combined image are shown.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum ChannelFilter {
+    Rgba,
+    Red,
+    Green,
+    Blue,
+    Alpha,
+}
+
+impl Default for ChannelFilter {
+    fn default() -> Self {
+        Self::Rgba
+    }
+}
+
+impl ChannelFilter {
+    const ALL: [ChannelFilter; 5] = [
+        ChannelFilter::Rgba,
+        ChannelFilter::Red,
+        ChannelFilter::Green,
+        ChannelFilter::Blue,
+        ChannelFilter::Alpha,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Rgba => "RGBA",
+            Self::Red => "R",
+            Self::Green => "G",
+            Self::Blue => "B",
+            Self::Alpha => "A",
+        }
+    }
+}
+
+/// A command from one of the buttons in the texture viewer.
+#[derive(Copy, Clone, Debug)]
+enum ViewerCommand {
+    Filter(ChannelFilter),
+    NextSlot,
+    ToggleTiling,
+    Fit,
+    ToggleDock,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum ViewerMouse {
+    Press(Mouse),
+    Release,
+    Move(Point),
+    Scroll(Point),
+}
+
+// Shows the full resolution output of the selected node, one output slot at a time or all of
+// them combined. The image can be zoomed with the scroll wheel, panned by dragging and repeated
+// 3x3 to check that it tiles.
+widget!(
+    TextureViewer<TextureViewerState> {
+        open: bool,
+        source: ViewerSource
+    }
+);
+
+impl Template for TextureViewer {
+    fn template(mut self, id: Entity, ctx: &mut BuildContext) -> Self {
+        let title = TextBlock::create()
+            .element("text-block")
+            .margin((5., 5., 5., 5.))
+            .text("")
+            .build(ctx);
+        let dock_button =
+            Self::command_button(ctx, id, "Dock left", 100., ViewerCommand::ToggleDock);
+        let slot_button = Self::command_button(ctx, id, "All slots", 110., ViewerCommand::NextSlot);
+        let canvas = ViewerCanvas::create()
+            .width(CANVAS_SIZE)
+            .height(CANVAS_SIZE)
+            .margin((10., 5., 10., 5.))
+            .on_mouse_down(move |states, m| {
+                states
+                    .get::<TextureViewerState>(id)
+                    .mouse(ViewerMouse::Press(m));
+                true
+            })
+            .on_mouse_up(move |states, _| {
+                states
+                    .get::<TextureViewerState>(id)
+                    .mouse(ViewerMouse::Release);
+                true
+            })
+            .on_mouse_move(move |states, p| {
+                states
+                    .get::<TextureViewerState>(id)
+                    .mouse(ViewerMouse::Move(p));
+                true
+            })
+            .on_scroll(move |states, p| {
+                states
+                    .get::<TextureViewerState>(id)
+                    .mouse(ViewerMouse::Scroll(p));
+                true
+            })
+            .build(ctx);
+        let info = TextBlock::create()
+            .element("text-block")
+            .margin((5., 2., 5., 2.))
+            .text("")
+            .build(ctx);
+        let inspector = TextBlock::create()
+            .element("text-block")
+            .margin((5., 2., 5., 2.))
+            .text("")
+            .build(ctx);
+
+        let mut filter_buttons = Stack::create().orientation("horizontal");
+        for filter in ChannelFilter::ALL.iter() {
+            let width = if *filter == ChannelFilter::Rgba {
+                60.
+            } else {
+                40.
+            };
+            filter_buttons = filter_buttons.child(Self::command_button(
+                ctx,
+                id,
+                filter.name(),
+                width,
+                ViewerCommand::Filter(*filter),
+            ));
+        }
+
+        let state = self.state_mut();
+        state.title = title;
+        state.dock_button = dock_button;
+        state.slot_button = slot_button;
+        state.canvas = canvas;
+        state.info = info;
+        state.inspector = inspector;
+
+        self.name("TextureViewer")
+            .open(false)
+            .source(ViewerSource::default())
+            .width(PANEL_WIDTH)
+            .horizontal_alignment("end")
+            .margin((0., PANEL_TOP, 0., 0.))
+            .visibility(Visibility::Collapsed)
+            .child(
+                Container::create()
+                    .background("#333333")
+                    .child(
+                        Stack::create()
+                            .orientation("vertical")
+                            .child(
+                                Stack::create()
+                                    .orientation("horizontal")
+                                    .child(dock_button)
+                                    .child(title)
+                                    .build(ctx),
+                            )
+                            .child(filter_buttons.build(ctx))
+                            .child(
+                                Stack::create()
+                                    .orientation("horizontal")
+                                    .child(slot_button)
+                                    .child(Self::command_button(
+                                        ctx,
+                                        id,
+                                        "Tile",
+                                        60.,
+                                        ViewerCommand::ToggleTiling,
+                                    ))
+                                    .child(Self::command_button(
+                                        ctx,
+                                        id,
+                                        "Fit",
+                                        60.,
+                                        ViewerCommand::Fit,
+                                    ))
+                                    .build(ctx),
+                            )
+                            .child(canvas)
+                            .child(info)
+                            .child(inspector)
+                            .build(ctx),
+                    )
+                    .build(ctx),
+            )
+    }
+}
+
+impl TextureViewer {
+    fn command_button(
+        ctx: &mut BuildContext,
+        id: Entity,
+        text: &str,
+        width: f64,
+        command: ViewerCommand,
+    ) -> Entity {
+        Button::create()
+            .element("button")
+            .on_click(move |states, _| {
+                states.get_mut::<TextureViewerState>(id).command(command);
+                true
+            })
+            .text(text)
+            .width(width)
+            .build(ctx)
+    }
+}
+
+#[derive(Default, AsAny)]
+pub struct TextureViewerState {
+    command: Option<ViewerCommand>,
+    mouse: Cell<Option<ViewerMouse>>,
+    title: Entity,
+    dock_button: Entity,
+    slot_button: Entity,
+    canvas: Entity,
+    info: Entity,
+    inspector: Entity,
+    source: ViewerSource,
+    /// Whether the image has to be rebuilt from the source.
+    stale: bool,
+    /// The output slot that is shown, or `None` for all of them combined.
+    slot: Option<usize>,
+    filter: ChannelFilter,
+    tiled: bool,
+    docked_left: bool,
+    zoom: f64,
+    offset: Point,
+    image_size: (u32, u32),
+    /// Where a pan started, and the offset at that point.
+    pan_start: Option<(Point, Point)>,
+    /// The last position of the mouse over the canvas, in window coordinates.
+    cursor: Option<Point>,
+}
+
+impl State for TextureViewerState {
+    fn init(&mut self, _: &mut Registry, _: &mut Context<'_>) {
+        self.zoom = 1.;
+        self.stale = true;
+    }
+
+    fn update(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
+        if !*ctx.widget().get::<bool>("open") {
+            ctx.widget()
+                .set::<Visibility>("visibility", Visibility::Collapsed);
+            self.pan_start = None;
+            return;
+        }
+        ctx.widget()
+            .set::<Visibility>("visibility", Visibility::Visible);
+
+        if let Some(command) = self.command.take() {
+            self.handle_command(ctx, command);
+        }
+        self.refresh_image(ctx);
+        if let Some(mouse) = self.mouse.take() {
+            self.handle_mouse(ctx, mouse);
+        }
+        self.refresh_canvas(ctx);
+    }
+}
+
+impl TextureViewerState {
+    fn command(&mut self, command: ViewerCommand) {
+        self.command = Some(command);
+    }
+
+    fn mouse(&self, mouse: ViewerMouse) {
+        self.mouse.set(Some(mouse));
+    }
+
+    fn slot_count(&self) -> usize {
+        self.source
+            .channels
+            .as_ref()
+            .map_or(0, |channels| channels.channels.len())
+    }
+
+    fn handle_command(&mut self, ctx: &mut Context, command: ViewerCommand) {
+        match command {
+            ViewerCommand::Filter(filter) => {
+                self.filter = filter;
+                self.stale = true;
+            }
+            ViewerCommand::NextSlot => {
+                self.slot = match self.slot {
+                    None if self.slot_count() > 1 => Some(0),
+                    Some(slot) if slot + 1 < self.slot_count() => Some(slot + 1),
+                    _ => None,
+                };
+                self.stale = true;
+            }
+            ViewerCommand::ToggleTiling => {
+                self.tiled = !self.tiled;
+                self.fit();
+            }
+            ViewerCommand::Fit => self.fit(),
+            ViewerCommand::ToggleDock => {
+                self.docked_left = !self.docked_left;
+
+                let (alignment, text) = if self.docked_left {
+                    (Alignment::Start, "Dock right")
+                } else {
+                    (Alignment::End, "Dock left")
+                };
+                ctx.widget()
+                    .set::<Alignment>("horizontal_alignment", alignment);
+                ctx.get_widget(self.dock_button)
+                    .set::<String16>("text", String16::from(text));
+            }
+        }
+    }
+
+    /// Zooms and centers the image so all of it fits on the canvas, all nine copies of it when
+    /// tiling.
+    fn fit(&mut self) {
+        let (width, height) = self.image_size;
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let repeats = if self.tiled { 3. } else { 1. };
+        self.zoom = CANVAS_SIZE / (width.max(height) as f64 * repeats);
+        self.offset = Point::new(
+            (CANVAS_SIZE - width as f64 * self.zoom) * 0.5,
+            (CANVAS_SIZE - height as f64 * self.zoom) * 0.5,
+        );
+    }
+
+    /// Rebuilds the image when the node, its output or what part of it is shown has changed.
+    fn refresh_image(&mut self, ctx: &mut Context) {
+        let source = ctx.widget().clone::<ViewerSource>("source");
+        if !source.is_same(&self.source) {
+            self.source = source;
+            self.stale = true;
+        }
+        if !self.stale {
+            return;
+        }
+        self.stale = false;
+
+        if self.slot.map_or(false, |slot| slot >= self.slot_count()) {
+            self.slot = None;
+        }
+
+        let image = match &self.source.channels {
+            Some(channels) => viewer_image(channels, self.slot, self.filter),
+            None => ImageData::default(),
+        };
+        if (image.width, image.height) != self.image_size {
+            self.image_size = (image.width, image.height);
+            self.fit();
+        }
+        ctx.get_widget(self.canvas).set::<ImageData>("image", image);
+
+        let title = if self.source.title.is_empty() {
+            "No node selected"
+        } else {
+            self.source.title.as_str()
+        };
+        ctx.get_widget(self.title)
+            .set::<String16>("text", String16::from(title));
+
+        let slot_text = match self.slot {
+            Some(slot) => format!("Slot {} of {}", slot + 1, self.slot_count()),
+            None => "All slots".to_string(),
+        };
+        ctx.get_widget(self.slot_button)
+            .set::<String16>("text", String16::from(slot_text));
+    }
+
+    fn handle_mouse(&mut self, ctx: &mut Context, mouse: ViewerMouse) {
+        match mouse {
+            ViewerMouse::Press(mouse) => {
+                if let MouseButton::Left | MouseButton::Middle = mouse.button {
+                    self.pan_start = Some((mouse.position, self.offset));
+                }
+            }
+            ViewerMouse::Release => self.pan_start = None,
+            ViewerMouse::Move(position) => {
+                self.cursor = Some(position);
+
+                if let Some((start, start_offset)) = self.pan_start {
+                    self.offset = Point::new(
+                        start_offset.x + position.x - start.x,
+                        start_offset.y + position.y - start.y,
+                    );
+                }
+            }
+            ViewerMouse::Scroll(delta) => {
+                let zoom = if delta.y > 0. {
+                    self.zoom * ZOOM_STEP
+                } else if delta.y < 0. {
+                    self.zoom / ZOOM_STEP
+                } else {
+                    return;
+                }
+                .max(ZOOM_MIN)
+                .min(ZOOM_MAX);
+
+                // Zoom around the cursor so the pixel under it stays in place.
+                let origin = *ctx.get_widget(self.canvas).get::<Point>("origin");
+                let pivot = self
+                    .cursor
+                    .map_or(Point::new(CANVAS_SIZE * 0.5, CANVAS_SIZE * 0.5), |cursor| {
+                        Point::new(cursor.x - origin.x, cursor.y - origin.y)
+                    });
+                let scale = zoom / self.zoom;
+                self.offset = Point::new(
+                    pivot.x - (pivot.x - self.offset.x) * scale,
+                    pivot.y - (pivot.y - self.offset.y) * scale,
+                );
+                self.zoom = zoom;
+            }
+        }
+    }
+
+    /// Returns the pixel under the cursor, if the cursor is over the image.
+    fn hovered_pixel(&self, origin: Point) -> Option<(u32, u32)> {
+        let (width, height) = self.image_size;
+        let cursor = self.cursor?;
+        let x = ((cursor.x - origin.x - self.offset.x) / self.zoom).floor();
+        let y = ((cursor.y - origin.y - self.offset.y) / self.zoom).floor();
+        let (width_f, height_f) = (width as f64, height as f64);
+
+        let (range_min, range_max) = if self.tiled { (-1., 2.) } else { (0., 1.) };
+        if x < width_f * range_min
+            || x >= width_f * range_max
+            || y < height_f * range_min
+            || y >= height_f * range_max
+        {
+            return None;
+        }
+
+        Some((x.rem_euclid(width_f) as u32, y.rem_euclid(height_f) as u32))
+    }
+
+    /// Passes the zoom and pan to the canvas, and describes the image and the pixel under the
+    /// cursor.
+    fn refresh_canvas(&mut self, ctx: &mut Context) {
+        let origin = {
+            let mut canvas = ctx.get_widget(self.canvas);
+            canvas.set::<f64>("zoom", self.zoom);
+            canvas.set::<Point>("offset", self.offset);
+            canvas.set::<bool>("tiled", self.tiled);
+            *canvas.get::<Point>("origin")
+        };
+
+        let (width, height) = self.image_size;
+        let info = if width == 0 {
+            String::new()
+        } else {
+            format!(
+                "{} x {}, {}, {:.0}%",
+                width,
+                height,
+                self.filter.name(),
+                self.zoom * 100.
+            )
+        };
+
+        let inspector = match (&self.source.channels, self.hovered_pixel(origin)) {
+            (Some(channels), Some((x, y))) => {
+                let i = (y * width + x) as usize;
+
+                match self.slot {
+                    Some(slot) => format!("{}, {}: {:.3}", x, y, channels.channels[slot][i]),
+                    None => {
+                        let (r, g, b, a) = channels.rgba(i);
+                        format!("{}, {}: R {:.3} G {:.3} B {:.3} A {:.3}", x, y, r, g, b, a)
+                    }
+                }
+            }
+            _ => String::new(),
+        };
+
+        if ctx
+            .get_widget(self.info)
+            .get::<String16>("text")
+            .to_string()
+            != info
+        {
+            ctx.get_widget(self.info)
+                .set::<String16>("text", String16::from(info));
+        }
+        if ctx
+            .get_widget(self.inspector)
+            .get::<String16>("text")
+            .to_string()
+            != inspector
+        {
+            ctx.get_widget(self.inspector)
+                .set::<String16>("text", String16::from(inspector));
+        }
+    }
+}
+
+/// Builds the image to show for a node. A single slot is shown as grayscale, otherwise the
+/// slots are combined and the filter picks which of the combined channels are shown.
+fn viewer_image(channels: &NodeChannels, slot: Option<usize>, filter: ChannelFilter) -> ImageData {
+    if let Some(slot) = slot {
+        let values = &channels.channels[slot];
+        return channels.image_with(|i| (values[i], values[i], values[i], 1.));
+    }
+
+    match filter {
+        ChannelFilter::Rgba => channels.image(),
+        ChannelFilter::Red => channels.image_with(|i| {
+            let (r, _, _, _) = channels.rgba(i);
+            (r, r, r, 1.)
+        }),
+        ChannelFilter::Green => channels.image_with(|i| {
+            let (_, g, _, _) = channels.rgba(i);
+            (g, g, g, 1.)
+        }),
+        ChannelFilter::Blue => channels.image_with(|i| {
+            let (_, _, b, _) = channels.rgba(i);
+            (b, b, b, 1.)
+        }),
+        ChannelFilter::Alpha => channels.image_with(|i| {
+            let (_, _, _, a) = channels.rgba(i);
+            (a, a, a, 1.)
+        }),
+    }
+}
+
+widget!(
+    ViewerCanvas<ViewerCanvasState>: MouseHandler {
+        image: ImageData,
+        zoom: f64,
+        offset: Point,
+        tiled: bool,
+        origin: Point
+    }
+);
+
+impl Template for ViewerCanvas {
+    fn template(self, _id: Entity, _ctx: &mut BuildContext) -> Self {
+        self.name("ViewerCanvas")
+            .image(ImageData::default())
+            .zoom(1.)
+            .offset(Point::default())
+            .tiled(false)
+            .origin(Point::default())
+    }
+
+    fn render_object(&self) -> Box<dyn RenderObject> {
+        Box::new(ViewerCanvasRenderObject)
+    }
+}
+
+#[derive(AsAny, Default)]
+pub struct ViewerCanvasState {}
+
+impl State for ViewerCanvasState {}
+
+pub struct ViewerCanvasRenderObject;
+
+impl RenderObject for ViewerCanvasRenderObject {
+    fn render_self(&self, ctx: &mut Context<'_>, global_position: &Point) {
+        let (bounds, image_data, zoom, offset, tiled, origin) = {
+            let widget = ctx.widget();
+            (
+                *widget.get::<Rectangle>("bounds"),
+                widget.get::<ImageData>("image").clone(),
+                *widget.get::<f64>("zoom"),
+                *widget.get::<Point>("offset"),
+                *widget.get::<bool>("tiled"),
+                *widget.get::<Point>("origin"),
+            )
+        };
+
+        let left = global_position.x + bounds.x();
+        let top = global_position.y + bounds.y();
+
+        // Mouse positions arrive in window coordinates, the state needs to know where the
+        // canvas is to find the pixel under the cursor.
+        if origin != Point::new(left, top) {
+            ctx.widget().set::<Point>("origin", Point::new(left, top));
+        }
+
+        let rc2d = ctx.render_context_2_d();
+        rc2d.set_fill_style(Brush::SolidColor(BACKGROUND_COLOR));
+        rc2d.fill_rect(left, top, bounds.width(), bounds.height());
+
+        if image_data.data.is_empty() {
+            return;
+        }
+
+        let image =
+            match Image::from_data(image_data.width, image_data.height, image_data.data.clone()) {
+                Ok(image) => image,
+                Err(_) => return,
+            };
+
+        let width = image_data.width as f64 * zoom;
+        let height = image_data.height as f64 * zoom;
+        let repeats = if tiled { -1..=1 } else { 0..=0 };
+
+        rc2d.save();
+        rc2d.begin_path();
+        rc2d.rect(left, top, bounds.width(), bounds.height());
+        rc2d.clip();
+
+        for y in repeats.clone() {
+            for x in repeats.clone() {
+                rc2d.draw_image_with_size(
+                    &image,
+                    left + offset.x + x as f64 * width,
+                    top + offset.y + y as f64 * height,
+                    width,
+                    height,
+                );
+            }
+        }
+
+        rc2d.restore();
+    }
+}
@@ -0,0 +1,60 @@
+use crate::processing::ImageData;
+use orbtk::prelude::*;
+
+widget!(
+    Thumbnail<ThumbnailState> {
+        image: ImageData
+    }
+);
+
+impl Template for Thumbnail {
+    fn template(self, _id: Entity, _ctx: &mut BuildContext) -> Self {
+        self.name("Thumbnail").image(ImageData::default())
+    }
+
+    fn render_object(&self) -> Box<dyn RenderObject> {
+        Box::new(ThumbnailRenderObject)
+    }
+}
+
+#[derive(AsAny, Default)]
+pub struct ThumbnailState {}
+
+impl State for ThumbnailState {}
+
+pub struct ThumbnailRenderObject;
+
+impl RenderObject for ThumbnailRenderObject {
+    fn render_self(&self, ctx: &mut Context<'_>, global_position: &Point) {
+        let (bounds, image_data) = {
+            let widget = ctx.widget();
+            (
+                *widget.get::<Rectangle>("bounds"),
+                widget.get::<ImageData>("image").clone(),
+            )
+        };
+
+        if image_data.data.is_empty() {
+            return;
+        }
+
+        let image = match Image::from_data(
+            image_data.width,
+            image_data.height,
+            image_data.data.clone(),
+        ) {
+            Ok(image) => image,
+            Err(_) => return,
+        };
+
+        // Fit the image inside the bounds and center it in case it is not square.
+        let scale = (bounds.width() / image_data.width as f64)
+            .min(bounds.height() / image_data.height as f64);
+        let width = image_data.width as f64 * scale;
+        let height = image_data.height as f64 * scale;
+        let x = global_position.x + bounds.x() + (bounds.width() - width) * 0.5;
+        let y = global_position.y + bounds.y() + (bounds.height() - height) * 0.5;
+
+        ctx.render_context_2_d().draw_image_with_size(&image, x, y, width, height);
+    }
+}
@@ -0,0 +1,125 @@
+use orbtk::prelude::*;
+
+const ZOOM_MIN: f64 = 0.25;
+const ZOOM_MAX: f64 = 2.;
+const FRAME_PADDING: f64 = 50.;
+
+/// Maps between graph space, where node locations are stored, and screen space, where widgets
+/// are laid out and the mouse is.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct View {
+    pub offset: Point,
+    pub zoom: f64,
+}
+into_property_source!(View);
+
+impl Default for View {
+    fn default() -> Self {
+        Self {
+            offset: Point::new(0., 0.),
+            zoom: 1.,
+        }
+    }
+}
+
+impl View {
+    pub fn to_screen(&self, point: Point) -> Point {
+        Point::new(
+            point.x * self.zoom + self.offset.x,
+            point.y * self.zoom + self.offset.y,
+        )
+    }
+
+    pub fn to_graph(&self, point: Point) -> Point {
+        Point::new(
+            (point.x - self.offset.x) / self.zoom,
+            (point.y - self.offset.y) / self.zoom,
+        )
+    }
+
+    /// Converts a margin in graph space to a margin in screen space.
+    pub fn thickness_to_screen(&self, thickness: Thickness) -> Thickness {
+        let point = self.to_screen(Point::new(thickness.left, thickness.top));
+
+        Thickness {
+            left: point.x,
+            top: point.y,
+            right: thickness.right,
+            bottom: thickness.bottom,
+        }
+    }
+
+    /// Moves the view by the given distance in screen space.
+    pub fn pan(&mut self, delta: Point) {
+        self.offset.x += delta.x;
+        self.offset.y += delta.y;
+    }
+
+    /// Multiplies the zoom by `factor` while keeping the graph point under `screen_point` in
+    /// place.
+    pub fn zoom_around(&mut self, screen_point: Point, factor: f64) {
+        let graph_point = self.to_graph(screen_point);
+        self.zoom = (self.zoom * factor).max(ZOOM_MIN).min(ZOOM_MAX);
+
+        self.offset = Point::new(
+            screen_point.x - graph_point.x * self.zoom,
+            screen_point.y - graph_point.y * self.zoom,
+        );
+    }
+
+    /// Zooms and pans so `graph_rect` fills `viewport`.
+    pub fn frame(&mut self, graph_rect: Rectangle, viewport: Rectangle) {
+        let available_width = (viewport.width() - FRAME_PADDING * 2.).max(1.);
+        let available_height = (viewport.height() - FRAME_PADDING * 2.).max(1.);
+
+        self.zoom = (available_width / graph_rect.width().max(1.))
+            .min(available_height / graph_rect.height().max(1.))
+            .max(ZOOM_MIN)
+            .min(ZOOM_MAX);
+
+        let graph_center = Point::new(
+            graph_rect.x() + graph_rect.width() * 0.5,
+            graph_rect.y() + graph_rect.height() * 0.5,
+        );
+        let viewport_center = Point::new(
+            viewport.x() + viewport.width() * 0.5,
+            viewport.y() + viewport.height() * 0.5,
+        );
+
+        self.offset = Point::new(
+            viewport_center.x - graph_center.x * self.zoom,
+            viewport_center.y - graph_center.y * self.zoom,
+        );
+    }
+}
+
+/// Returns the smallest rectangle containing all the given rectangles.
+pub fn union_rectangles(rectangles: &[Rectangle]) -> Option<Rectangle> {
+    let first = rectangles.first()?;
+
+    let mut left = first.x();
+    let mut top = first.y();
+    let mut right = first.x() + first.width();
+    let mut bottom = first.y() + first.height();
+
+    for rectangle in &rectangles[1..] {
+        left = left.min(rectangle.x());
+        top = top.min(rectangle.y());
+        right = right.max(rectangle.x() + rectangle.width());
+        bottom = bottom.max(rectangle.y() + rectangle.height());
+    }
+
+    Some(Rectangle::new(left, top, right - left, bottom - top))
+}
+
+/// Returns the rectangle spanned by two opposite corners.
+pub fn rectangle_from_points(a: Point, b: Point) -> Rectangle {
+    Rectangle::new(a.x.min(b.x), a.y.min(b.y), (a.x - b.x).abs(), (a.y - b.y).abs())
+}
+
+pub fn rectangles_intersect(a: &Rectangle, b: &Rectangle) -> bool {
+    a.x() <= b.x() + b.width()
+        && b.x() <= a.x() + a.width()
+        && a.y() <= b.y() + b.height()
+        && b.y() <= a.y() + a.height()
+}
@@ -1,149 +1,98 @@
-use crate::{node_container::NodeContainer, shared::*};
-use kanter_core::node::{MixType, NodeType};
+use crate::{
+    channels::OptionChannelNodeKind,
+    file_browser::{FileBrowserMode, OptionFileBrowserMode},
+    filter::OptionFilter,
+    generator::OptionGeneratorType,
+    keymap::{Command, KeyChord, Keymap},
+    layout::{Alignment, Axis},
+    node_container::NodeContainer,
+    node_registry::{search, NodeEntry},
+    rasterizer::Material,
+    shared::*,
+    texture_viewer::ViewerSource,
+};
 use orbtk::{
     prelude::*,
     shell::{ButtonState, Key},
 };
 use std::cell::Cell;
 
+const PALETTE_WIDTH: f64 = 200.;
+
 widget!(Workspace<WorkspaceState>: MouseHandler, KeyDownHandler {
     action_main: OptionActionMain,
-    focused: bool
+    focused: bool,
+    error_message: String16,
+    graph_path: String16,
+    modified: bool,
+    browse_request: OptionFileBrowserMode,
+    viewer_source: ViewerSource,
+    preview_material: Material
 });
 
 impl Template for Workspace {
     fn template(mut self, id: Entity, ctx: &mut BuildContext) -> Self {
-        let node_container = NodeContainer::create().build(ctx);
+        let node_container = NodeContainer::create()
+            .error_message(("error_message", id))
+            .graph_path(("graph_path", id))
+            .modified(("modified", id))
+            .browse_request(("browse_request", id))
+            .viewer_source(("viewer_source", id))
+            .preview_material(("preview_material", id))
+            .build(ctx);
         self.state_mut().node_container = node_container;
 
-        let menu_node = Popup::create()
-            .margin(Thickness {
-                left: 0.,
-                top: 30.,
-                right: 0.,
-                bottom: 0.,
-            })
-            .width(200.)
+        let palette_search = TextBox::create()
+            .water_mark("Search...")
+            .text("")
+            .build(ctx);
+        self.state_mut().palette_search = palette_search;
+
+        let palette_list = Stack::create().orientation("vertical").build(ctx);
+        self.state_mut().palette_list = palette_list;
+
+        let palette = Popup::create()
+            .width(PALETTE_WIDTH)
             .target(id)
+            .child(
+                Stack::create()
+                    .orientation("vertical")
+                    .child(palette_search)
+                    .child(palette_list)
+                    .build(ctx),
+            )
+            .build(ctx);
+        self.state_mut().palette = palette;
+
+        let shortcuts_list = Stack::create().orientation("vertical").build(ctx);
+        self.state_mut().shortcuts_list = shortcuts_list;
+
+        let shortcuts = Container::create()
+            .horizontal_alignment("center")
+            .vertical_alignment("center")
+            .background("#444444")
+            .visibility(Visibility::Collapsed)
             .child(
                 Stack::create()
                     .orientation("vertical")
                     .child(
-                        Button::create()
-                            .element("button")
-                            .on_click(move |states, _| {
-                                states
-                                    .get_mut::<WorkspaceState>(id)
-                                    .add_node(NodeType::Mix(MixType::default()));
-                                true
-                            })
-                            .text("Mix")
-                            .build(ctx),
-                    )
-                    .child(
-                        Button::create()
-                            .element("button")
-                            .on_click(move |states, _| {
-                                states
-                                    .get_mut::<WorkspaceState>(id)
-                                    .add_node(NodeType::Value(0.));
-                                true
-                            })
-                            .text("Value")
-                            .build(ctx),
-                    )
-                    .child(
-                        Button::create()
-                            .element("button")
-                            .on_click(move |states, _| {
-                                states
-                                    .get_mut::<WorkspaceState>(id)
-                                    .add_node(NodeType::Resize(None, None));
-                                true
-                            })
-                            .text("Resize")
-                            .build(ctx),
-                    )
-                    .child(
-                        Button::create()
-                            .element("button")
-                            .on_click(move |states, _| {
-                                states
-                                    .get_mut::<WorkspaceState>(id)
-                                    .add_node(NodeType::HeightToNormal);
-                                true
-                            })
-                            .text("HeightToNormal")
-                            .build(ctx),
-                    )
-                    .child(
-                        Button::create()
-                            .element("button")
-                            .on_click(move |states, _| {
-                                states
-                                    .get_mut::<WorkspaceState>(id)
-                                    .add_node(NodeType::Image(String::new()));
-                                true
-                            })
-                            .text("Image")
-                            .build(ctx),
-                    )
-                    // .child(
-                    //     Button::create()
-                    //         .element("button")
-                    //         .on_click(move |states, _| {
-                    //             states
-                    //                 .get_mut::<WorkspaceState>(id)
-                    //                 .add_node(NodeType::InputGray);
-                    //             true
-                    //         })
-                    //         .text("InputGray")
-                    //         .build(ctx),
-                    // )
-                    // .child(
-                    //     Button::create()
-                    //         .element("button")
-                    //         .on_click(move |states, _| {
-                    //             states
-                    //                 .get_mut::<WorkspaceState>(id)
-                    //                 .add_node(NodeType::InputRgba);
-                    //             true
-                    //         })
-                    //         .text("InputRgba")
-                    //         .build(ctx),
-                    // )
-                    .child(
-                        Button::create()
-                            .element("button")
-                            .on_click(move |states, _| {
-                                states
-                                    .get_mut::<WorkspaceState>(id)
-                                    .add_node(NodeType::OutputGray);
-                                true
-                            })
-                            .text("OutputGray")
+                        TextBlock::create()
+                            .text("Shortcuts")
+                            .element("text-block")
+                            .margin((10., 10., 10., 5.))
                             .build(ctx),
                     )
-                    // .child(
-                    //     Button::create()
-                    //         .element("button")
-                    //         .on_click(move |states, _| {
-                    //             states
-                    //                 .get_mut::<WorkspaceState>(id)
-                    //                 .add_node(NodeType::OutputRgba);
-                    //             true
-                    //         })
-                    //         .text("OutputRgba")
-                    //         .build(ctx),
-                    // )
+                    .child(shortcuts_list)
                     .build(ctx),
             )
             .build(ctx);
-        self.state_mut().menu_node = menu_node;
+        self.state_mut().shortcuts = shortcuts;
 
         self.name("Workspace")
             .on_mouse_move(move |states, p| {
-                states.get::<WorkspaceState>(id).action(Action::Move(p));
+                let workspace_state = states.get::<WorkspaceState>(id);
+                workspace_state.mouse_position.set(p);
+                workspace_state.action(Action::Move(p));
                 false
             })
             .on_mouse_down(move |states, m| {
@@ -154,23 +103,40 @@ impl Template for Workspace {
                 states.get::<WorkspaceState>(id).action(Action::Release(m));
                 false
             })
+            .on_scroll(move |states, p| {
+                states.get::<WorkspaceState>(id).action(Action::Scroll(p));
+                false
+            })
             .on_key_down(move |states, event| -> bool {
-                if event.key == Key::Delete && event.state == ButtonState::Down {
-                    states.get_mut::<WorkspaceState>(id).action(Action::Delete);
+                if event.state == ButtonState::Down {
+                    states.get::<WorkspaceState>(id).key_down(event.key);
                 }
                 false
             })
             .child(node_container)
-            .child(menu_node)
+            .child(palette)
+            .child(shortcuts)
     }
 }
 
 #[derive(Default, AsAny)]
 struct WorkspaceState {
     action: Cell<OptionAction>,
+    key_down: Cell<Option<Key>>,
+    mouse_position: Cell<Point>,
     node_container: Entity,
-    menu_node: Entity,
+    palette: Entity,
+    palette_search: Entity,
+    palette_list: Entity,
+    palette_filter: Option<String>,
+    palette_position: Point,
     add_node: OptionNodeType,
+    add_generator: OptionGeneratorType,
+    add_filter: OptionFilter,
+    add_channels: OptionChannelNodeKind,
+    keymap: Keymap,
+    shortcuts: Entity,
+    shortcuts_list: Entity,
 }
 
 impl State for WorkspaceState {
@@ -178,32 +144,177 @@ impl State for WorkspaceState {
         ctx.parent()
             .set::<u32>("node_container_entity", self.node_container.0);
         ctx.push_event_by_window(FocusEvent::RequestFocus(ctx.entity));
+
+        self.keymap = match Keymap::load() {
+            Ok(keymap) => keymap,
+            Err(e) => {
+                ctx.widget().set::<String16>(
+                    "error_message",
+                    String16::from(format!("Could not load keymap, using the defaults: {}", e)),
+                );
+                Keymap::default()
+            }
+        };
+        self.fill_shortcuts(ctx);
     }
 
     fn update(&mut self, _: &mut Registry, ctx: &mut Context<'_>) {
         self.handle_action_main(ctx);
+        self.handle_key_down(ctx);
+        self.refresh_palette(ctx);
         self.propagate_action(ctx);
     }
 }
 
 impl WorkspaceState {
-    fn add_node(&mut self, node_type: NodeType) {
-        self.add_node = Some(node_type);
+    fn add_node(&mut self, node_entry: &NodeEntry) {
+        self.add_node = Some(node_entry.node_type.clone());
+        self.add_generator = node_entry.generator;
+        self.add_filter = node_entry.filter.clone();
+        self.add_channels = node_entry.channels;
     }
 
     fn action(&self, action: Action) {
         self.action.set(Some(action));
     }
 
+    fn key_down(&self, key: Key) {
+        self.key_down.set(Some(key));
+    }
+
+    /// Turns the most recent key press into an `Action`, taking modifier keys into account.
+    fn handle_key_down(&mut self, ctx: &mut Context) {
+        let key = match self.key_down.take() {
+            Some(key) => key,
+            None => return,
+        };
+
+        if self.is_palette_open(ctx) {
+            match key {
+                Key::Escape => self.close_palette(ctx),
+                Key::Enter => self.add_first_match(ctx),
+                _ => {}
+            }
+            return;
+        }
+
+        if let Key::Escape = key {
+            if self.is_shortcuts_open(ctx) {
+                self.toggle_shortcuts(ctx);
+                return;
+            }
+        }
+
+        let command = KeyChord::new(key, is_ctrl_down(ctx), is_shift_down(ctx))
+            .and_then(|chord| self.keymap.command(&chord));
+        if let Some(command) = command {
+            self.run_command(ctx, command);
+        }
+    }
+
+    fn run_command(&mut self, ctx: &mut Context, command: Command) {
+        match command {
+            Command::Open => {
+                Self::action_main_to_parent(ctx, ActionMain::Browse(FileBrowserMode::Open))
+            }
+            Command::OpenRecent => Self::action_main_to_parent(ctx, ActionMain::ToggleRecentFiles),
+            Command::Save => Self::action_main_to_parent(ctx, ActionMain::Save),
+            Command::SaveAs => {
+                Self::action_main_to_parent(ctx, ActionMain::Browse(FileBrowserMode::Save))
+            }
+            Command::ExportOutputs => {
+                Self::action_main_to_parent(ctx, ActionMain::ExportOutputs(String::new()))
+            }
+            Command::Quit => Self::action_main_to_parent(ctx, ActionMain::Quit),
+            Command::AddNode => self.open_palette(ctx, self.mouse_position.get()),
+            Command::ShowShortcuts => self.toggle_shortcuts(ctx),
+            Command::ToggleViewer => Self::action_main_to_parent(ctx, ActionMain::ToggleViewer),
+            Command::TogglePreview => Self::action_main_to_parent(ctx, ActionMain::TogglePreview),
+            Command::Delete => self.action(Action::Delete),
+            Command::Duplicate => self.action(Action::Duplicate),
+            Command::Copy => self.action(Action::Copy),
+            Command::Cut => self.action(Action::Cut),
+            Command::Paste => self.action(Action::Paste),
+            Command::Undo => self.action(Action::Undo),
+            Command::Redo => self.action(Action::Redo),
+            Command::FrameAll => self.action(Action::FrameAll),
+            Command::FrameSelected => self.action(Action::FrameSelected),
+            Command::CollapseSelection => self.action(Action::CollapseSelection),
+            Command::EnterSubgraph => self.action(Action::EnterSubgraph),
+            Command::LeaveSubgraph => self.action(Action::LeaveSubgraph),
+            Command::ToggleGrid => self.action(Action::ToggleGrid),
+            Command::AlignLeft => self.action(Action::Align(Alignment::Left)),
+            Command::AlignRight => self.action(Action::Align(Alignment::Right)),
+            Command::AlignTop => self.action(Action::Align(Alignment::Top)),
+            Command::AlignBottom => self.action(Action::Align(Alignment::Bottom)),
+            Command::DistributeHorizontally => self.action(Action::Distribute(Axis::Horizontal)),
+            Command::DistributeVertically => self.action(Action::Distribute(Axis::Vertical)),
+            Command::AutoLayout => self.action(Action::AutoLayout),
+        }
+    }
+
+    /// Sends an action to the main view, for commands that are also buttons there.
+    fn action_main_to_parent(ctx: &mut Context, action_main: ActionMain) {
+        ctx.parent()
+            .set::<OptionActionMain>("action_main", Some(action_main));
+    }
+
+    fn is_shortcuts_open(&self, ctx: &mut Context) -> bool {
+        *ctx.get_widget(self.shortcuts).get::<Visibility>("visibility") == Visibility::Visible
+    }
+
+    fn toggle_shortcuts(&mut self, ctx: &mut Context) {
+        let visibility = if self.is_shortcuts_open(ctx) {
+            Visibility::Collapsed
+        } else {
+            Visibility::Visible
+        };
+
+        ctx.get_widget(self.shortcuts)
+            .set::<Visibility>("visibility", visibility);
+    }
+
+    /// Lists every command with the keys bound to it in the shortcut overview.
+    fn fill_shortcuts(&mut self, ctx: &mut Context) {
+        ctx.clear_children_of(self.shortcuts_list);
+
+        let shortcuts_list = self.shortcuts_list;
+        let rows: Vec<String> = Command::ALL
+            .iter()
+            .map(|command| {
+                let chords = self.keymap.chords(*command);
+                let chords = if chords.is_empty() {
+                    "Not bound".to_string()
+                } else {
+                    chords.join(", ")
+                };
+
+                format!("{}: {}", command.description(), chords)
+            })
+            .collect();
+
+        let bc = &mut ctx.build_context();
+        for row in rows {
+            let text_block = TextBlock::create()
+                .text(row)
+                .element("text-block")
+                .margin((10., 2., 10., 2.))
+                .build(bc);
+            bc.append_child(shortcuts_list, text_block);
+        }
+    }
+
     fn handle_action_main(&mut self, ctx: &mut Context) {
-        if let Some(action_main) = ctx.widget().get::<OptionActionMain>("action_main") {
+        if let Some(action_main) = ctx.widget().get::<OptionActionMain>("action_main").clone() {
             match action_main {
-                ActionMain::MenuNode(_) => {
-                    let current_open = *ctx.get_widget(self.menu_node).get::<bool>("open");
-
-                    let mut menu_node_widget = ctx.get_widget(self.menu_node);
-                    menu_node_widget.set::<bool>("open", !current_open);
+                ActionMain::MenuNode(p) => {
+                    if self.is_palette_open(ctx) {
+                        self.close_palette(ctx);
+                    } else {
+                        self.open_palette(ctx, p);
+                    }
                 }
+                ActionMain::ShowShortcuts => self.toggle_shortcuts(ctx),
                 _ => {}
             };
         }
@@ -211,12 +322,98 @@ impl WorkspaceState {
         ctx.widget().set::<OptionActionMain>("action_main", None);
     }
 
+    fn is_palette_open(&self, ctx: &mut Context) -> bool {
+        *ctx.get_widget(self.palette).get::<bool>("open")
+    }
+
+    /// Opens the node palette at `position` in screen space, new nodes are placed there.
+    fn open_palette(&mut self, ctx: &mut Context, position: Point) {
+        self.palette_position = position;
+
+        let mut palette_widget = ctx.get_widget(self.palette);
+        palette_widget.set::<Thickness>(
+            "margin",
+            Thickness {
+                left: position.x,
+                top: position.y,
+                right: 0.,
+                bottom: 0.,
+            },
+        );
+        palette_widget.set::<bool>("open", true);
+
+        ctx.get_widget(self.palette_search)
+            .set::<String16>("text", String16::default());
+        ctx.push_event_by_window(FocusEvent::RequestFocus(self.palette_search));
+    }
+
+    fn close_palette(&mut self, ctx: &mut Context) {
+        ctx.get_widget(self.palette).set::<bool>("open", false);
+        ctx.push_event_by_window(FocusEvent::RequestFocus(ctx.entity));
+    }
+
+    fn add_first_match(&mut self, ctx: &mut Context) {
+        let filter = ctx.get_widget(self.palette_search).get::<String16>("text").to_string();
+
+        if let Some(node_entry) = search(&filter).into_iter().next() {
+            self.add_node(&node_entry);
+        }
+    }
+
+    /// Rebuilds the list of nodes in the palette when the search text has changed.
+    fn refresh_palette(&mut self, ctx: &mut Context) {
+        let filter = ctx.get_widget(self.palette_search).get::<String16>("text").to_string();
+        if self.palette_filter.as_ref() == Some(&filter) {
+            return;
+        }
+
+        ctx.clear_children_of(self.palette_list);
+
+        let id = ctx.entity;
+        let palette_list = self.palette_list;
+        let bc = &mut ctx.build_context();
+
+        let mut category = "";
+        for node_entry in search(&filter) {
+            if node_entry.category != category {
+                category = node_entry.category;
+
+                let header = TextBlock::create()
+                    .text(category)
+                    .element("text-block")
+                    .margin((4., 4., 4., 0.))
+                    .build(bc);
+                bc.append_child(palette_list, header);
+            }
+
+            let name = node_entry.name;
+            let button = Button::create()
+                .element("button")
+                .on_click(move |states, _| {
+                    states.get_mut::<WorkspaceState>(id).add_node(&node_entry);
+                    true
+                })
+                .text(name)
+                .build(bc);
+            bc.append_child(palette_list, button);
+        }
+
+        self.palette_filter = Some(filter);
+    }
+
     fn propagate_action(&mut self, ctx: &mut Context) {
         if self.add_node.is_some() {
-            ctx.get_widget(self.node_container)
-                .set::<OptionNodeType>("add_node", self.add_node.clone());
+            let palette_position = self.palette_position;
+            let mut node_container_widget = ctx.get_widget(self.node_container);
+            node_container_widget.set::<Point>("add_node_position", palette_position);
+            node_container_widget.set::<OptionNodeType>("add_node", self.add_node.clone());
+            node_container_widget.set::<OptionGeneratorType>("add_generator", self.add_generator);
+            node_container_widget.set::<OptionFilter>("add_filter", self.add_filter.take());
+            node_container_widget
+                .set::<OptionChannelNodeKind>("add_channels", self.add_channels.take());
             self.add_node = None;
-            ctx.get_widget(self.menu_node).set::<bool>("open", false);
+            self.add_generator = None;
+            self.close_palette(ctx);
         } else {
             ctx.get_widget(self.node_container)
                 .set::<OptionAction>("action", self.action.get());